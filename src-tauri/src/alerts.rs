@@ -0,0 +1,393 @@
+//! Per-site alert rules, evaluated after each background refresh. A rule that starts
+//! firing is an incident: it notifies once, not on every refresh while it lasts. Nothing
+//! is sent during quiet hours (an incident still going when they end notifies then), or
+//! within a rule's cooldown of its last notification, so a flapping site stays quiet.
+
+use crate::budget::metered_client;
+use crate::client::CloudflareClient;
+use crate::commands::{default_true, fetch_sites, fetch_visits_between, get_settings, save_settings, timezone, Settings};
+use crate::errors::FetchError;
+use chrono::{DateTime, Duration, NaiveTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+/// What a rule watches for, measured over the last hour unless it says otherwise.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum AlertCondition {
+    /// More than `visits` visits in the last hour.
+    VisitsAbove { visits: u64 },
+    /// Fewer than `visits` visits in the last hour.
+    VisitsBelow { visits: u64 },
+    /// Not a single visit in the last `hours` hours.
+    NoTraffic { hours: u32 },
+    /// Last hour's visits at least `percent` below the same hour a week earlier.
+    DropVsLastWeek { percent: u32 },
+}
+
+impl AlertCondition {
+    /// How far back the condition looks.
+    fn window(&self) -> Duration {
+        match self {
+            AlertCondition::NoTraffic { hours } => Duration::hours(*hours as i64),
+            _ => Duration::hours(1),
+        }
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        match self {
+            AlertCondition::NoTraffic { hours } if !(1..=24 * 7).contains(hours) => {
+                Err("No-traffic alerts need between 1 and 168 hours".to_string())
+            }
+            AlertCondition::DropVsLastWeek { percent } if !(1..=100).contains(percent) => {
+                Err("Drop alerts need a percentage between 1 and 100".to_string())
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AlertRule {
+    /// Assigned by `save_alert_rule` when empty.
+    #[serde(default)]
+    pub id: String,
+    pub site: String,
+    pub condition: AlertCondition,
+    /// Minimum time between two notifications of this rule.
+    #[serde(default = "default_cooldown_mins")]
+    pub cooldown_mins: u64,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+fn default_cooldown_mins() -> u64 {
+    60
+}
+
+/// Local times, e.g. "22:00" to "07:00", in `Settings::timezone`. `end` before `start`
+/// spans midnight.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct QuietHours {
+    pub start: String,
+    pub end: String,
+}
+
+fn parse_time(time: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(time.trim(), "%H:%M").map_err(|_| format!("Invalid time \"{}\": use e.g. 22:00", time))
+}
+
+impl QuietHours {
+    fn contains(&self, time: NaiveTime) -> bool {
+        let (Ok(start), Ok(end)) = (parse_time(&self.start), parse_time(&self.end)) else {
+            return false;
+        };
+        if start <= end {
+            start <= time && time < end
+        } else {
+            time >= start || time < end
+        }
+    }
+}
+
+/// Rejects rules and quiet hours `check_rules` couldn't evaluate; part of `save_settings`.
+pub(crate) fn validate(settings: &Settings) -> Result<(), String> {
+    for rule in &settings.alert_rules {
+        rule.condition.validate().map_err(|e| format!("{}: {}", rule.site, e))?;
+    }
+    if let Some(quiet) = &settings.alert_quiet_hours {
+        parse_time(&quiet.start)?;
+        parse_time(&quiet.end)?;
+    }
+    Ok(())
+}
+
+/// Where each rule's current incident stands, by rule ID.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub struct RuleState {
+    /// The condition held at the last check.
+    pub firing: bool,
+    /// This incident has been notified.
+    pub notified: bool,
+}
+
+pub struct AlertRuleState(pub Mutex<HashMap<String, RuleState>>);
+
+/// The notification text if `condition` holds for `visits` over its window, and for
+/// `DropVsLastWeek` the visits of the same hour a week earlier.
+fn verdict(condition: &AlertCondition, visits: u64, last_week: Option<u64>) -> Option<String> {
+    match *condition {
+        AlertCondition::VisitsAbove { visits: limit } if visits > limit => {
+            Some(format!("{} visits in the last hour, above {}", visits, limit))
+        }
+        AlertCondition::VisitsBelow { visits: limit } if visits < limit => {
+            Some(format!("{} visits in the last hour, below {}", visits, limit))
+        }
+        AlertCondition::NoTraffic { hours } if visits == 0 => Some(format!("No visits in the last {} hours", hours)),
+        AlertCondition::DropVsLastWeek { percent } => {
+            let before = last_week.filter(|&v| v > 0)?;
+            let drop = 100.0 - visits as f64 / before as f64 * 100.0;
+            (drop >= percent as f64).then(|| {
+                format!("{} visits in the last hour, down {:.0}% from {} a week ago", visits, drop, before)
+            })
+        }
+        _ => None,
+    }
+}
+
+/// Update `state` with whether the rule is `firing` and decide whether to notify: once
+/// per incident, outside quiet hours, and not within `cooldown` of `last_fired`.
+fn should_notify(
+    state: &mut RuleState,
+    firing: bool,
+    quiet: bool,
+    last_fired: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+    cooldown: Duration,
+) -> bool {
+    if !firing {
+        *state = RuleState::default();
+        return false;
+    }
+    state.firing = true;
+    if state.notified || quiet || last_fired.is_some_and(|at| now - at < cooldown) {
+        return false;
+    }
+    state.notified = true;
+    true
+}
+
+/// Fetch what `condition` needs for the site and return its notification text if it holds.
+async fn check(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+    site_tag: &str,
+    condition: &AlertCondition,
+    now: DateTime<Utc>,
+) -> Result<Option<String>, FetchError> {
+    let visits = fetch_visits_between(client, settings, site_tag, now - condition.window(), now).await?;
+    let last_week = match condition {
+        AlertCondition::DropVsLastWeek { .. } => {
+            let week_ago = now - Duration::weeks(1);
+            Some(fetch_visits_between(client, settings, site_tag, week_ago - Duration::hours(1), week_ago).await?)
+        }
+        _ => None,
+    };
+    Ok(verdict(condition, visits, last_week))
+}
+
+fn rule_key(id: &str) -> String {
+    format!("rule:{}", id)
+}
+
+/// Evaluate every enabled rule and notify on new incidents.
+pub async fn check_rules(app: &AppHandle) {
+    let Ok(settings) = get_settings(app.clone()) else {
+        return;
+    };
+    let rules: Vec<&AlertRule> = settings.alert_rules.iter().filter(|r| r.enabled).collect();
+    if rules.is_empty() {
+        return;
+    }
+
+    let client = match metered_client(app, &settings) {
+        Ok(client) => client,
+        Err(e) => {
+            warn!(error = %e, "Error building HTTP client for alert rules");
+            return;
+        }
+    };
+    let tags: HashMap<String, String> = match fetch_sites(&client, &settings.credentials(), &settings.account_id).await {
+        Ok(sites) => sites.into_iter().collect(),
+        Err(e) => {
+            warn!(error = %e, "Error fetching sites for alert rules");
+            return;
+        }
+    };
+
+    let now = Utc::now();
+    let quiet = settings
+        .alert_quiet_hours
+        .as_ref()
+        .is_some_and(|q| q.contains(now.with_timezone(&timezone(&settings)).time()));
+    let mut changed = false;
+    for rule in rules {
+        let Some(site_tag) = tags.get(&rule.site) else {
+            continue;
+        };
+        let message = match check(&client, &settings, site_tag, &rule.condition, now).await {
+            Ok(message) => message,
+            Err(e) => {
+                warn!(site = %rule.site, rule = %rule.id, error = %e, "Error checking alert rule");
+                continue;
+            }
+        };
+
+        let key = rule_key(&rule.id);
+        let last_fired = app.state::<crate::journal::LastFired>().0.lock().unwrap().get(&key).copied();
+        let cooldown = Duration::minutes(rule.cooldown_mins as i64);
+        let notify = {
+            let states = app.state::<AlertRuleState>();
+            let mut states = states.0.lock().unwrap();
+            let state = states.entry(rule.id.clone()).or_default();
+            let before = *state;
+            let notify = should_notify(state, message.is_some(), quiet, last_fired, now, cooldown);
+            changed |= *state != before;
+            notify
+        };
+        if notify && let Some(message) = message {
+            crate::notifications::notify(app, &key, &format!("Alert on {}", rule.site), &message);
+        }
+    }
+
+    if changed {
+        crate::journal::persist(app);
+    }
+}
+
+/// Forget a rule's incident, e.g. after it was edited.
+fn reset(app: &AppHandle, id: &str) {
+    app.state::<AlertRuleState>().0.lock().unwrap().remove(id);
+    crate::journal::persist(app);
+}
+
+#[tauri::command]
+pub fn get_alert_rules(app: AppHandle) -> Result<Vec<AlertRule>, FetchError> {
+    Ok(get_settings(app)?.alert_rules)
+}
+
+/// Add a rule (when its `id` is empty) or replace the one with the same ID, returning it
+/// with its ID.
+#[tauri::command]
+pub fn save_alert_rule(app: AppHandle, mut rule: AlertRule) -> Result<AlertRule, FetchError> {
+    rule.condition.validate()?;
+    let mut settings = get_settings(app.clone())?;
+    if rule.id.is_empty() {
+        rule.id = format!("{:08x}", rand::random::<u32>());
+    }
+    match settings.alert_rules.iter_mut().find(|r| r.id == rule.id) {
+        Some(existing) => *existing = rule.clone(),
+        None => settings.alert_rules.push(rule.clone()),
+    }
+    save_settings(app.clone(), settings)?;
+    reset(&app, &rule.id);
+    Ok(rule)
+}
+
+#[tauri::command]
+pub fn delete_alert_rule(app: AppHandle, id: String) -> Result<(), FetchError> {
+    let mut settings = get_settings(app.clone())?;
+    settings.alert_rules.retain(|r| r.id != id);
+    save_settings(app.clone(), settings)?;
+    reset(&app, &id);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(time: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(time).unwrap().to_utc()
+    }
+
+    fn time(hm: &str) -> NaiveTime {
+        parse_time(hm).unwrap()
+    }
+
+    #[test]
+    fn test_verdict_per_condition() {
+        assert!(verdict(&AlertCondition::VisitsAbove { visits: 100 }, 100, None).is_none());
+        assert_eq!(
+            verdict(&AlertCondition::VisitsAbove { visits: 100 }, 101, None).unwrap(),
+            "101 visits in the last hour, above 100"
+        );
+        assert!(verdict(&AlertCondition::VisitsBelow { visits: 5 }, 4, None).is_some());
+        assert!(verdict(&AlertCondition::VisitsBelow { visits: 5 }, 5, None).is_none());
+        assert_eq!(verdict(&AlertCondition::NoTraffic { hours: 6 }, 0, None).unwrap(), "No visits in the last 6 hours");
+        assert!(verdict(&AlertCondition::NoTraffic { hours: 6 }, 1, None).is_none());
+    }
+
+    #[test]
+    fn test_verdict_drop_vs_last_week() {
+        let drop = AlertCondition::DropVsLastWeek { percent: 50 };
+        assert_eq!(verdict(&drop, 40, Some(100)).unwrap(), "40 visits in the last hour, down 60% from 100 a week ago");
+        assert!(verdict(&drop, 60, Some(100)).is_none());
+        // Nothing to compare with
+        assert!(verdict(&drop, 0, Some(0)).is_none());
+        assert!(verdict(&drop, 0, None).is_none());
+    }
+
+    #[test]
+    fn test_should_notify_once_per_incident() {
+        let now = at("2024-01-15T12:00:00Z");
+        let mut state = RuleState::default();
+        assert!(should_notify(&mut state, true, false, None, now, Duration::hours(1)));
+        assert!(!should_notify(&mut state, true, false, None, now + Duration::hours(3), Duration::hours(1)));
+        assert!(!should_notify(&mut state, false, false, None, now, Duration::hours(1)));
+        assert_eq!(state, RuleState::default());
+    }
+
+    #[test]
+    fn test_should_notify_respects_cooldown() {
+        let now = at("2024-01-15T12:00:00Z");
+        let fired = Some(now - Duration::minutes(20));
+        let mut state = RuleState::default();
+        // Resolved and back within the cooldown: still the same flapping incident
+        assert!(!should_notify(&mut state, true, false, fired, now, Duration::hours(1)));
+        assert!(state.firing && !state.notified);
+        assert!(should_notify(&mut state, true, false, fired, now + Duration::minutes(45), Duration::hours(1)));
+    }
+
+    #[test]
+    fn test_should_notify_after_quiet_hours_if_still_firing() {
+        let now = at("2024-01-15T03:00:00Z");
+        let mut state = RuleState::default();
+        assert!(!should_notify(&mut state, true, true, None, now, Duration::hours(1)));
+        assert!(should_notify(&mut state, true, false, None, now + Duration::hours(5), Duration::hours(1)));
+    }
+
+    #[test]
+    fn test_quiet_hours_across_midnight() {
+        let night = QuietHours { start: "22:00".to_string(), end: "07:00".to_string() };
+        assert!(night.contains(time("23:30")));
+        assert!(night.contains(time("06:59")));
+        assert!(!night.contains(time("07:00")));
+        assert!(!night.contains(time("12:00")));
+        let lunch = QuietHours { start: "12:00".to_string(), end: "13:00".to_string() };
+        assert!(lunch.contains(time("12:30")));
+        assert!(!lunch.contains(time("22:00")));
+    }
+
+    #[test]
+    fn test_rule_deserializes_with_defaults() {
+        let rule: AlertRule =
+            serde_json::from_str(r#"{"site":"a.com","condition":{"kind":"no_traffic","hours":6}}"#).unwrap();
+        assert_eq!(rule.condition, AlertCondition::NoTraffic { hours: 6 });
+        assert_eq!(rule.cooldown_mins, 60);
+        assert!(rule.enabled);
+        assert!(rule.id.is_empty());
+    }
+
+    #[test]
+    fn test_validate_rejects_bad_rules_and_times() {
+        let rule = |condition| AlertRule {
+            id: "r1".to_string(),
+            site: "a.com".to_string(),
+            condition,
+            cooldown_mins: 60,
+            enabled: true,
+        };
+        let settings = Settings { alert_rules: vec![rule(AlertCondition::NoTraffic { hours: 0 })], ..Default::default() };
+        assert!(validate(&settings).unwrap_err().starts_with("a.com: "));
+        let settings = Settings {
+            alert_rules: vec![rule(AlertCondition::DropVsLastWeek { percent: 30 })],
+            alert_quiet_hours: Some(QuietHours { start: "10pm".to_string(), end: "07:00".to_string() }),
+            ..Default::default()
+        };
+        assert_eq!(validate(&settings).unwrap_err(), "Invalid time \"10pm\": use e.g. 22:00");
+    }
+}
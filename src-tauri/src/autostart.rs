@@ -0,0 +1,38 @@
+use crate::commands::{get_settings, save_settings};
+use crate::errors::FetchError;
+use tauri::AppHandle;
+use tauri_plugin_autostart::ManagerExt;
+use tracing::warn;
+
+/// Register or remove the login item so it matches `enabled`.
+fn apply(app: &AppHandle, enabled: bool) -> Result<(), String> {
+    let autolaunch = app.autolaunch();
+    let current = autolaunch.is_enabled().map_err(|e| e.to_string())?;
+    if current == enabled {
+        return Ok(());
+    }
+    if enabled {
+        autolaunch.enable().map_err(|e| e.to_string())
+    } else {
+        autolaunch.disable().map_err(|e| e.to_string())
+    }
+}
+
+/// On startup, bring the login item in line with `launch_at_login`, e.g. after the
+/// app was moved and the old LaunchAgent points to a stale path.
+pub fn sync(app: &AppHandle) {
+    let Ok(settings) = get_settings(app.clone()) else {
+        return;
+    };
+    if let Err(e) = apply(app, settings.launch_at_login) {
+        warn!(error = %e, "Error updating launch at login");
+    }
+}
+
+#[tauri::command]
+pub fn set_launch_at_login(app: AppHandle, enabled: bool) -> Result<(), FetchError> {
+    apply(&app, enabled)?;
+    let mut settings = get_settings(app.clone())?;
+    settings.launch_at_login = enabled;
+    save_settings(app, settings)
+}
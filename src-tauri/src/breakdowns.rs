@@ -0,0 +1,305 @@
+use crate::budget::metered_client;
+use crate::client::CloudflareClient;
+use crate::commands::{
+    cache_key, get_settings, get_time_range, graphql_request, timezone, SiteData, Settings,
+};
+use crate::errors::FetchError;
+use crate::lowdata::QueryPlan;
+use crate::sites::cached_sites;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+/// Rows per dimension.
+const TOP_N: usize = 10;
+/// How many of the busiest sites are prefetched after each refresh.
+const PREFETCH_SITES: usize = 3;
+/// A cached breakdown is served for this long, as long as the settings match.
+const BREAKDOWN_TTL_SECS: i64 = 15 * 60;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct DimensionCount {
+    pub value: String,
+    pub visits: u64,
+}
+
+/// Top countries, referrers and paths of one site over the selected period.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct Breakdown {
+    pub countries: Vec<DimensionCount>,
+    pub referrers: Vec<DimensionCount>,
+    pub paths: Vec<DimensionCount>,
+}
+
+pub struct CachedBreakdown {
+    breakdown: Breakdown,
+    fetched_at: DateTime<Utc>,
+    /// `cache_key` of the settings it was fetched with.
+    key: String,
+}
+
+/// Breakdowns by site name, filled on demand and prefetched for the top sites.
+pub struct BreakdownCache(pub Mutex<HashMap<String, CachedBreakdown>>);
+
+impl CachedBreakdown {
+    fn is_fresh(&self, key: &str, now: DateTime<Utc>) -> bool {
+        self.key == key && now - self.fetched_at < chrono::Duration::seconds(BREAKDOWN_TTL_SECS)
+    }
+
+    /// Whether to serve it rather than query again: when fresh, or with `TotalsOnly` as long
+    /// as the settings match, so low-data mode only queries breakdowns it has never loaded.
+    fn is_usable(&self, key: &str, now: DateTime<Utc>, plan: QueryPlan) -> bool {
+        self.is_fresh(key, now) || (!plan.includes_series() && self.key == key)
+    }
+}
+
+fn parse_dimension(groups: &serde_json::Value) -> Vec<DimensionCount> {
+    groups
+        .as_array()
+        .map(|groups| {
+            groups
+                .iter()
+                .filter_map(|group| {
+                    let value = group["dimensions"]["value"].as_str()?;
+                    Some(DimensionCount {
+                        value: if value.is_empty() { "(none)".to_string() } else { value.to_string() },
+                        visits: group["sum"]["visits"].as_u64().unwrap_or(0),
+                    })
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+fn parse_breakdown(data: &serde_json::Value) -> Breakdown {
+    let account = &data["data"]["viewer"]["accounts"][0];
+    Breakdown {
+        countries: parse_dimension(&account["countries"]),
+        referrers: parse_dimension(&account["referrers"]),
+        paths: parse_dimension(&account["paths"]),
+    }
+}
+
+async fn fetch_breakdown(client: &impl CloudflareClient, settings: &Settings, site_tag: &str) -> Result<Breakdown, FetchError> {
+    let (start, end, _) = get_time_range(&settings.period, timezone(settings));
+
+    let group = |alias: &str, dimension: &str| {
+        format!(
+            r#"{alias}: rumPageloadEventsAdaptiveGroups(limit: {TOP_N}, filter: $filter, orderBy: [sum_visits_DESC]) {{
+        sum {{ visits }}
+        dimensions {{ value: {dimension} }}
+      }}"#
+        )
+    };
+    let query = format!(
+        r#"{{
+  viewer {{
+    accounts(filter: {{ accountTag: $accountTag }}) {{
+      {}
+      {}
+      {}
+    }}
+  }}
+}}"#,
+        group("countries", "countryName"),
+        group("referrers", "refererHost"),
+        group("paths", "requestPath"),
+    );
+
+    let mut filters = vec![
+        serde_json::json!({ "datetime_geq": start, "datetime_leq": end }),
+        serde_json::json!({ "siteTag": site_tag }),
+    ];
+    if settings.exclude_bots {
+        filters.push(serde_json::json!({ "bot": 0 }));
+    }
+    filters.extend(settings.filters.clauses());
+    let variables = serde_json::json!({
+        "accountTag": settings.account_id,
+        "filter": { "AND": filters },
+    });
+
+    let data = graphql_request(client, &settings.credentials(), &query, variables).await?;
+    Ok(parse_breakdown(&data))
+}
+
+/// Fetch `site`'s breakdown and store it in the cache.
+async fn load(app: &AppHandle, client: &impl CloudflareClient, settings: &Settings, site: &str) -> Result<Breakdown, FetchError> {
+    let sites = cached_sites(app, client, settings).await?;
+    let site_tag = sites
+        .iter()
+        .find(|s| s.name == site)
+        .map(|s| s.site_tag.clone())
+        .ok_or_else(|| format!("Unknown site {}", site))?;
+
+    let breakdown = fetch_breakdown(client, settings, &site_tag).await?;
+    app.state::<BreakdownCache>().0.lock().unwrap().insert(
+        site.to_string(),
+        CachedBreakdown {
+            breakdown: breakdown.clone(),
+            fetched_at: Utc::now(),
+            key: cache_key(settings),
+        },
+    );
+    Ok(breakdown)
+}
+
+/// Names of the sites worth prefetching: the busiest few that aren't cached yet. The
+/// list may come pinned first or in the user's sort order, so it's ranked by visits here.
+fn prefetch_targets(sites: &[SiteData], cached: &HashMap<String, CachedBreakdown>, key: &str, now: DateTime<Utc>) -> Vec<String> {
+    let mut busiest: Vec<&SiteData> = sites.iter().filter(|site| site.visits > 0 && !site.aggregate).collect();
+    busiest.sort_by_key(|site| std::cmp::Reverse(site.visits));
+    busiest
+        .into_iter()
+        .take(PREFETCH_SITES)
+        .filter(|site| !cached.get(&site.name).is_some_and(|c| c.is_fresh(key, now)))
+        .map(|site| site.name.clone())
+        .collect()
+}
+
+/// After a refresh, warm the cache for the top sites in the background so
+/// expanding one in the panel doesn't have to wait. Skipped in low-data mode.
+pub fn prefetch(app: &AppHandle, sites: &[SiteData]) {
+    let Ok(settings) = get_settings(app.clone()) else {
+        return;
+    };
+    if !crate::lowdata::query_plan(&settings).includes_series() {
+        return;
+    }
+    let targets = {
+        let cache = app.state::<BreakdownCache>();
+        let cached = cache.0.lock().unwrap();
+        prefetch_targets(sites, &cached, &cache_key(&settings), Utc::now())
+    };
+    if targets.is_empty() {
+        return;
+    }
+
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        let Ok(client) = metered_client(&app, &settings) else {
+            return;
+        };
+        for site in targets {
+            if let Err(e) = load(&app, &client, &settings, &site).await {
+                warn!(site = %site, error = %e, "Error prefetching breakdown");
+            }
+        }
+    });
+}
+
+/// Top countries, referrers and paths for `site`, from the cache when fresh. Unlike
+/// prefetching this runs in low-data mode too, since the user asked for the site, but
+/// then reuses a cached breakdown of any age unless `force` is set.
+#[tauri::command]
+pub async fn fetch_site_breakdown(app: AppHandle, site: String, force: Option<bool>) -> Result<Breakdown, FetchError> {
+    let settings = get_settings(app.clone())?;
+    if !settings.has_credentials() {
+        return Err(FetchError::missing_credentials());
+    }
+
+    if !force.unwrap_or(false) {
+        let cache = app.state::<BreakdownCache>();
+        let cached = cache.0.lock().unwrap();
+        if let Some(entry) = cached.get(&site)
+            && entry.is_usable(&cache_key(&settings), Utc::now(), crate::lowdata::query_plan(&settings))
+        {
+            return Ok(entry.breakdown.clone());
+        }
+    }
+
+    let client = metered_client(&app, &settings)?;
+    load(&app, &client, &settings, &site).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn site(name: &str, visits: u64) -> SiteData {
+        SiteData {
+            name: name.to_string(),
+            visits,
+            ..Default::default()
+        }
+    }
+
+    fn cached(key: &str, fetched_at: DateTime<Utc>) -> CachedBreakdown {
+        CachedBreakdown {
+            breakdown: Breakdown::default(),
+            fetched_at,
+            key: key.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_parse_breakdown() {
+        let data = json!({"data": {"viewer": {"accounts": [{
+            "countries": [
+                {"sum": {"visits": 40}, "dimensions": {"value": "Poland"}},
+                {"sum": {"visits": 12}, "dimensions": {"value": "Germany"}}
+            ],
+            "referrers": [{"sum": {"visits": 30}, "dimensions": {"value": ""}}],
+            "paths": []
+        }]}}});
+        let breakdown = parse_breakdown(&data);
+        assert_eq!(
+            breakdown.countries,
+            vec![
+                DimensionCount { value: "Poland".to_string(), visits: 40 },
+                DimensionCount { value: "Germany".to_string(), visits: 12 },
+            ]
+        );
+        assert_eq!(breakdown.referrers[0].value, "(none)");
+        assert!(breakdown.paths.is_empty());
+    }
+
+    #[test]
+    fn test_parse_breakdown_missing_data() {
+        assert_eq!(parse_breakdown(&json!({"data": null})), Breakdown::default());
+    }
+
+    #[test]
+    fn test_prefetch_targets_top_uncached_sites() {
+        let now = Utc::now();
+        let sites = [site("a.com", 500), site("b.com", 300), site("c.com", 200), site("d.com", 100)];
+        let mut cache = HashMap::new();
+        cache.insert("b.com".to_string(), cached("k", now));
+        assert_eq!(prefetch_targets(&sites, &cache, "k", now), vec!["a.com", "c.com"]);
+    }
+
+    #[test]
+    fn test_prefetch_targets_ranks_by_visits() {
+        let now = Utc::now();
+        // Sorted by name, with the All sites entry on top
+        let mut sites = vec![site("a.com", 10), site("b.com", 900), site("c.com", 0), site("d.com", 400), site("e.com", 50)];
+        sites.insert(0, SiteData { aggregate: true, ..site("All sites", 1_360) });
+        assert_eq!(prefetch_targets(&sites, &HashMap::new(), "k", now), vec!["b.com", "d.com", "e.com"]);
+    }
+
+    #[test]
+    fn test_prefetch_targets_refetches_stale_entries() {
+        let now = Utc::now();
+        let sites = [site("a.com", 500), site("b.com", 0)];
+        let mut cache = HashMap::new();
+        cache.insert("a.com".to_string(), cached("other", now));
+        assert_eq!(prefetch_targets(&sites, &cache, "k", now), vec!["a.com"]);
+
+        let old = now - chrono::Duration::seconds(BREAKDOWN_TTL_SECS + 1);
+        cache.insert("a.com".to_string(), cached("k", old));
+        assert_eq!(prefetch_targets(&sites, &cache, "k", now), vec!["a.com"]);
+    }
+    #[test]
+    fn test_low_data_mode_reuses_stale_breakdowns() {
+        let now = Utc::now();
+        let old = cached("k", now - chrono::Duration::seconds(BREAKDOWN_TTL_SECS + 1));
+        assert!(!old.is_usable("k", now, QueryPlan::Full));
+        assert!(old.is_usable("k", now, QueryPlan::TotalsOnly));
+        assert!(!old.is_usable("other", now, QueryPlan::TotalsOnly));
+        assert!(cached("k", now).is_usable("k", now, QueryPlan::Full));
+    }
+}
@@ -0,0 +1,189 @@
+//! GraphQL call accounting. Cloudflare allows about 300 GraphQL queries per user in any
+//! 5-minute window and blocks the token for a while past that, which accounts with many
+//! sites can reach with a short refresh interval. Calls are counted per account, and the
+//! background refresh waits longer when the next one would go over `SOFT_LIMIT`.
+
+use crate::client::{shared_client, ApiResponse, CloudflareClient, Credentials};
+use crate::commands::{get_settings, Settings};
+use crate::errors::FetchError;
+use reqwest::Client;
+use serde::Serialize;
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tracing::info;
+
+pub const WINDOW: Duration = Duration::from_secs(5 * 60);
+/// Cloudflare's documented GraphQL limit per `WINDOW`.
+pub const LIMIT: usize = 300;
+/// Leave headroom for panel actions (breakdowns, live visitors) between refreshes.
+pub const SOFT_LIMIT: usize = LIMIT * 4 / 5;
+
+/// When each GraphQL call of the last `WINDOW` was made, oldest first, per account ID.
+pub struct ApiBudget(pub Mutex<HashMap<String, VecDeque<Instant>>>);
+
+fn prune(calls: &mut VecDeque<Instant>, now: Instant) {
+    while calls.front().is_some_and(|&call| call + WINDOW <= now) {
+        calls.pop_front();
+    }
+}
+
+fn record(app: &AppHandle, account_id: &str) {
+    let now = Instant::now();
+    let budget = app.state::<ApiBudget>();
+    let mut accounts = budget.0.lock().unwrap();
+    let calls = accounts.entry(account_id.to_string()).or_default();
+    prune(calls, now);
+    calls.push_back(now);
+}
+
+/// A client that counts the GraphQL calls it makes against the account's budget.
+#[derive(Clone)]
+pub struct MeteredClient {
+    inner: Client,
+    app: AppHandle,
+    account_id: String,
+}
+
+impl MeteredClient {
+    /// Count `inner`'s GraphQL calls towards the account configured in `settings`.
+    pub fn new(app: &AppHandle, settings: &Settings, inner: Client) -> Self {
+        Self {
+            inner,
+            app: app.clone(),
+            account_id: settings.account_id.clone(),
+        }
+    }
+}
+
+/// The shared client for `settings`, counting its GraphQL calls towards the configured account.
+pub(crate) fn metered_client(app: &AppHandle, settings: &Settings) -> Result<MeteredClient, String> {
+    Ok(MeteredClient::new(app, settings, shared_client(app, settings)?))
+}
+
+impl CloudflareClient for MeteredClient {
+    async fn get(&self, auth: &Credentials, url: &str, query: &[(&str, String)]) -> Result<ApiResponse, FetchError> {
+        CloudflareClient::get(&self.inner, auth, url, query).await
+    }
+
+    async fn post_json(&self, auth: &Credentials, url: &str, body: &serde_json::Value) -> Result<ApiResponse, FetchError> {
+        if url.ends_with("/graphql") {
+            record(&self.app, &self.account_id);
+        }
+        CloudflareClient::post_json(&self.inner, auth, url, body).await
+    }
+}
+
+/// How long to wait before the next background refresh: `wait`, or longer if by then the
+/// window would still hold too many calls to fit another `per_refresh` under `SOFT_LIMIT`.
+fn throttled_wait(calls: &VecDeque<Instant>, now: Instant, wait: Duration, per_refresh: usize) -> Duration {
+    let budget = SOFT_LIMIT.saturating_sub(per_refresh);
+    let at = now + wait;
+    let live: Vec<Instant> = calls.iter().copied().filter(|&call| call + WINDOW > at).collect();
+    if live.len() <= budget {
+        return wait;
+    }
+    // Wait until the oldest calls over the budget have left the window
+    let last_to_expire = live[live.len() - budget - 1];
+    (last_to_expire + WINDOW).saturating_duration_since(now).max(wait)
+}
+
+/// The refresh interval `wait`, stretched when the configured account is close to its budget.
+/// Each refresh is assumed to make one GraphQL call per known site.
+pub fn throttle(app: &AppHandle, wait: Duration) -> Duration {
+    let Ok(settings) = get_settings(app.clone()) else {
+        return wait;
+    };
+    let per_refresh = app.state::<crate::sites::SiteCache>().count(&settings).max(1);
+    let budget = app.state::<ApiBudget>();
+    let accounts = budget.0.lock().unwrap();
+    let Some(calls) = accounts.get(&settings.account_id) else {
+        return wait;
+    };
+    let stretched = throttled_wait(calls, Instant::now(), wait, per_refresh);
+    if stretched > wait {
+        info!(
+            calls = calls.len(),
+            per_refresh,
+            wait_secs = stretched.as_secs(),
+            "Close to the GraphQL rate limit, delaying the next refresh"
+        );
+    }
+    stretched
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+pub struct ApiUsage {
+    /// GraphQL calls for the configured account in the last `window_secs`.
+    pub calls: usize,
+    pub window_secs: u64,
+    pub limit: usize,
+    /// Background refreshes are being spaced out to stay under `SOFT_LIMIT`.
+    pub throttled: bool,
+}
+
+#[tauri::command]
+pub fn get_api_usage(app: AppHandle) -> Result<ApiUsage, FetchError> {
+    let settings = get_settings(app.clone())?;
+    let now = Instant::now();
+    let budget = app.state::<ApiBudget>();
+    let mut accounts = budget.0.lock().unwrap();
+    let calls = accounts.get_mut(&settings.account_id).map_or(0, |calls| {
+        prune(calls, now);
+        calls.len()
+    });
+    Ok(ApiUsage {
+        calls,
+        window_secs: WINDOW.as_secs(),
+        limit: LIMIT,
+        throttled: calls >= SOFT_LIMIT,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `count` calls made `secs_ago` seconds before `now`.
+    fn calls_at(now: Instant, batches: &[(u64, usize)]) -> VecDeque<Instant> {
+        batches
+            .iter()
+            .flat_map(|&(secs_ago, count)| std::iter::repeat_n(now - Duration::from_secs(secs_ago), count))
+            .collect()
+    }
+
+    #[test]
+    fn test_prune_drops_calls_outside_window() {
+        let now = Instant::now() + WINDOW * 2;
+        let mut calls = calls_at(now, &[(400, 3), (200, 2), (10, 1)]);
+        prune(&mut calls, now);
+        assert_eq!(calls.len(), 3);
+    }
+
+    #[test]
+    fn test_throttled_wait_keeps_interval_under_budget() {
+        let now = Instant::now() + WINDOW * 2;
+        let calls = calls_at(now, &[(60, 50)]);
+        let wait = Duration::from_secs(60);
+        assert_eq!(throttled_wait(&calls, now, wait, 50), wait);
+    }
+
+    #[test]
+    fn test_throttled_wait_waits_for_old_calls_to_expire() {
+        let now = Instant::now() + WINDOW * 2;
+        // 200 calls 4 minutes ago and 100 a minute ago; another 60 only fit once the older
+        // batch has left the window, a minute from now
+        let calls = calls_at(now, &[(240, 200), (60, 100)]);
+        let wait = throttled_wait(&calls, now, Duration::from_secs(30), 60);
+        assert_eq!(wait, Duration::from_secs(60));
+    }
+
+    #[test]
+    fn test_throttled_wait_ignores_calls_expired_by_then() {
+        let now = Instant::now() + WINDOW * 2;
+        let calls = calls_at(now, &[(280, 250)]);
+        let wait = Duration::from_secs(30);
+        assert_eq!(throttled_wait(&calls, now, wait, 60), wait);
+    }
+}
@@ -0,0 +1,154 @@
+//! `flarestats --print [--period 7d] [--json]`: one fetch with the saved settings, printed
+//! to stdout, for scripts and status bars. `run()` in `lib.rs` builds the app without the
+//! tray or any window for it and exits with the code `run` returns.
+
+use crate::commands::{cache_key, fetch_all_sites_reporting, get_settings, SiteData, Snapshot, PERIODS};
+use crate::errors::FetchError;
+use chrono::Utc;
+use tauri::AppHandle;
+
+#[derive(Debug, Default, PartialEq)]
+pub struct PrintOptions {
+    /// Overrides `Settings::period` for this fetch only.
+    pub period: Option<String>,
+    /// Print the `AnalyticsSnapshot` as JSON instead of a table.
+    pub json: bool,
+}
+
+/// `Some` when the app was launched with `--print`. Without it every argument is left
+/// alone, since the OS passes its own (deep links, process serial numbers).
+pub fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Option<PrintOptions>, String> {
+    let args: Vec<String> = args.into_iter().collect();
+    if !args.iter().any(|arg| arg == "--print") {
+        return Ok(None);
+    }
+    let mut options = PrintOptions::default();
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--print" => {}
+            "--json" => options.json = true,
+            "--period" => {
+                let period = args.next().ok_or("--period needs a value, e.g. --period 7d")?;
+                if !PERIODS.contains(&period.as_str()) {
+                    return Err(format!("Unknown period {}, use one of {}", period, PERIODS.join(", ")));
+                }
+                options.period = Some(period);
+            }
+            other => return Err(format!("Unknown argument {}, usage: flarestats --print [--period 7d] [--json]", other)),
+        }
+    }
+    Ok(Some(options))
+}
+
+fn format_change(change: Option<f64>) -> String {
+    change.map_or("-".to_string(), |c| format!("{:+.0}%", c))
+}
+
+/// One line per site: name, visits, page views and change in visits, in aligned columns.
+fn format_table(sites: &[SiteData]) -> String {
+    let width = sites.iter().map(|s| s.name.chars().count()).max().unwrap_or(0).max("SITE".len());
+    let mut lines = vec![format!("{:<width$}  {:>10}  {:>10}  {:>7}", "SITE", "VISITS", "VIEWS", "CHANGE")];
+    for site in sites {
+        lines.push(format!(
+            "{:<width$}  {:>10}  {:>10}  {:>7}",
+            site.name,
+            site.visits,
+            site.page_views,
+            format_change(site.visits_change)
+        ));
+    }
+    lines.join("\n")
+}
+
+/// Fetch every site once and format the result. Sites that fail are reported on stderr
+/// and the output is marked partial, like in the panel.
+async fn fetch_output(app: &AppHandle, options: &PrintOptions) -> Result<String, FetchError> {
+    let mut settings = get_settings(app.clone())?;
+    if let Some(period) = &options.period {
+        settings.period = period.clone();
+    }
+    let client = crate::budget::metered_client(app, &settings)?;
+    let (sites, failures) = fetch_all_sites_reporting(&client, &settings, |_| {}).await?;
+    for (site, error) in &failures {
+        eprintln!("{}: {}", site, error);
+    }
+
+    let snapshot = Snapshot {
+        sites,
+        fetched_at: Utc::now(),
+        site_fetched_at: Default::default(),
+        key: cache_key(&settings),
+        period: settings.period.clone(),
+        partial: !failures.is_empty(),
+    }
+    .into_analytics(&settings);
+    if options.json {
+        serde_json::to_string_pretty(&snapshot).map_err(|e| e.to_string().into())
+    } else {
+        Ok(format_table(&snapshot.sites))
+    }
+}
+
+/// Print one fetch to stdout, returning the process exit code: 0 on success (even if
+/// some sites failed), 1 if nothing could be fetched.
+pub fn run(app: &AppHandle, options: PrintOptions) -> i32 {
+    match tauri::async_runtime::block_on(fetch_output(app, &options)) {
+        Ok(output) => {
+            println!("{}", output);
+            0
+        }
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(list: &[&str]) -> Vec<String> {
+        list.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn test_parse_args_without_print_is_normal_launch() {
+        assert_eq!(parse_args(args(&[])), Ok(None));
+        assert_eq!(parse_args(args(&["-psn_0_12345", "flarestats://site/a.com"])), Ok(None));
+    }
+
+    #[test]
+    fn test_parse_args_print_options() {
+        assert_eq!(parse_args(args(&["--print"])), Ok(Some(PrintOptions::default())));
+        assert_eq!(
+            parse_args(args(&["--print", "--period", "7d", "--json"])),
+            Ok(Some(PrintOptions { period: Some("7d".to_string()), json: true }))
+        );
+    }
+
+    #[test]
+    fn test_parse_args_rejects_bad_input() {
+        assert!(parse_args(args(&["--print", "--period"])).is_err());
+        assert!(parse_args(args(&["--print", "--period", "2w"])).unwrap_err().starts_with("Unknown period 2w"));
+        assert!(parse_args(args(&["--print", "--csv"])).is_err());
+    }
+
+    #[test]
+    fn test_format_table_aligns_columns() {
+        let sites = vec![
+            SiteData { name: "example.com".to_string(), visits: 1200, page_views: 3400, visits_change: Some(12.4), ..Default::default() },
+            SiteData { name: "a.io".to_string(), visits: 5, page_views: 9, ..Default::default() },
+        ];
+        assert_eq!(
+            format_table(&sites),
+            [
+                "SITE             VISITS       VIEWS   CHANGE",
+                "example.com        1200        3400     +12%",
+                "a.io                  5           9        -",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,308 @@
+//! Every request to the Cloudflare API goes through `CloudflareClient`. The app uses the
+//! `reqwest` implementation; tests run the same fetch code against `mock::MockClient`.
+
+use crate::commands::{api_client, new_correlation_id, send_with_retry, tag_error, Settings};
+use crate::errors::FetchError;
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+use std::future::Future;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::debug;
+
+pub(crate) const API_BASE: &str = "https://api.cloudflare.com/client/v4";
+
+/// The `reqwest::Client` all commands share, so connections and TLS sessions are reused
+/// across refreshes, with the network settings it was built for.
+pub struct SharedClient(pub Mutex<Option<(String, reqwest::Client)>>);
+
+/// Settings that need a new client when they change.
+fn network_key(settings: &Settings) -> String {
+    format!("{}|{}", settings.proxy_url.trim(), settings.ca_cert_path.trim())
+}
+
+/// The client cached in `slot` if it was built for `key`, otherwise a new one from `build`.
+fn reuse(
+    slot: &mut Option<(String, reqwest::Client)>,
+    key: String,
+    build: impl FnOnce() -> Result<reqwest::Client, String>,
+) -> Result<reqwest::Client, String> {
+    if let Some((cached_key, client)) = slot.as_ref()
+        && *cached_key == key
+    {
+        return Ok(client.clone());
+    }
+    let client = build()?;
+    *slot = Some((key, client.clone()));
+    Ok(client)
+}
+
+/// The shared client, rebuilt when the proxy or certificate settings changed. Clones
+/// share the same connection pool.
+pub(crate) fn shared_client(app: &AppHandle, settings: &Settings) -> Result<reqwest::Client, String> {
+    let state = app.state::<SharedClient>();
+    let mut slot = state.0.lock().unwrap();
+    reuse(&mut slot, network_key(settings), || api_client(settings))
+}
+
+/// How requests are authenticated, see `Settings::auth_method`.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuthMethod {
+    /// An API token, sent as `Authorization: Bearer`.
+    #[default]
+    Token,
+    /// A legacy Global API key and the email of its user, sent as `X-Auth-Key` and `X-Auth-Email`.
+    ApiKey,
+}
+
+/// What requests are authenticated with: an API token, or a Global API key with its email.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub(crate) struct Credentials {
+    pub method: AuthMethod,
+    /// The token or the key.
+    pub secret: String,
+    /// Only used with `AuthMethod::ApiKey`.
+    pub email: String,
+}
+
+impl Credentials {
+    pub fn new(method: AuthMethod, secret: &str, email: &str) -> Self {
+        Self { method, secret: secret.trim().to_string(), email: email.trim().to_string() }
+    }
+
+    /// Whether everything `method` needs is filled in.
+    pub fn is_complete(&self) -> bool {
+        !self.secret.is_empty() && (self.method == AuthMethod::Token || !self.email.is_empty())
+    }
+}
+
+/// The headers authenticating a request, the same for REST and GraphQL.
+pub(crate) fn auth_headers(auth: &Credentials) -> Vec<(&'static str, String)> {
+    match auth.method {
+        AuthMethod::Token => vec![("Authorization", format!("Bearer {}", auth.secret))],
+        AuthMethod::ApiKey => vec![("X-Auth-Key", auth.secret.clone()), ("X-Auth-Email", auth.email.clone())],
+    }
+}
+
+/// `request` with `auth_headers` added.
+fn authorize(request: reqwest::RequestBuilder, auth: &Credentials) -> reqwest::RequestBuilder {
+    auth_headers(auth)
+        .into_iter()
+        .fold(request, |request, (name, value)| request.header(name, value))
+}
+
+/// Status and raw body of an API response, with the correlation ID the request was sent with.
+pub(crate) struct ApiResponse {
+    pub status: StatusCode,
+    pub body: String,
+    pub request_id: String,
+}
+
+impl ApiResponse {
+    /// The body parsed as JSON.
+    pub fn json(&self) -> Result<serde_json::Value, FetchError> {
+        serde_json::from_str(&self.body).map_err(|e| FetchError::parse(tag_error(e, &self.request_id)))
+    }
+
+    /// The body parsed as JSON, or an error carrying the body for non-2xx statuses.
+    pub fn success_json(&self) -> Result<serde_json::Value, FetchError> {
+        if !self.status.is_success() {
+            let message = tag_error(format!("API error {}: {}", self.status, self.body), &self.request_id);
+            return Err(FetchError::for_status(self.status, message));
+        }
+        self.json()
+    }
+}
+
+pub(crate) trait CloudflareClient: Sync {
+    /// `GET url` with `query` parameters, authenticated with `auth`.
+    fn get(
+        &self,
+        auth: &Credentials,
+        url: &str,
+        query: &[(&str, String)],
+    ) -> impl Future<Output = Result<ApiResponse, FetchError>> + Send;
+
+    /// `POST url` with `body` as JSON, authenticated with `auth`.
+    fn post_json(
+        &self,
+        auth: &Credentials,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> impl Future<Output = Result<ApiResponse, FetchError>> + Send;
+}
+
+/// Read the body of `resp`, logging it at debug level.
+async fn read(resp: reqwest::Response, request_id: String) -> Result<ApiResponse, FetchError> {
+    let status = resp.status();
+    let body = resp
+        .text()
+        .await
+        .map_err(|e| FetchError::Network { message: tag_error(e, &request_id) })?;
+    debug!(request_id = %request_id, body = %body, "API response body");
+    Ok(ApiResponse { status, body, request_id })
+}
+
+/// Requests are retried on rate limits, 5xx and network errors; see `send_with_retry`.
+impl CloudflareClient for reqwest::Client {
+    async fn get(&self, auth: &Credentials, url: &str, query: &[(&str, String)]) -> Result<ApiResponse, FetchError> {
+        let request_id = new_correlation_id();
+        let resp = send_with_retry(&request_id, || authorize(self.get(url).query(query), auth)).await?;
+        read(resp, request_id).await
+    }
+
+    async fn post_json(&self, auth: &Credentials, url: &str, body: &serde_json::Value) -> Result<ApiResponse, FetchError> {
+        let request_id = new_correlation_id();
+        let resp = send_with_retry(&request_id, || authorize(self.post(url).json(body), auth)).await?;
+        read(resp, request_id).await
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod mock {
+    use super::{ApiResponse, CloudflareClient, Credentials, FetchError};
+    use reqwest::StatusCode;
+    use std::sync::Mutex;
+
+    /// A request as `MockClient` received it.
+    #[derive(Clone, Debug)]
+    pub struct Request {
+        pub method: &'static str,
+        pub url: String,
+        pub query: Vec<(String, String)>,
+        pub body: Option<serde_json::Value>,
+    }
+
+    impl Request {
+        pub fn param(&self, name: &str) -> Option<&str> {
+            self.query.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+        }
+
+        /// The GraphQL variables, serialized, for matching on site tags and filters.
+        pub fn variables(&self) -> String {
+            self.body.as_ref().map(|b| b["variables"].to_string()).unwrap_or_default()
+        }
+    }
+
+    type Responder = Box<dyn Fn(&Request) -> (u16, String) + Send + Sync>;
+
+    /// Answers every request with `respond`'s canned `(status, body)` and records it.
+    pub struct MockClient {
+        respond: Responder,
+        pub requests: Mutex<Vec<Request>>,
+    }
+
+    impl MockClient {
+        pub fn new(respond: impl Fn(&Request) -> (u16, String) + Send + Sync + 'static) -> Self {
+            Self { respond: Box::new(respond), requests: Mutex::new(Vec::new()) }
+        }
+
+        pub fn requests(&self) -> Vec<Request> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        fn answer(&self, request: Request) -> Result<ApiResponse, FetchError> {
+            let (status, body) = (self.respond)(&request);
+            self.requests.lock().unwrap().push(request);
+            Ok(ApiResponse {
+                status: StatusCode::from_u16(status).unwrap(),
+                body,
+                request_id: "mock".to_string(),
+            })
+        }
+    }
+
+    impl CloudflareClient for MockClient {
+        async fn get(&self, _auth: &Credentials, url: &str, query: &[(&str, String)]) -> Result<ApiResponse, FetchError> {
+            self.answer(Request {
+                method: "GET",
+                url: url.to_string(),
+                query: query.iter().map(|(k, v)| (k.to_string(), v.clone())).collect(),
+                body: None,
+            })
+        }
+
+        async fn post_json(&self, _auth: &Credentials, url: &str, body: &serde_json::Value) -> Result<ApiResponse, FetchError> {
+            self.answer(Request { method: "POST", url: url.to_string(), query: Vec::new(), body: Some(body.clone()) })
+        }
+    }
+
+    /// A `(status, body)` pair with `body` serialized as JSON.
+    pub fn json(status: u16, body: serde_json::Value) -> (u16, String) {
+        (status, body.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(status: u16, body: &str) -> ApiResponse {
+        ApiResponse {
+            status: StatusCode::from_u16(status).unwrap(),
+            body: body.to_string(),
+            request_id: "abc".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_success_json_includes_error_body_and_request_id() {
+        let err = response(403, r#"{"errors":[{"code":10000}]}"#).success_json().unwrap_err();
+        assert_eq!(
+            err,
+            FetchError::Auth {
+                message: r#"API error 403 Forbidden: {"errors":[{"code":10000}]} [request abc]"#.to_string()
+            }
+        );
+    }
+
+    #[test]
+    fn test_auth_headers_per_method() {
+        let token = Credentials::new(AuthMethod::Token, " abc ", "");
+        assert_eq!(auth_headers(&token), vec![("Authorization", "Bearer abc".to_string())]);
+        let key = Credentials::new(AuthMethod::ApiKey, "key", "me@example.com");
+        assert_eq!(
+            auth_headers(&key),
+            vec![("X-Auth-Key", "key".to_string()), ("X-Auth-Email", "me@example.com".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_credentials_complete() {
+        assert!(Credentials::new(AuthMethod::Token, "abc", "").is_complete());
+        assert!(!Credentials::new(AuthMethod::Token, " ", "").is_complete());
+        assert!(!Credentials::new(AuthMethod::ApiKey, "key", "").is_complete());
+        assert!(Credentials::new(AuthMethod::ApiKey, "key", "me@example.com").is_complete());
+    }
+
+    #[test]
+    fn test_reuse_builds_once_per_key() {
+        let mut slot = None;
+        let mut builds = 0;
+        for key in ["|", "|", "http://proxy:3128|"] {
+            reuse(&mut slot, key.to_string(), || {
+                builds += 1;
+                Ok(reqwest::Client::new())
+            })
+            .unwrap();
+        }
+        assert_eq!(builds, 2);
+        assert_eq!(slot.unwrap().0, "http://proxy:3128|");
+    }
+
+    #[test]
+    fn test_reuse_keeps_cached_client_when_build_fails() {
+        let mut slot = Some(("|".to_string(), reqwest::Client::new()));
+        assert!(reuse(&mut slot, "bad|".to_string(), || Err("Invalid proxy URL".to_string())).is_err());
+        assert_eq!(slot.unwrap().0, "|");
+    }
+
+    #[test]
+    fn test_json_rejects_non_json_body() {
+        let err = response(200, "<html>").json().unwrap_err();
+        assert!(matches!(err, FetchError::Parse { .. }));
+        assert!(err.message().ends_with("[request abc]"));
+        assert_eq!(response(200, r#"{"ok":true}"#).json().unwrap()["ok"], true);
+    }
+}
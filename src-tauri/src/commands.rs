@@ -1,29 +1,289 @@
-use chrono::{NaiveDate, NaiveDateTime, Timelike, Utc};
+use crate::alerts::{AlertRule, QuietHours};
+use crate::client::{AuthMethod, CloudflareClient, Credentials, API_BASE};
+use crate::errors::FetchError;
+use crate::lowdata::QueryPlan;
+use crate::notifications::AlertThreshold;
+use crate::scripts::AlertScript;
+use chrono::{DateTime, Datelike, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc};
+use chrono_tz::Tz;
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet, VecDeque};
 use std::fs;
 use std::path::PathBuf;
 use std::sync::Mutex;
 use tauri::{AppHandle, Emitter, Manager};
+use tauri_plugin_opener::OpenerExt;
+use futures::StreamExt;
 use tokio::task::JoinHandle;
+use tracing::{debug, warn};
 
 pub struct RefreshTask(pub Mutex<Option<JoinHandle<()>>>);
 
+/// Recent successful fetches, newest first. The newest is reused by `fetch_analytics`
+/// while fresh; older ones can be replayed with `get_snapshot_at`.
+pub struct AnalyticsCache(pub Mutex<VecDeque<Snapshot>>);
+
+impl AnalyticsCache {
+    pub fn latest(&self) -> Option<Snapshot> {
+        self.0.lock().unwrap().front().cloned()
+    }
+}
+
+/// Add `snapshot` as the newest entry, keeping at most `keep` (and always the new one).
+fn push_snapshot(history: &mut VecDeque<Snapshot>, snapshot: Snapshot, keep: usize) {
+    history.push_front(snapshot);
+    history.truncate(keep.max(1));
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Snapshot {
+    pub sites: Vec<SiteData>,
+    /// When the oldest of `sites` was fetched.
+    pub fetched_at: DateTime<Utc>,
+    /// When each site was fetched, which differs between them once some are skipped.
+    /// Empty in snapshots saved before it was recorded.
+    #[serde(default)]
+    pub site_fetched_at: HashMap<String, DateTime<Utc>>,
+    /// Identifies the settings the data was fetched with, see `cache_key`.
+    pub key: String,
+    /// Empty in snapshots saved before it was recorded.
+    #[serde(default)]
+    pub period: String,
+    /// Some sites failed to load and are missing from `sites`.
+    #[serde(default)]
+    pub partial: bool,
+}
+
+impl Snapshot {
+    fn is_fresh(&self, key: &str, ttl: chrono::Duration, now: DateTime<Utc>) -> bool {
+        self.key == key && now - self.fetched_at < ttl
+    }
+
+    /// The snapshot as commands return it, with sites hidden, sorted and summed for `settings`.
+    pub(crate) fn into_analytics(self, settings: &Settings) -> AnalyticsSnapshot {
+        AnalyticsSnapshot {
+            fetched_at: self.fetched_at.timestamp_millis(),
+            period: self.period,
+            sites: with_all_sites(settings, arrange_sites(settings, self.sites)),
+            partial: self.partial,
+            offline: false,
+        }
+    }
+}
+
+/// Sites as returned by `fetch_analytics` and `refresh_now` and sent with `analytics-refreshed`,
+/// so the panel can tell a fresh result from an old or incomplete one.
+#[derive(Serialize, Clone)]
+pub struct AnalyticsSnapshot {
+    /// Unix milliseconds of the fetch from Cloudflare, or of the oldest one when some sites
+    /// kept earlier data. For a cached result it's earlier than the call that returned it.
+    pub fetched_at: i64,
+    pub period: String,
+    pub sites: Vec<SiteData>,
+    /// Some sites failed to load and are missing from `sites`; see `analytics-error`.
+    pub partial: bool,
+    /// Cloudflare couldn't be reached and this is the last saved fetch; see `connectivity`.
+    pub offline: bool,
+}
+
+/// Settings that change what a fetch returns; a snapshot from different ones is never reused.
+/// Includes the low-data query plan, so a snapshot without series isn't served once it's off.
+pub(crate) fn cache_key(settings: &Settings) -> String {
+    format!(
+        "{}|{}|{}|{}|{}|{}|{}|{}|{}|{:?}",
+        settings.account_id,
+        settings.period,
+        settings.exclude_bots,
+        settings.split_bots,
+        settings.quarter_hour_24h,
+        settings.filters.country,
+        settings.filters.device_type,
+        settings.filters.host,
+        timezone(settings).name(),
+        crate::lowdata::query_plan(settings)
+    )
+}
+
 #[derive(Serialize, Deserialize, Clone)]
 pub struct Settings {
+    /// Shape of the file, see `migrations`.
+    #[serde(default)]
+    pub schema_version: u32,
+    /// The API token, or the Global API key with `AuthMethod::ApiKey`.
     pub token: String,
+    #[serde(default)]
+    pub auth_method: AuthMethod,
+    /// Email of the user the Global API key belongs to; unused with tokens.
+    #[serde(default)]
+    pub email: String,
     pub account_id: String,
     pub period: String,
     #[serde(default = "default_true")]
     pub exclude_bots: bool,
+    /// Count bot traffic separately (`SiteData::bot_visits`, `SeriesPoint::bot_visits`)
+    /// instead of dropping or mixing it in; overrides `exclude_bots` for site analytics.
+    #[serde(default)]
+    pub split_bots: bool,
     #[serde(default = "default_theme")]
     pub theme: String,
     #[serde(default = "default_refresh_interval")]
     pub refresh_interval: String,
+    /// Refresh interval per site name, e.g. "5m" for a busy site or "1h" for a dormant one.
+    /// Other sites follow `refresh_interval`.
+    #[serde(default)]
+    pub site_refresh_intervals: HashMap<String, String>,
+    #[serde(default)]
+    pub alert_thresholds: HashMap<String, AlertThreshold>,
+    /// Per-site alert rules, see `alerts`.
+    #[serde(default)]
+    pub alert_rules: Vec<AlertRule>,
+    /// When alert rules hold their notifications until the morning; `None` for never.
+    #[serde(default)]
+    pub alert_quiet_hours: Option<QuietHours>,
+    /// What to show next to the tray icon: "off", "total" or "site" (visits of `tray_site`).
+    #[serde(default = "default_tray_title")]
+    pub tray_title: String,
+    #[serde(default)]
+    pub tray_site: String,
+    /// Draw the last 24h of visits of `tray_site` (or the first pinned site) as the tray
+    /// icon instead of the trend arrow.
+    #[serde(default)]
+    pub tray_sparkline: bool,
+    /// Shell command run after each background refresh, with the analytics JSON on stdin.
+    #[serde(default)]
+    pub refresh_hook: String,
+    #[serde(default)]
+    pub alert_scripts: Vec<AlertScript>,
+    /// How long `fetch_analytics` reuses the last result, in seconds (0 disables the cache).
+    #[serde(default = "default_cache_ttl_secs")]
+    pub cache_ttl_secs: u64,
+    /// How many past refreshes are kept in memory for replay in the panel.
+    #[serde(default = "default_snapshot_history")]
+    pub snapshot_history: usize,
+    /// Label and color per account ID, attached to every site of that account.
+    #[serde(default)]
+    pub account_styles: HashMap<String, AccountStyle>,
+    /// "off", "on", or "auto" to switch to totals-only queries on a constrained network.
+    #[serde(default = "default_low_data_mode")]
+    pub low_data_mode: String,
+    /// Proxy for all Cloudflare API calls, e.g. `http://proxy:3128` or `socks5://proxy:1080`.
+    #[serde(default)]
+    pub proxy_url: String,
+    /// PEM file with extra root certificates to trust, for proxies that intercept TLS.
+    #[serde(default)]
+    pub ca_cert_path: String,
+    /// Start the app when the user logs in, see `autostart`.
+    #[serde(default)]
+    pub launch_at_login: bool,
+    /// Accelerator that toggles the panel from anywhere, empty to disable.
+    #[serde(default = "default_global_shortcut")]
+    pub global_shortcut: String,
+    /// How often to look for a new release, e.g. "24h", or "off".
+    #[serde(default = "default_update_check_interval")]
+    pub update_check_interval: String,
+    /// IANA zone that day buckets and period boundaries follow, or "auto" for the system zone.
+    #[serde(default = "default_timezone")]
+    pub timezone: String,
+    /// Prepend an "All sites" entry summing every site, see `with_all_sites`.
+    #[serde(default = "default_true")]
+    pub show_all_sites: bool,
+    /// How many of the busiest sites the tray menu lists with their visits, 0 to hide the list.
+    #[serde(default = "default_tray_menu_sites")]
+    pub tray_menu_sites: usize,
+    /// Sites left out of the results; they aren't queried at all.
+    #[serde(default)]
+    pub hidden_sites: Vec<String>,
+    /// Sites listed before all others, see `arrange_sites`.
+    #[serde(default)]
+    pub pinned_sites: Vec<String>,
+    /// Chart the 24h period in 15-minute instead of hourly buckets.
+    #[serde(default)]
+    pub quarter_hour_24h: bool,
+    /// Order of the sites list, one of `SORT_KEYS`.
+    #[serde(default = "default_sort_by")]
+    pub sort_by: String,
+    /// "desc" or "asc".
+    #[serde(default = "default_sort_direction")]
+    pub sort_direction: String,
+    /// How many sites are queried at once; Cloudflare rate limits bursts of GraphQL requests.
+    #[serde(default = "default_max_concurrent_requests")]
+    pub max_concurrent_requests: usize,
+    /// Narrows every analytics query, see `DimensionFilters`.
+    #[serde(default)]
+    pub filters: DimensionFilters,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub struct AccountStyle {
+    pub label: String,
+    /// CSS hex color such as `#f38020`, or empty for none.
+    pub color: String,
+}
+
+impl AccountStyle {
+    fn validate(&self) -> Result<(), String> {
+        let hex = self.color.strip_prefix('#').unwrap_or("");
+        if self.color.is_empty() || (hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit())) {
+            Ok(())
+        } else {
+            Err(format!("Invalid account color \"{}\": use e.g. #f38020", self.color))
+        }
+    }
+}
+
+/// Only count traffic from one country, device type and/or hostname. Empty fields
+/// don't filter.
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct DimensionFilters {
+    /// ISO 3166-1 alpha-2 code such as `DE`.
+    pub country: String,
+    /// One of `DEVICE_TYPES`.
+    pub device_type: String,
+    /// Hostname the page was served from, e.g. `blog.example.com`.
+    pub host: String,
+}
+
+pub(crate) const DEVICE_TYPES: &[&str] = &["desktop", "mobile", "tablet"];
+
+impl DimensionFilters {
+    fn validate(&self) -> Result<(), String> {
+        let is_code = self.country.len() == 2 && self.country.chars().all(|c| c.is_ascii_uppercase());
+        if !self.country.is_empty() && !is_code {
+            return Err(format!("Invalid country \"{}\": use a two-letter code such as DE", self.country));
+        }
+        if !self.device_type.is_empty() && !DEVICE_TYPES.contains(&self.device_type.as_str()) {
+            return Err(format!(
+                "Unknown device type \"{}\", use one of {}",
+                self.device_type,
+                DEVICE_TYPES.join(", ")
+            ));
+        }
+        Ok(())
+    }
+
+    /// GraphQL filter clauses for the fields that are set, to `AND` with the others.
+    pub(crate) fn clauses(&self) -> Vec<serde_json::Value> {
+        [("countryName", &self.country), ("deviceType", &self.device_type), ("requestHost", &self.host)]
+            .into_iter()
+            .filter(|(_, value)| !value.is_empty())
+            .map(|(field, value)| serde_json::json!({ field: value }))
+            .collect()
+    }
+}
+
+/// The style for the configured account, if the user gave it a label or color.
+fn account_style(settings: &Settings) -> Option<AccountStyle> {
+    settings
+        .account_styles
+        .get(&settings.account_id)
+        .filter(|style| !style.label.is_empty() || !style.color.is_empty())
+        .cloned()
 }
 
-fn default_true() -> bool {
+pub(crate) fn default_true() -> bool {
     true
 }
 
@@ -35,32 +295,168 @@ fn default_refresh_interval() -> String {
     "15m".to_string()
 }
 
+fn default_tray_title() -> String {
+    "off".to_string()
+}
+
+fn default_cache_ttl_secs() -> u64 {
+    60
+}
+
+fn default_global_shortcut() -> String {
+    crate::shortcut::DEFAULT_SHORTCUT.to_string()
+}
+
+fn default_timezone() -> String {
+    "auto".to_string()
+}
+
+fn default_update_check_interval() -> String {
+    "24h".to_string()
+}
+
+fn default_low_data_mode() -> String {
+    "auto".to_string()
+}
+
+fn default_snapshot_history() -> usize {
+    12
+}
+
+fn default_tray_menu_sites() -> usize {
+    5
+}
+
+fn default_sort_by() -> String {
+    "visits".to_string()
+}
+
+fn default_sort_direction() -> String {
+    "desc".to_string()
+}
+
+fn default_max_concurrent_requests() -> usize {
+    6
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self {
+            schema_version: crate::migrations::CURRENT_VERSION,
             token: String::new(),
+            auth_method: AuthMethod::Token,
+            email: String::new(),
             account_id: String::new(),
             period: String::new(),
             exclude_bots: true,
+            split_bots: false,
             theme: "auto".to_string(),
             refresh_interval: "15m".to_string(),
+            site_refresh_intervals: HashMap::new(),
+            alert_thresholds: HashMap::new(),
+            alert_rules: Vec::new(),
+            alert_quiet_hours: None,
+            tray_title: "off".to_string(),
+            tray_site: String::new(),
+            tray_sparkline: false,
+            refresh_hook: String::new(),
+            alert_scripts: Vec::new(),
+            cache_ttl_secs: default_cache_ttl_secs(),
+            snapshot_history: default_snapshot_history(),
+            account_styles: HashMap::new(),
+            low_data_mode: default_low_data_mode(),
+            proxy_url: String::new(),
+            ca_cert_path: String::new(),
+            launch_at_login: false,
+            global_shortcut: default_global_shortcut(),
+            update_check_interval: default_update_check_interval(),
+            timezone: default_timezone(),
+            show_all_sites: true,
+            tray_menu_sites: default_tray_menu_sites(),
+            hidden_sites: Vec::new(),
+            pinned_sites: Vec::new(),
+            quarter_hour_24h: false,
+            sort_by: default_sort_by(),
+            sort_direction: default_sort_direction(),
+            max_concurrent_requests: default_max_concurrent_requests(),
+            filters: DimensionFilters::default(),
         }
     }
 }
 
-#[derive(Serialize, Clone)]
+impl Settings {
+    pub(crate) fn credentials(&self) -> Credentials {
+        Credentials::new(self.auth_method, &self.token, &self.email)
+    }
+
+    /// Whether the selected auth method and the account ID are all filled in.
+    pub fn has_credentials(&self) -> bool {
+        self.credentials().is_complete() && !self.account_id.is_empty()
+    }
+}
+
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SiteData {
     pub name: String,
     pub visits: u64,
     pub page_views: u64,
+    /// Totals for the equally long window right before the current period.
+    pub prev_visits: u64,
+    pub prev_page_views: u64,
+    /// Percentage change vs the previous window; `None` when it had no traffic.
+    pub visits_change: Option<f64>,
+    pub page_views_change: Option<f64>,
     pub series: Vec<SeriesPoint>,
+    /// Label and color of the account the site belongs to, see `Settings::account_styles`.
+    pub account: Option<AccountStyle>,
+    /// True for the "All sites" entry, which sums the others.
+    pub aggregate: bool,
+    /// Listed in `Settings::pinned_sites`.
+    pub pinned: bool,
+    /// Bot traffic, counted apart from `visits`/`page_views`; `None` unless
+    /// `Settings::split_bots` is on.
+    #[serde(default)]
+    pub bot_visits: Option<u64>,
+    #[serde(default)]
+    pub bot_page_views: Option<u64>,
+    /// The series still hit `SERIES_LIMIT` rows after splitting its range, so the chart
+    /// is missing data; the totals are complete.
+    #[serde(default)]
+    pub truncated: bool,
 }
 
-#[derive(Serialize, Clone)]
+#[derive(Serialize, Deserialize, Clone, Default)]
 pub struct SeriesPoint {
     pub timestamp: String,
     pub visits: u64,
     pub page_views: u64,
+    /// As `SiteData::bot_visits`, per bucket.
+    #[serde(default)]
+    pub bot_visits: Option<u64>,
+    #[serde(default)]
+    pub bot_page_views: Option<u64>,
+    /// For a week bucket cut short by the start or end of the range, how many of its
+    /// days are in it; `None` for whole weeks and other buckets.
+    #[serde(default)]
+    pub days: Option<u32>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct WorkerData {
+    pub script_name: String,
+    pub requests: u64,
+    pub errors: u64,
+    /// Median / 99th percentile CPU time per invocation, in microseconds.
+    pub cpu_time_p50: f64,
+    pub cpu_time_p99: f64,
+    pub series: Vec<WorkerSeriesPoint>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct WorkerSeriesPoint {
+    pub timestamp: String,
+    pub requests: u64,
+    pub errors: u64,
 }
 
 fn settings_path(app: &AppHandle) -> PathBuf {
@@ -73,11 +469,19 @@ fn settings_path(app: &AppHandle) -> PathBuf {
 }
 
 #[tauri::command]
-pub fn get_settings(app: AppHandle) -> Result<Settings, String> {
+pub fn get_settings(app: AppHandle) -> Result<Settings, FetchError> {
     let path = settings_path(&app);
     if path.exists() {
         let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
-        serde_json::from_str(&data).map_err(|e| e.to_string())
+        let mut value: serde_json::Value = serde_json::from_str(&data).map_err(|e| e.to_string())?;
+        let from = crate::migrations::migrate(&mut value)?;
+        if from < crate::migrations::CURRENT_VERSION {
+            // Keep the original next to the upgraded file in case anything was lost
+            fs::write(path.with_extension(format!("v{}.json", from)), &data).map_err(|e| e.to_string())?;
+            let upgraded = serde_json::to_string_pretty(&value).map_err(|e| e.to_string())?;
+            fs::write(&path, upgraded).map_err(|e| e.to_string())?;
+        }
+        serde_json::from_value(value).map_err(|e| e.to_string().into())
     } else {
         Ok(Settings {
             period: "24h".to_string(),
@@ -87,68 +491,433 @@ pub fn get_settings(app: AppHandle) -> Result<Settings, String> {
 }
 
 #[tauri::command]
-pub fn save_settings(app: AppHandle, settings: Settings) -> Result<(), String> {
+pub fn save_settings(app: AppHandle, mut settings: Settings) -> Result<(), FetchError> {
+    parse_interval(&settings.refresh_interval)?;
+    for (site, interval) in &settings.site_refresh_intervals {
+        parse_interval(interval).map_err(|e| format!("{}: {}", site, e))?;
+    }
+    crate::updater::check_interval(&settings.update_check_interval)?;
+    parse_timezone(&settings.timezone)?;
+    validate_sort(&settings)?;
+    if settings.max_concurrent_requests == 0 {
+        return Err("max_concurrent_requests must be at least 1".into());
+    }
+    settings.schema_version = crate::migrations::CURRENT_VERSION;
+    api_client(&settings)?;
+    for style in settings.account_styles.values() {
+        style.validate()?;
+    }
+    settings.filters.validate()?;
+    crate::alerts::validate(&settings)?;
+    if settings.auth_method == AuthMethod::ApiKey && !settings.email.is_empty() && !settings.email.contains('@') {
+        return Err(format!("Invalid email \"{}\"", settings.email).into());
+    }
     let path = settings_path(&app);
     let data = serde_json::to_string_pretty(&settings).map_err(|e| e.to_string())?;
-    fs::write(&path, data).map_err(|e| e.to_string())
+    fs::write(&path, data).map_err(|e| e.to_string())?;
+    if settings.tray_title == "off" {
+        crate::tray::clear_title(&app);
+    }
+    Ok(())
+}
+
+#[derive(Serialize)]
+pub struct AppStatus {
+    pub background_refresh: bool,
+    /// Unix milliseconds of the last successful fetch.
+    pub last_refreshed_at: Option<i64>,
 }
 
-async fn fetch_analytics_inner(app: &AppHandle) -> Result<Vec<SiteData>, String> {
+/// Everything the panel needs for its first frame, in a single call.
+#[derive(Serialize)]
+pub struct InitialPayload {
+    pub settings: Settings,
+    /// The last successful fetch, if there was one.
+    pub analytics: Option<AnalyticsSnapshot>,
+    /// True until both the token and account ID are configured.
+    pub needs_setup: bool,
+    pub status: AppStatus,
+}
+
+#[tauri::command]
+pub fn get_initial_payload(app: AppHandle) -> Result<InitialPayload, FetchError> {
     let settings = get_settings(app.clone())?;
-    if settings.token.is_empty() || settings.account_id.is_empty() {
-        return Err("Please configure API token and Account ID in settings".to_string());
-    }
+    let snapshot = app.state::<AnalyticsCache>().latest();
+    let background_refresh = app
+        .state::<RefreshTask>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .as_ref()
+        .is_some_and(|task| !task.is_finished());
+
+    Ok(InitialPayload {
+        needs_setup: !settings.has_credentials(),
+        status: AppStatus {
+            background_refresh,
+            last_refreshed_at: snapshot.as_ref().map(|s| s.fetched_at.timestamp_millis()),
+        },
+        analytics: snapshot.map(|s| s.into_analytics(&settings)),
+        settings,
+    })
+}
+
+/// Open the app data folder (settings, history, journal) in the file manager.
+#[tauri::command]
+pub fn reveal_app_data(app: AppHandle) -> Result<(), FetchError> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    app.opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| e.to_string().into())
+}
 
-    let client = Client::new();
+/// Reveal `settings.json` in the file manager, writing the defaults first if it doesn't exist yet.
+#[tauri::command]
+pub fn reveal_settings_file(app: AppHandle) -> Result<(), FetchError> {
+    let path = settings_path(&app);
+    if !path.exists() {
+        save_settings(app.clone(), get_settings(app.clone())?)?;
+    }
+    app.opener().reveal_item_in_dir(&path).map_err(|e| e.to_string().into())
+}
 
-    let sites = fetch_sites(&client, &settings.token, &settings.account_id).await?;
+pub(crate) async fn fetch_analytics_inner(app: &AppHandle) -> Result<AnalyticsSnapshot, FetchError> {
+    fetch_due_sites(app, &HashSet::new()).await
+}
 
-    let futures: Vec<_> = sites
+/// The previous sites in `skipped`, then everything in `fresh`.
+fn merge_sites(previous: Vec<SiteData>, fresh: Vec<SiteData>, skipped: &HashSet<String>) -> Vec<SiteData> {
+    let mut sites: Vec<SiteData> = previous
         .into_iter()
-        .map(|(name, site_tag)| {
-            let client = client.clone();
-            let token = settings.token.clone();
-            let account_id = settings.account_id.clone();
-            let period = settings.period.clone();
-            let exclude_bots = settings.exclude_bots;
-            async move {
-                fetch_site_analytics(&client, &token, &account_id, &name, &site_tag, &period, exclude_bots).await
-            }
-        })
+        .filter(|site| skipped.contains(&site.name) && !fresh.iter().any(|f| f.name == site.name))
         .collect();
+    sites.extend(fresh);
+    sites
+}
 
-    let results = futures::future::join_all(futures).await;
-    let mut sites_data = Vec::new();
-    for result in results {
-        match result {
-            Ok(data) => sites_data.push(data),
-            Err(e) => eprintln!("Error fetching site data: {}", e),
+/// When each of `sites` was fetched: `now` for those in `fresh`, otherwise their time in
+/// `previous`, falling back to its `fetched_at` for snapshots without per-site times.
+fn site_fetch_times(
+    sites: &[SiteData],
+    fresh: &HashSet<String>,
+    previous: Option<&Snapshot>,
+    now: DateTime<Utc>,
+) -> HashMap<String, DateTime<Utc>> {
+    sites
+        .iter()
+        .map(|site| {
+            let fetched_at = match previous {
+                Some(previous) if !fresh.contains(&site.name) => {
+                    previous.site_fetched_at.get(&site.name).copied().unwrap_or(previous.fetched_at)
+                }
+                _ => now,
+            };
+            (site.name.clone(), fetched_at)
+        })
+        .collect()
+}
+
+/// `fetch_analytics_inner` for every site not in `skip`; those keep their data from the
+/// latest snapshot. Without a snapshot for the current settings every site is fetched.
+pub(crate) async fn fetch_due_sites(app: &AppHandle, skip: &HashSet<String>) -> Result<AnalyticsSnapshot, FetchError> {
+    let settings = get_settings(app.clone())?;
+    let key = cache_key(&settings);
+    let previous = app.state::<AnalyticsCache>().latest().filter(|s| !skip.is_empty() && s.key == key);
+    let no_skip = HashSet::new();
+    let skip = if previous.is_some() { skip } else { &no_skip };
+    let client = crate::budget::metered_client(app, &settings)?;
+    let (fresh, failures) = fetch_sites_reporting(&client, &settings, skip, |site| {
+        let _ = app.emit("analytics-site-loaded", site);
+    })
+    .await?;
+    for (site, error) in &failures {
+        crate::errors::emit_error(app, Some(site), error);
+    }
+    crate::refresh::record_site_fetches(app, &fresh);
+    let now = Utc::now();
+    let fresh_names: HashSet<String> = fresh.iter().map(|s| s.name.clone()).collect();
+    let sites_data = match &previous {
+        Some(previous) => arrange_sites(&settings, merge_sites(previous.sites.clone(), fresh, skip)),
+        None => fresh,
+    };
+    let site_fetched_at = site_fetch_times(&sites_data, &fresh_names, previous.as_ref(), now);
+    crate::errors::emit_summary(app, sites_data.len(), failures.len());
+
+    let snapshot = Snapshot {
+        sites: sites_data.clone(),
+        fetched_at: site_fetched_at.values().min().copied().unwrap_or(now),
+        site_fetched_at,
+        key,
+        period: settings.period.clone(),
+        partial: !failures.is_empty(),
+    };
+    crate::lastfetch::save(app, &snapshot);
+    let total = {
+        let cache = app.state::<AnalyticsCache>();
+        let mut history = cache.0.lock().unwrap();
+        push_snapshot(&mut history, snapshot.clone(), settings.snapshot_history);
+        history.len()
+    };
+    crate::replay::emit_changed(app, total);
+    crate::history::record_refresh(app, &settings, &sites_data);
+    crate::tray::update_title(app, &sites_data);
+    crate::tray::update_sparkline(app, &settings, &sites_data);
+    crate::tray::update_icon(app, &snapshot);
+    crate::tray::update_menu(app, &sites_data);
+    crate::breakdowns::prefetch(app, &sites_data);
+
+    Ok(snapshot.into_analytics(&settings))
+}
+
+/// Name of the entry `with_all_sites` adds.
+pub const ALL_SITES: &str = "All sites";
+
+/// Sum every site into one entry. Series are merged point by point on their timestamps,
+/// keeping the order they first appear in.
+fn aggregate_sites(sites: &[SiteData]) -> SiteData {
+    let mut series: Vec<SeriesPoint> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for point in sites.iter().flat_map(|s| &s.series) {
+        match index.get(&point.timestamp) {
+            Some(&i) => {
+                series[i].visits += point.visits;
+                series[i].page_views += point.page_views;
+                series[i].bot_visits = add_bots(series[i].bot_visits, point.bot_visits);
+                series[i].bot_page_views = add_bots(series[i].bot_page_views, point.bot_page_views);
+            }
+            None => {
+                index.insert(point.timestamp.clone(), series.len());
+                series.push(point.clone());
+            }
         }
     }
 
-    sites_data.sort_by(|a, b| b.visits.cmp(&a.visits));
+    let visits = sites.iter().map(|s| s.visits).sum();
+    let page_views = sites.iter().map(|s| s.page_views).sum();
+    let prev_visits = sites.iter().map(|s| s.prev_visits).sum();
+    let prev_page_views = sites.iter().map(|s| s.prev_page_views).sum();
+    SiteData {
+        name: ALL_SITES.to_string(),
+        visits,
+        page_views,
+        prev_visits,
+        prev_page_views,
+        visits_change: percent_change(visits, prev_visits),
+        page_views_change: percent_change(page_views, prev_page_views),
+        series,
+        account: None,
+        aggregate: true,
+        pinned: false,
+        bot_visits: sites.iter().fold(None, |sum, s| add_bots(sum, s.bot_visits)),
+        bot_page_views: sites.iter().fold(None, |sum, s| add_bots(sum, s.bot_page_views)),
+        truncated: sites.iter().any(|s| s.truncated),
+    }
+}
+
+/// Sum of two bot counts, `None` only when neither side counted bots.
+fn add_bots(a: Option<u64>, b: Option<u64>) -> Option<u64> {
+    match (a, b) {
+        (None, None) => None,
+        _ => Some(a.unwrap_or(0) + b.unwrap_or(0)),
+    }
+}
+
+/// Put the "All sites" entry first when enabled and there is more than one site to sum.
+pub(crate) fn with_all_sites(settings: &Settings, mut sites: Vec<SiteData>) -> Vec<SiteData> {
+    if settings.show_all_sites && sites.len() > 1 {
+        sites.insert(0, aggregate_sites(&sites));
+    }
+    sites
+}
+
+/// What `Settings::sort_by` can order by. "change" is the size of the visits change
+/// vs the previous period, up or down.
+pub(crate) const SORT_KEYS: &[&str] = &["visits", "page_views", "name", "change"];
+
+fn validate_sort(settings: &Settings) -> Result<(), String> {
+    if !SORT_KEYS.contains(&settings.sort_by.as_str()) {
+        return Err(format!("Unknown sort order \"{}\", use one of {}", settings.sort_by, SORT_KEYS.join(", ")));
+    }
+    if !["asc", "desc"].contains(&settings.sort_direction.as_str()) {
+        return Err(format!("Unknown sort direction \"{}\", use asc or desc", settings.sort_direction));
+    }
+    Ok(())
+}
+
+/// Order two sites by `sort_by`. Sites without a change (no previous traffic) go last
+/// either way.
+fn compare_sites(sort_by: &str, descending: bool, a: &SiteData, b: &SiteData) -> Ordering {
+    let ordering = match sort_by {
+        "page_views" => a.page_views.cmp(&b.page_views),
+        "name" => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        "change" => match (a.visits_change, b.visits_change) {
+            (Some(x), Some(y)) => x.abs().total_cmp(&y.abs()),
+            (Some(_), None) => return Ordering::Less,
+            (None, Some(_)) => return Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        },
+        _ => a.visits.cmp(&b.visits),
+    };
+    if descending { ordering.reverse() } else { ordering }
+}
+
+/// Drop hidden sites and put pinned ones first, each group ordered by `Settings::sort_by`.
+/// Sorting is stable, so sites that tie keep their order between refreshes.
+pub(crate) fn arrange_sites(settings: &Settings, mut sites: Vec<SiteData>) -> Vec<SiteData> {
+    sites.retain(|site| !settings.hidden_sites.contains(&site.name));
+    for site in &mut sites {
+        site.pinned = settings.pinned_sites.contains(&site.name);
+    }
+    let descending = settings.sort_direction != "asc";
+    sites.sort_by(|a, b| compare_sites(&settings.sort_by, descending, a, b));
+    sites.sort_by_key(|site| !site.pinned);
+    sites
+}
 
+/// Fetch analytics for every site using `settings` as given, without side effects.
+pub(crate) async fn fetch_all_sites(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+) -> Result<Vec<SiteData>, FetchError> {
+    let (sites_data, failures) = fetch_all_sites_reporting(client, settings, |_| {}).await?;
+    for (site, error) in &failures {
+        warn!(site = %site, error = %error, "Error fetching data");
+    }
     Ok(sites_data)
 }
 
+/// Like `fetch_all_sites`, but also returns the `(site, error)` of every site that failed,
+/// and hands each site to `on_site` as soon as its query completes.
+pub(crate) async fn fetch_all_sites_reporting(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+    on_site: impl FnMut(&SiteData),
+) -> Result<(Vec<SiteData>, Vec<(String, FetchError)>), FetchError> {
+    fetch_sites_reporting(client, settings, &HashSet::new(), on_site).await
+}
+
+/// `fetch_all_sites_reporting` leaving out the sites in `skip`.
+async fn fetch_sites_reporting(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+    skip: &HashSet<String>,
+    mut on_site: impl FnMut(&SiteData),
+) -> Result<(Vec<SiteData>, Vec<(String, FetchError)>), FetchError> {
+    if !settings.has_credentials() {
+        return Err(FetchError::missing_credentials());
+    }
+
+    let plan = crate::lowdata::query_plan(settings);
+
+    let sites = fetch_sites(client, &settings.credentials(), &settings.account_id).await?;
+
+    let requests = sites
+        .into_iter()
+        .filter(|(name, _)| !settings.hidden_sites.contains(name) && !skip.contains(name))
+        .map(|(name, site_tag)| async move {
+            fetch_site_analytics(client, settings, &name, &site_tag, plan)
+                .await
+                .map_err(|e| (name, e))
+        });
+
+    // A file edited by hand can hold 0, which would never poll anything
+    let mut results = futures::stream::iter(requests).buffer_unordered(settings.max_concurrent_requests.max(1));
+    let style = account_style(settings);
+    let mut sites_data = Vec::new();
+    let mut failures = Vec::new();
+    while let Some(result) = results.next().await {
+        match result {
+            Ok(mut data) => {
+                data.pinned = settings.pinned_sites.contains(&data.name);
+                data.account = style.clone();
+                on_site(&data);
+                sites_data.push(data);
+            }
+            Err(failure) => failures.push(failure),
+        }
+    }
+    if !failures.is_empty() {
+        warn!(failed = failures.len(), loaded = sites_data.len(), "Some sites failed to load");
+    }
+
+    Ok((arrange_sites(settings, sites_data), failures))
+}
+
+/// Analytics for every site, served from `AnalyticsCache` when it is younger than
+/// `cache_ttl_secs` unless `force` is set. While offline, the last saved fetch.
 #[tauri::command]
-pub async fn fetch_analytics(app: AppHandle) -> Result<Vec<SiteData>, String> {
-    fetch_analytics_inner(&app).await
+pub async fn fetch_analytics(app: AppHandle, force: Option<bool>) -> Result<AnalyticsSnapshot, FetchError> {
+    if crate::connectivity::still_offline(&app).await {
+        return crate::connectivity::last_snapshot(&app);
+    }
+    if !force.unwrap_or(false) {
+        let settings = get_settings(app.clone())?;
+        let ttl = chrono::Duration::seconds(settings.cache_ttl_secs as i64);
+        let cached = app.state::<AnalyticsCache>().latest();
+        if let Some(snapshot) = cached
+            && snapshot.is_fresh(&cache_key(&settings), ttl, Utc::now())
+        {
+            return Ok(snapshot.into_analytics(&settings));
+        }
+    }
+    match fetch_analytics_inner(&app).await {
+        Err(e) if crate::connectivity::went_offline(&app, &e).await => crate::connectivity::last_snapshot(&app),
+        result => result,
+    }
 }
 
-fn parse_interval_ms(interval: &str) -> u64 {
-    match interval {
-        "5m" => 300_000,
-        "15m" => 900_000,
-        "60m" => 3_600_000,
-        _ => 900_000,
+/// Shortest background refresh interval; every refresh costs one request per site.
+const MIN_REFRESH_INTERVAL_SECS: u64 = 30;
+
+/// Parse a refresh interval such as "30s", "2m", "1h" or "1h30m".
+pub(crate) fn parse_interval(interval: &str) -> Result<std::time::Duration, String> {
+    let invalid = || format!("Invalid refresh interval \"{}\": use e.g. 30s, 5m or 1h", interval);
+    let interval = interval.trim();
+    if interval.is_empty() {
+        return Err(invalid());
+    }
+
+    let mut secs: u64 = 0;
+    let mut digits = String::new();
+    for c in interval.chars() {
+        if c.is_ascii_digit() {
+            digits.push(c);
+            continue;
+        }
+        let unit = match c {
+            's' => 1,
+            'm' => 60,
+            'h' => 3600,
+            _ => return Err(invalid()),
+        };
+        let value: u64 = digits.parse().map_err(|_| invalid())?;
+        secs = value
+            .checked_mul(unit)
+            .and_then(|v| secs.checked_add(v))
+            .ok_or_else(invalid)?;
+        digits.clear();
+    }
+    if !digits.is_empty() {
+        return Err(invalid());
+    }
+
+    if secs < MIN_REFRESH_INTERVAL_SECS {
+        return Err(format!(
+            "Refresh interval must be at least {}s",
+            MIN_REFRESH_INTERVAL_SECS
+        ));
     }
+    Ok(std::time::Duration::from_secs(secs))
 }
 
 #[tauri::command]
-pub async fn start_background_refresh(app: AppHandle) -> Result<(), String> {
+pub async fn start_background_refresh(app: AppHandle) -> Result<(), FetchError> {
     let settings = get_settings(app.clone())?;
-    let interval_ms = parse_interval_ms(&settings.refresh_interval);
+    let interval = parse_interval(&settings.refresh_interval)?;
+    if crate::refresh::is_paused(&app) {
+        return Ok(());
+    }
 
     let state = app.state::<RefreshTask>();
     let mut handle = state.0.lock().map_err(|e| e.to_string())?;
@@ -158,13 +927,62 @@ pub async fn start_background_refresh(app: AppHandle) -> Result<(), String> {
 
     let app_clone = app.clone();
     let task = tokio::spawn(async move {
+        let mut schedule = crate::refresh::Schedule::new(std::time::Instant::now());
         loop {
-            tokio::time::sleep(std::time::Duration::from_millis(interval_ms)).await;
-            match fetch_analytics_inner(&app_clone).await {
+            let current = get_settings(app_clone.clone()).ok();
+            let plan = current.as_ref().map(crate::lowdata::query_plan).unwrap_or(QueryPlan::Full);
+            let default_interval = plan.refresh_interval(interval);
+            let site_intervals: HashMap<String, std::time::Duration> = current
+                .as_ref()
+                .map(crate::refresh::site_intervals)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(site, every)| (site, plan.refresh_interval(every)))
+                .collect();
+            // Tick as often as the most frequently refreshed site needs
+            let tick_interval = site_intervals.values().copied().fold(default_interval, std::cmp::min);
+            let now = std::time::Instant::now();
+            let tick = schedule.next_tick(now, tick_interval, rand::random::<f64>());
+            let wait = crate::budget::throttle(&app_clone, tick.saturating_duration_since(now));
+            crate::refresh::schedule_next(&app_clone, wait);
+            tokio::time::sleep(wait).await;
+            // `connectivity` refreshes as soon as the API can be reached again
+            if crate::refresh::check_auto_pause(&app_clone).await || crate::connectivity::is_offline(&app_clone) {
+                continue;
+            }
+            let Some(skip) =
+                crate::refresh::sites_to_skip(&app_clone, &site_intervals, default_interval, tick_interval)
+            else {
+                continue;
+            };
+            let result = fetch_due_sites(&app_clone, &skip).await;
+            let offline = match &result {
+                Err(e) => crate::connectivity::went_offline(&app_clone, e).await,
+                Ok(_) => false,
+            };
+            // Being offline isn't a failure to back off from
+            if !offline {
+                schedule.record(result.is_ok(), std::time::Instant::now());
+            }
+            match result {
                 Ok(data) => {
-                    let _ = app_clone.emit("analytics-refreshed", data);
+                    crate::refresh::record_run(&app_clone, Ok(()));
+                    let _ = app_clone.emit("analytics-refreshed", &data);
+                    let hook_app = app_clone.clone();
+                    let hook_data = data.sites.clone();
+                    tokio::spawn(async move {
+                        crate::hooks::run_refresh_hook(&hook_app, &hook_data).await;
+                    });
+                    crate::notifications::check_thresholds(&app_clone).await;
+                    crate::alerts::check_rules(&app_clone).await;
+                    crate::scripts::run_alert_scripts(&app_clone, &data.sites).await;
+                }
+                Err(e) => {
+                    crate::refresh::record_run(&app_clone, Err(&e));
+                    if !offline {
+                        crate::errors::emit_error(&app_clone, None, &e);
+                    }
                 }
-                Err(e) => eprintln!("Background refresh error: {}", e),
             }
         }
     });
@@ -173,34 +991,167 @@ pub async fn start_background_refresh(app: AppHandle) -> Result<(), String> {
     Ok(())
 }
 
-async fn fetch_sites(
-    client: &Client,
-    token: &str,
-    account_id: &str,
-) -> Result<Vec<(String, String)>, String> {
-    let url = format!(
-        "https://api.cloudflare.com/client/v4/accounts/{}/rum/site_info/list",
-        account_id
-    );
+const MAX_ATTEMPTS: u32 = 4;
+const BASE_RETRY_DELAY_MS: u64 = 500;
+const MAX_RETRY_DELAY_MS: u64 = 30_000;
+
+/// Parse a `Retry-After` header given in seconds (the form Cloudflare sends).
+fn parse_retry_after(value: &str) -> Option<std::time::Duration> {
+    value
+        .trim()
+        .parse::<u64>()
+        .ok()
+        .map(std::time::Duration::from_secs)
+}
 
-    let resp = client
-        .get(&url)
-        .header("Authorization", format!("Bearer {}", token))
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+/// Delay before retry number `attempt` (1-based): the server's `Retry-After` when
+/// given, otherwise exponential backoff with up to 50% random jitter (`jitter` in 0..1).
+fn backoff_delay(
+    attempt: u32,
+    retry_after: Option<std::time::Duration>,
+    jitter: f64,
+) -> std::time::Duration {
+    let max = std::time::Duration::from_millis(MAX_RETRY_DELAY_MS);
+    if let Some(delay) = retry_after {
+        return delay.min(max);
+    }
+    let base = BASE_RETRY_DELAY_MS.saturating_mul(1 << (attempt - 1).min(16));
+    let with_jitter = base as f64 * (1.0 + jitter.clamp(0.0, 1.0) * 0.5);
+    std::time::Duration::from_millis(with_jitter as u64).min(max)
+}
+
+const USER_AGENT: &str = concat!("FlareStats/", env!("CARGO_PKG_VERSION"));
+const CORRELATION_HEADER: &str = "X-Correlation-ID";
+const CONNECT_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+/// Per attempt, so one hung request can't hold up a whole refresh.
+const REQUEST_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// HTTP client for Cloudflare API calls, identifying the app via `User-Agent` and
+/// going through the proxy and extra root certificates from `settings`, if set.
+/// Commands share one through `client::shared_client` rather than building their own.
+pub(crate) fn api_client(settings: &Settings) -> Result<Client, String> {
+    let mut builder = Client::builder()
+        .user_agent(USER_AGENT)
+        .connect_timeout(CONNECT_TIMEOUT)
+        .timeout(REQUEST_TIMEOUT);
+
+    let proxy_url = settings.proxy_url.trim();
+    if !proxy_url.is_empty() {
+        let proxy = reqwest::Proxy::all(proxy_url)
+            .map_err(|e| format!("Invalid proxy URL \"{}\": {}", proxy_url, e))?;
+        builder = builder.proxy(proxy);
+    }
+
+    for cert in root_certificates(settings)? {
+        builder = builder.add_root_certificate(cert);
+    }
+
+    builder.build().map_err(|e| format!("Error building HTTP client: {}", e))
+}
+
+/// The extra root certificates in `Settings::ca_cert_path`, trusted on top of the system
+/// ones; none when it isn't set.
+pub(crate) fn root_certificates(settings: &Settings) -> Result<Vec<reqwest::Certificate>, String> {
+    let ca_cert_path = settings.ca_cert_path.trim();
+    if ca_cert_path.is_empty() {
+        return Ok(Vec::new());
+    }
+    let pem = fs::read(ca_cert_path).map_err(|e| format!("Can't read CA certificate {}: {}", ca_cert_path, e))?;
+    let certs = reqwest::Certificate::from_pem_bundle(&pem)
+        .map_err(|e| format!("Invalid CA certificate {}: {}", ca_cert_path, e))?;
+    if certs.is_empty() {
+        return Err(format!("No PEM certificates found in {}", ca_cert_path));
+    }
+    Ok(certs)
+}
+
+/// Short random ID sent with a request so it can be matched up in logs and error reports.
+pub(crate) fn new_correlation_id() -> String {
+    format!("{:012x}", rand::random::<u64>() & 0xffff_ffff_ffff)
+}
+
+/// Append the correlation ID to an error message.
+pub(crate) fn tag_error(message: impl std::fmt::Display, correlation_id: &str) -> String {
+    format!("{} [request {}]", message, correlation_id)
+}
+
+/// Send a request tagged with `correlation_id`, retrying 429 responses, 5xx responses and
+/// connection failures. Other responses (including 4xx) are returned to the caller as-is.
+pub(crate) async fn send_with_retry(
+    correlation_id: &str,
+    build: impl Fn() -> reqwest::RequestBuilder,
+) -> Result<reqwest::Response, FetchError> {
+    let mut attempt = 0;
+    loop {
+        attempt += 1;
+        let (client, request) = build().header(CORRELATION_HEADER, correlation_id).build_split();
+        let request = request.map_err(|e| FetchError::Other { message: tag_error(e, correlation_id) })?;
+        debug!(
+            request_id = correlation_id,
+            method = %request.method(),
+            url = %request.url(),
+            headers = %crate::logging::redacted_headers(request.headers()),
+            body = %request.body().and_then(|b| b.as_bytes()).map(String::from_utf8_lossy).unwrap_or_default(),
+            "API request"
+        );
+        let started = std::time::Instant::now();
+        let result = client.execute(request).await;
+        let elapsed_ms = started.elapsed().as_millis() as u64;
+        match &result {
+            Ok(resp) => debug!(request_id = correlation_id, status = %resp.status(), elapsed_ms, "API response"),
+            Err(e) => debug!(request_id = correlation_id, error = %e, elapsed_ms, "API request failed"),
+        }
 
-    if !resp.status().is_success() {
-        let status = resp.status();
-        let body = resp.text().await.unwrap_or_default();
-        return Err(format!("API error {}: {}", status, body));
+        let (error, retry_after) = match result {
+            Ok(resp) if resp.status() == reqwest::StatusCode::TOO_MANY_REQUESTS => {
+                let retry_after = resp
+                    .headers()
+                    .get(reqwest::header::RETRY_AFTER)
+                    .and_then(|v| v.to_str().ok())
+                    .and_then(parse_retry_after);
+                let error = FetchError::RateLimit {
+                    message: "Rate limited by Cloudflare (429)".to_string(),
+                    retry_after_secs: retry_after.map(|d| d.as_secs()),
+                };
+                (error, retry_after)
+            }
+            Ok(resp) if resp.status().is_server_error() => {
+                (FetchError::Network { message: format!("Server error {}", resp.status()) }, None)
+            }
+            Ok(resp) => return Ok(resp),
+            Err(e) if e.is_timeout() => (FetchError::Timeout { message: format!("Network error: {}", e) }, None),
+            Err(e) if e.is_connect() => (FetchError::Network { message: format!("Network error: {}", e) }, None),
+            Err(e) => return Err(FetchError::Network { message: tag_error(e, correlation_id) }),
+        };
+
+        if attempt >= MAX_ATTEMPTS {
+            return Err(error.map_message(|reason| {
+                tag_error(format!("{}; gave up after {} attempts", reason, attempt), correlation_id)
+            }));
+        }
+        warn!(
+            request_id = correlation_id,
+            "{}; retrying (attempt {}/{})",
+            error,
+            attempt + 1,
+            MAX_ATTEMPTS
+        );
+        tokio::time::sleep(backoff_delay(attempt, retry_after, rand::random::<f64>())).await;
     }
+}
 
-    let body: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+const SITES_PER_PAGE: u64 = 100;
+/// Safety cap so a misbehaving `result_info` can't keep us paging forever.
+const MAX_SITE_PAGES: u64 = 100;
 
-    let sites = body["result"]
+/// Parse one page of `rum/site_info/list` into `(name, site_tag)` pairs,
+/// along with whether another page follows.
+fn parse_sites_page(body: &serde_json::Value, page: u64) -> Result<(Vec<(String, String)>, bool), String> {
+    let result = body["result"]
         .as_array()
-        .ok_or("Invalid response: missing result array")?
+        .ok_or("Invalid response: missing result array")?;
+
+    let sites = result
         .iter()
         .filter_map(|site| {
             let name = site["ruleset"]["zone_name"].as_str()?.to_string();
@@ -209,162 +1160,778 @@ async fn fetch_sites(
         })
         .collect();
 
+    let info = &body["result_info"];
+    let per_page = info["per_page"].as_u64().unwrap_or(SITES_PER_PAGE);
+    let has_more = if let Some(total_pages) = info["total_pages"].as_u64() {
+        page < total_pages
+    } else if let Some(total_count) = info["total_count"].as_u64() {
+        page * per_page < total_count
+    } else {
+        result.len() as u64 >= per_page
+    };
+
+    Ok((sites, has_more && !result.is_empty()))
+}
+
+/// Request pages starting at 1 until the last one has been collected,
+/// handing each page to `on_page` as it arrives.
+async fn collect_site_pages<F, Fut>(
+    mut fetch_page: F,
+    mut on_page: impl FnMut(&[(String, String)]),
+) -> Result<Vec<(String, String)>, FetchError>
+where
+    F: FnMut(u64) -> Fut,
+    Fut: std::future::Future<Output = Result<serde_json::Value, FetchError>>,
+{
+    let mut sites = Vec::new();
+    for page in 1..=MAX_SITE_PAGES {
+        let body = fetch_page(page).await?;
+        let (batch, has_more) = parse_sites_page(&body, page).map_err(FetchError::parse)?;
+        on_page(&batch);
+        sites.extend(batch);
+        if !has_more {
+            break;
+        }
+    }
     Ok(sites)
 }
 
-async fn fetch_site_analytics(
-    client: &Client,
-    token: &str,
+pub(crate) async fn fetch_sites(
+    client: &impl CloudflareClient,
+    auth: &Credentials,
+    account_id: &str,
+) -> Result<Vec<(String, String)>, FetchError> {
+    fetch_sites_streaming(client, auth, account_id, |_| {}).await
+}
+
+/// Like `fetch_sites`, but calls `on_page` with each page of sites as it arrives.
+pub(crate) async fn fetch_sites_streaming(
+    client: &impl CloudflareClient,
+    auth: &Credentials,
     account_id: &str,
+    on_page: impl FnMut(&[(String, String)]),
+) -> Result<Vec<(String, String)>, FetchError> {
+    let url = format!("{}/accounts/{}/rum/site_info/list", API_BASE, account_id);
+
+    collect_site_pages(|page| {
+        let url = &url;
+        async move {
+            let query = [("page", page.to_string()), ("per_page", SITES_PER_PAGE.to_string())];
+            client.get(auth, url, &query).await?.success_json()
+        }
+    }, on_page)
+    .await
+}
+
+/// Rows the API returns per group query at most; anything past it is dropped without
+/// an error, see `fetch_series_chunked`.
+const SERIES_LIMIT: usize = 5000;
+/// How many times a range whose series fills `SERIES_LIMIT` is halved before the
+/// series is flagged `truncated` instead. Each half that fills up is split again, so a
+/// range costs up to 2 + 4 + 8 + 16 = 30 queries on top of the original one.
+const MAX_SERIES_SPLITS: u32 = 4;
+
+/// The `series` field of an analytics query, grouped by `dimension` (and `bot_dimension`).
+fn series_selection(dimension: &str, bot_dimension: &str) -> String {
+    format!(
+        r#"series: rumPageloadEventsAdaptiveGroups(limit: {SERIES_LIMIT}, filter: $filter) {{
+        count
+        sum {{ visits }}
+        dimensions {{ ts: {dimension} {bot_dimension} }}
+      }}"#
+    )
+}
+
+/// `from..to` split at its midpoint.
+fn halve(from: DateTime<Utc>, to: DateTime<Utc>) -> [(DateTime<Utc>, DateTime<Utc>); 2] {
+    let mid = from + (to - from) / 2;
+    [(from, mid), (mid, to)]
+}
+
+/// Series rows for `start..=end` after a single query came back full: the range is
+/// queried in halves, and any half that is full again is halved once more, up to
+/// `MAX_SERIES_SPLITS` times. Rows of a bucket cut in two by a split are summed like
+/// any other rows with the same timestamp. Returns the rows and whether some range
+/// was still full at the last split.
+async fn fetch_series_chunked(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+    series: &str,
+    scope: &[serde_json::Value],
+    start: &str,
+    end: &str,
+) -> Result<(Vec<serde_json::Value>, bool), FetchError> {
+    let query = format!(
+        r#"{{
+  viewer {{
+    accounts(filter: {{ accountTag: $accountTag }}) {{
+      {series}
+    }}
+  }}
+}}"#
+    );
+    let parse = |ts: &str| NaiveDateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%SZ").unwrap_or_default().and_utc();
+    let format = |dt: DateTime<Utc>| dt.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let end = parse(end);
+
+    let mut pending: Vec<_> = halve(parse(start), end).into_iter().rev().map(|range| (range, 1)).collect();
+    let mut rows = Vec::new();
+    let mut truncated = false;
+    while let Some(((from, to), splits)) = pending.pop() {
+        // Only the last range includes its end, so no event is counted twice
+        let range = if to == end {
+            serde_json::json!({ "datetime_geq": format(from), "datetime_leq": format(to) })
+        } else {
+            serde_json::json!({ "datetime_geq": format(from), "datetime_lt": format(to) })
+        };
+        let filters: Vec<_> = std::iter::once(range).chain(scope.iter().cloned()).collect();
+        let variables = serde_json::json!({ "accountTag": settings.account_id, "filter": { "AND": filters } });
+        let data = graphql_request(client, &settings.credentials(), &query, variables).await?;
+        let chunk = data["data"]["viewer"]["accounts"][0]["series"].as_array().cloned().unwrap_or_default();
+        if chunk.len() >= SERIES_LIMIT {
+            if splits < MAX_SERIES_SPLITS {
+                pending.extend(halve(from, to).into_iter().rev().map(|range| (range, splits + 1)));
+                continue;
+            }
+            truncated = true;
+        }
+        rows.extend(chunk);
+    }
+    Ok((rows, truncated))
+}
+
+async fn fetch_site_analytics(
+    client: &impl CloudflareClient,
+    settings: &Settings,
     name: &str,
     site_tag: &str,
-    period: &str,
-    exclude_bots: bool,
-) -> Result<SiteData, String> {
-    let (start, end, ts_field) = get_time_range(period);
+    plan: QueryPlan,
+) -> Result<SiteData, FetchError> {
+    let tz = timezone(settings);
+    let (start, end, bucket) = time_range(settings);
+
+    // Hourly unless charting quarter hours: day buckets are summed up locally so they
+    // follow `tz`, not UTC
+    let series_dimension = if bucket == QUARTER_HOUR_BUCKET { QUARTER_HOUR_BUCKET } else { HOUR_BUCKET };
+    // Split mode groups everything by the `bot` flag as well, so humans and bots come
+    // back as separate rows of the same query
+    let (totals_limit, bot_dimension) = if settings.split_bots { (2, "bot") } else { (1, "") };
+    let series_query = if plan.includes_series() {
+        series_selection(series_dimension, bot_dimension)
+    } else {
+        String::new()
+    };
+    let totals_dimensions = if settings.split_bots { "dimensions { bot }" } else { "" };
 
     let query = format!(
         r#"{{
   viewer {{
     accounts(filter: {{ accountTag: $accountTag }}) {{
-      totals: rumPageloadEventsAdaptiveGroups(limit: 1, filter: $filter) {{
+      totals: rumPageloadEventsAdaptiveGroups(limit: {totals_limit}, filter: $filter) {{
         count
         sum {{ visits }}
+        {totals_dimensions}
       }}
-      series: rumPageloadEventsAdaptiveGroups(limit: 5000, filter: $filter) {{
+      previous: rumPageloadEventsAdaptiveGroups(limit: {totals_limit}, filter: $prevFilter) {{
         count
         sum {{ visits }}
-        dimensions {{ ts: {ts_field} }}
+        {totals_dimensions}
       }}
+      {series_query}
     }}
   }}
 }}"#
     );
 
-    let mut filters = vec![
-        serde_json::json!({ "datetime_geq": start, "datetime_leq": end }),
-        serde_json::json!({ "siteTag": site_tag }),
-    ];
-    if exclude_bots {
-        filters.push(serde_json::json!({ "bot": 0 }));
+    let (prev_start, prev_end) = previous_range(&start, &end);
+
+    // Everything but the time range, shared by every query of this site
+    let mut scope = vec![serde_json::json!({ "siteTag": site_tag })];
+    if settings.exclude_bots && !settings.split_bots {
+        scope.push(serde_json::json!({ "bot": 0 }));
     }
+    scope.extend(settings.filters.clauses());
+    let mut filters = vec![serde_json::json!({ "datetime_geq": start, "datetime_leq": end })];
+    filters.extend(scope.iter().cloned());
+    let mut prev_filters = vec![serde_json::json!({ "datetime_geq": prev_start, "datetime_lt": prev_end })];
+    prev_filters.extend(scope.iter().cloned());
 
     let variables = serde_json::json!({
-        "accountTag": account_id,
-        "filter": { "AND": filters }
+        "accountTag": settings.account_id,
+        "filter": { "AND": filters },
+        "prevFilter": { "AND": prev_filters }
     });
 
-    let body = serde_json::json!({
-        "query": query,
-        "variables": variables,
-    });
+    let data = graphql_request(client, &settings.credentials(), &query, variables).await?;
 
-    let resp = client
-        .post("https://api.cloudflare.com/client/v4/graphql")
-        .header("Authorization", format!("Bearer {}", token))
-        .header("Content-Type", "application/json")
-        .json(&body)
-        .send()
-        .await
-        .map_err(|e| e.to_string())?;
+    let accounts = &data["data"]["viewer"]["accounts"][0];
+
+    let ((visits, page_views), bots) = sum_groups(&accounts["totals"]);
+    let ((prev_visits, prev_page_views), _) = sum_groups(&accounts["previous"]);
 
-    if !resp.status().is_success() {
-        return Err(format!("GraphQL error: {}", resp.status()));
+    let mut series_rows = accounts["series"].as_array().cloned().unwrap_or_default();
+    let mut truncated = false;
+    if series_rows.len() >= SERIES_LIMIT {
+        debug!(site = name, rows = series_rows.len(), "Series hit the row limit, querying it in parts");
+        (series_rows, truncated) = fetch_series_chunked(client, settings, &series_query, &scope, &start, &end).await?;
+        if truncated {
+            warn!(site = name, "Series still over the row limit after splitting, chart is incomplete");
+        }
     }
 
-    let data: serde_json::Value = resp.json().await.map_err(|e| e.to_string())?;
+    let mut raw_series: HashMap<String, (u64, u64)> = HashMap::new();
+    let mut raw_bot_series: HashMap<String, (u64, u64)> = HashMap::new();
+    for point in &series_rows {
+        let Some(ts) = point["dimensions"]["ts"].as_str() else {
+            continue;
+        };
+        let target = if is_bot_group(point) { &mut raw_bot_series } else { &mut raw_series };
+        let entry = target.entry(ts.to_string()).or_default();
+        entry.0 += point["sum"]["visits"].as_u64().unwrap_or(0);
+        entry.1 += point["count"].as_u64().unwrap_or(0);
+    }
 
-    if let Some(errors) = data["errors"].as_array() {
-        if !errors.is_empty() {
-            return Err(format!("GraphQL errors: {:?}", errors));
+    let mut series_data = if plan.includes_series() {
+        fill_series_gaps(&start, &end, bucket, tz, &raw_series)
+    } else {
+        Vec::new()
+    };
+    if settings.split_bots && plan.includes_series() {
+        let bot_series = fill_series_gaps(&start, &end, bucket, tz, &raw_bot_series);
+        for (point, bot) in series_data.iter_mut().zip(bot_series) {
+            point.bot_visits = Some(bot.visits);
+            point.bot_page_views = Some(bot.page_views);
         }
     }
 
-    let accounts = &data["data"]["viewer"]["accounts"][0];
+    Ok(SiteData {
+        name: name.to_string(),
+        visits,
+        page_views,
+        prev_visits,
+        prev_page_views,
+        visits_change: percent_change(visits, prev_visits),
+        page_views_change: percent_change(page_views, prev_page_views),
+        series: series_data,
+        account: None,
+        aggregate: false,
+        pinned: false,
+        bot_visits: settings.split_bots.then_some(bots.0),
+        bot_page_views: settings.split_bots.then_some(bots.1),
+        truncated,
+    })
+}
 
-    let totals = accounts["totals"]
-        .as_array()
-        .and_then(|arr| arr.first());
-    let page_views = totals.map_or(0, |t| t["count"].as_u64().unwrap_or(0));
-    let visits = totals.map_or(0, |t| t["sum"]["visits"].as_u64().unwrap_or(0));
+/// Whether an analytics group is bot traffic; groups without a `bot` dimension aren't.
+fn is_bot_group(group: &serde_json::Value) -> bool {
+    group["dimensions"]["bot"].as_u64().unwrap_or(0) != 0
+}
+
+/// `(visits, page_views)` of human and of bot traffic summed over `groups`.
+fn sum_groups(groups: &serde_json::Value) -> ((u64, u64), (u64, u64)) {
+    let (mut humans, mut bots) = ((0, 0), (0, 0));
+    for group in groups.as_array().into_iter().flatten() {
+        let target = if is_bot_group(group) { &mut bots } else { &mut humans };
+        target.0 += group["sum"]["visits"].as_u64().unwrap_or(0);
+        target.1 += group["count"].as_u64().unwrap_or(0);
+    }
+    (humans, bots)
+}
+
+/// The window of the same length immediately preceding `start..end`, as
+/// `(start, end)` where `end` is exclusive (equal to the current `start`).
+fn previous_range(start: &str, end: &str) -> (String, String) {
+    let start_dt = NaiveDateTime::parse_from_str(start, "%Y-%m-%dT%H:%M:%SZ").unwrap_or_default();
+    let end_dt = NaiveDateTime::parse_from_str(end, "%Y-%m-%dT%H:%M:%SZ").unwrap_or_default();
+    let prev_start = start_dt - (end_dt - start_dt);
+    (
+        prev_start.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        start.to_string(),
+    )
+}
+
+fn percent_change(current: u64, previous: u64) -> Option<f64> {
+    if previous == 0 {
+        return None;
+    }
+    Some((current as f64 - previous as f64) / previous as f64 * 100.0)
+}
+
+/// Total visits for a site over the trailing `window` (no series).
+pub(crate) async fn fetch_recent_visits(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+    site_tag: &str,
+    window: chrono::Duration,
+) -> Result<u64, FetchError> {
+    let now = Utc::now();
+    fetch_visits_between(client, settings, site_tag, now - window, now).await
+}
+
+/// Total visits for a site from `start` to `end` (no series).
+pub(crate) async fn fetch_visits_between(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+    site_tag: &str,
+    start: DateTime<Utc>,
+    end: DateTime<Utc>,
+) -> Result<u64, FetchError> {
+    let query = r#"{
+  viewer {
+    accounts(filter: { accountTag: $accountTag }) {
+      totals: rumPageloadEventsAdaptiveGroups(limit: 1, filter: $filter) {
+        sum { visits }
+      }
+    }
+  }
+}"#;
+
+    let mut filters = vec![
+        serde_json::json!({
+            "datetime_geq": start.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            "datetime_leq": end.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+        }),
+        serde_json::json!({ "siteTag": site_tag }),
+    ];
+    if settings.exclude_bots {
+        filters.push(serde_json::json!({ "bot": 0 }));
+    }
+    filters.extend(settings.filters.clauses());
 
+    let variables = serde_json::json!({
+        "accountTag": settings.account_id,
+        "filter": { "AND": filters }
+    });
+
+    let data = graphql_request(client, &settings.credentials(), query, variables).await?;
+
+    Ok(data["data"]["viewer"]["accounts"][0]["totals"][0]["sum"]["visits"]
+        .as_u64()
+        .unwrap_or(0))
+}
+
+/// Visits per hour of a site over the last 24h, oldest first, for the tray sparkline.
+pub(crate) async fn fetch_hourly_visits(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+    site_tag: &str,
+) -> Result<Vec<u64>, FetchError> {
+    let (start, end, _) = get_time_range("24h", Tz::UTC);
+    let series = series_selection(HOUR_BUCKET, "");
+    let query = format!(
+        r#"{{
+  viewer {{
+    accounts(filter: {{ accountTag: $accountTag }}) {{
+      {series}
+    }}
+  }}
+}}"#
+    );
+
+    let mut filters = vec![
+        serde_json::json!({ "datetime_geq": start, "datetime_leq": end }),
+        serde_json::json!({ "siteTag": site_tag }),
+    ];
+    if settings.exclude_bots {
+        filters.push(serde_json::json!({ "bot": 0 }));
+    }
+    filters.extend(settings.filters.clauses());
+
+    let variables = serde_json::json!({
+        "accountTag": settings.account_id,
+        "filter": { "AND": filters }
+    });
+
+    let data = graphql_request(client, &settings.credentials(), &query, variables).await?;
+
+    let mut hours: HashMap<String, (u64, u64)> = HashMap::new();
+    for point in data["data"]["viewer"]["accounts"][0]["series"].as_array().into_iter().flatten() {
+        if let Some(ts) = point["dimensions"]["ts"].as_str() {
+            hours.entry(ts.to_string()).or_default().0 += point["sum"]["visits"].as_u64().unwrap_or(0);
+        }
+    }
+    Ok(fill_series_gaps(&start, &end, HOUR_BUCKET, Tz::UTC, &hours)
+        .into_iter()
+        .map(|point| point.visits)
+        .collect())
+}
+
+pub(crate) async fn graphql_request(
+    client: &impl CloudflareClient,
+    auth: &Credentials,
+    query: &str,
+    variables: serde_json::Value,
+) -> Result<serde_json::Value, FetchError> {
+    let body = serde_json::json!({
+        "query": query,
+        "variables": variables,
+    });
+
+    let resp = client.post_json(auth, &format!("{}/graphql", API_BASE), &body).await?;
+
+    if !resp.status.is_success() {
+        let message = tag_error(format!("GraphQL error: {}", resp.status), &resp.request_id);
+        return Err(FetchError::for_status(resp.status, message));
+    }
+
+    let data = resp.json()?;
+
+    if let Some(errors) = data["errors"].as_array()
+        && !errors.is_empty()
+    {
+        return Err(FetchError::Graphql {
+            message: tag_error(format!("GraphQL errors: {:?}", errors), &resp.request_id),
+            errors: errors.clone(),
+        });
+    }
+
+    Ok(data)
+}
+
+#[tauri::command]
+pub async fn fetch_workers_analytics(app: AppHandle) -> Result<Vec<WorkerData>, FetchError> {
+    let settings = get_settings(app.clone())?;
+    if !settings.has_credentials() {
+        return Err(FetchError::missing_credentials());
+    }
+
+    let client = crate::budget::metered_client(&app, &settings)?;
+    let tz = timezone(&settings);
+    let (start, end, ts_field) = time_range(&settings);
+    let dimension = query_dimension(ts_field);
+
+    let query = format!(
+        r#"{{
+  viewer {{
+    accounts(filter: {{ accountTag: $accountTag }}) {{
+      totals: workersInvocationsAdaptive(limit: 1000, filter: $filter) {{
+        sum {{ requests errors }}
+        quantiles {{ cpuTimeP50 cpuTimeP99 }}
+        dimensions {{ scriptName }}
+      }}
+      series: workersInvocationsAdaptive(limit: 5000, filter: $filter) {{
+        sum {{ requests errors }}
+        dimensions {{ scriptName ts: {dimension} }}
+      }}
+    }}
+  }}
+}}"#
+    );
+
+    let variables = serde_json::json!({
+        "accountTag": settings.account_id,
+        "filter": { "datetime_geq": start, "datetime_leq": end }
+    });
+
+    let data = graphql_request(&client, &settings.credentials(), &query, variables).await?;
+
+    Ok(parse_workers_response(&data, &start, &end, ts_field, tz))
+}
+
+fn parse_workers_response(
+    data: &serde_json::Value,
+    start: &str,
+    end: &str,
+    ts_field: &str,
+    tz: Tz,
+) -> Vec<WorkerData> {
+    let accounts = &data["data"]["viewer"]["accounts"][0];
     let empty = vec![];
-    let raw_series: HashMap<String, (u64, u64)> = accounts["series"]
+
+    let mut raw_series: HashMap<String, HashMap<String, (u64, u64)>> = HashMap::new();
+    for point in accounts["series"].as_array().unwrap_or(&empty) {
+        let (Some(script), Some(ts)) = (
+            point["dimensions"]["scriptName"].as_str(),
+            point["dimensions"]["ts"].as_str(),
+        ) else {
+            continue;
+        };
+        let requests = point["sum"]["requests"].as_u64().unwrap_or(0);
+        let errors = point["sum"]["errors"].as_u64().unwrap_or(0);
+        let entry = raw_series
+            .entry(script.to_string())
+            .or_default()
+            .entry(ts.to_string())
+            .or_insert((0, 0));
+        entry.0 += requests;
+        entry.1 += errors;
+    }
+
+    let mut workers: Vec<WorkerData> = accounts["totals"]
         .as_array()
         .unwrap_or(&empty)
         .iter()
-        .filter_map(|point| {
-            let ts = point["dimensions"]["ts"].as_str()?.to_string();
-            let v = point["sum"]["visits"].as_u64().unwrap_or(0);
-            let pv = point["count"].as_u64().unwrap_or(0);
-            Some((ts, (v, pv)))
+        .filter_map(|total| {
+            let script_name = total["dimensions"]["scriptName"].as_str()?.to_string();
+            let series = fill_series_gaps(
+                start,
+                end,
+                ts_field,
+                tz,
+                raw_series.get(&script_name).unwrap_or(&HashMap::new()),
+            )
+            .into_iter()
+            .map(|p| WorkerSeriesPoint {
+                timestamp: p.timestamp,
+                requests: p.visits,
+                errors: p.page_views,
+            })
+            .collect();
+            Some(WorkerData {
+                requests: total["sum"]["requests"].as_u64().unwrap_or(0),
+                errors: total["sum"]["errors"].as_u64().unwrap_or(0),
+                cpu_time_p50: total["quantiles"]["cpuTimeP50"].as_f64().unwrap_or(0.0),
+                cpu_time_p99: total["quantiles"]["cpuTimeP99"].as_f64().unwrap_or(0.0),
+                script_name,
+                series,
+            })
         })
         .collect();
 
-    let series_data = fill_series_gaps(&start, &end, ts_field, &raw_series);
-
-    Ok(SiteData {
-        name: name.to_string(),
-        visits,
-        page_views,
-        series: series_data,
-    })
+    workers.sort_by_key(|w| std::cmp::Reverse(w.requests));
+    workers
 }
 
-fn fill_series_gaps(
+/// Zero-filled series from `start` to `end` (UTC), bucketed by `bucket`:
+///
+/// - `"datetimeHour"`: one point per UTC hour, keyed like `2024-01-15T10:00:00Z` in `data`
+///   and labeled in `tz` with its offset, e.g. `2024-01-15T11:00:00+01:00`.
+/// - `"datetimeFifteenMinutes"`: the same, every 15 minutes.
+/// - `"week"`: one point per ISO week (Monday to Sunday) in `tz`, labeled with its Monday.
+///   Only days inside the range are summed, and partial weeks at either edge set `days`.
+/// - anything else: one point per calendar day in `tz`, labeled `YYYY-MM-DD`. `data` may hold
+///   hourly keys, which are summed into the local day they fall on, or plain dates.
+pub(crate) fn fill_series_gaps(
     start: &str,
     end: &str,
-    ts_field: &str,
+    bucket: &str,
+    tz: Tz,
     data: &HashMap<String, (u64, u64)>,
 ) -> Vec<SeriesPoint> {
     let mut series = Vec::new();
-
-    if ts_field == "datetimeHour" {
-        let start_dt = NaiveDateTime::parse_from_str(start, "%Y-%m-%dT%H:%M:%SZ")
-            .unwrap_or_default();
-        let end_dt = NaiveDateTime::parse_from_str(end, "%Y-%m-%dT%H:%M:%SZ")
-            .unwrap_or_default();
-        let end_hour = end_dt
-            .with_minute(0)
-            .unwrap_or(end_dt)
-            .with_second(0)
-            .unwrap_or(end_dt);
-        let mut current = start_dt;
-        while current <= end_hour {
+    let start_dt = NaiveDateTime::parse_from_str(start, "%Y-%m-%dT%H:%M:%SZ")
+        .unwrap_or_default()
+        .and_utc();
+    let end_dt = NaiveDateTime::parse_from_str(end, "%Y-%m-%dT%H:%M:%SZ")
+        .unwrap_or_default()
+        .and_utc();
+
+    if bucket == HOUR_BUCKET || bucket == QUARTER_HOUR_BUCKET {
+        let minutes = if bucket == HOUR_BUCKET { 60 } else { 15 };
+        let truncate = |dt: DateTime<Utc>| {
+            dt.with_minute(dt.minute() - dt.minute() % minutes)
+                .and_then(|d| d.with_second(0))
+                .unwrap_or(dt)
+        };
+        let end_bucket = truncate(end_dt);
+        let mut current = truncate(start_dt);
+        while current <= end_bucket {
             let key = current.format("%Y-%m-%dT%H:%M:%SZ").to_string();
             let (v, pv) = data.get(&key).copied().unwrap_or((0, 0));
             series.push(SeriesPoint {
-                timestamp: key,
+                timestamp: current.with_timezone(&tz).format("%Y-%m-%dT%H:%M:%S%:z").to_string(),
                 visits: v,
                 page_views: pv,
+                ..Default::default()
             });
-            current += chrono::Duration::hours(1);
+            current += chrono::Duration::minutes(minutes as i64);
         }
     } else {
-        let start_d = NaiveDate::parse_from_str(&start[..10], "%Y-%m-%d")
-            .unwrap_or_default();
-        let end_d = NaiveDate::parse_from_str(&end[..10], "%Y-%m-%d")
-            .unwrap_or_default();
-        let mut current = start_d;
+        // Weeks are labelled with their Monday, days with themselves
+        let (bucket_of, step): (fn(NaiveDate) -> NaiveDate, i64) = if bucket == WEEK_BUCKET {
+            (week_start, 7)
+        } else {
+            (|day| day, 1)
+        };
+        let first_day = start_dt.with_timezone(&tz).date_naive();
+        let last_day = end_dt.with_timezone(&tz).date_naive();
+        let mut buckets: HashMap<NaiveDate, (u64, u64)> = HashMap::new();
+        for (key, (v, pv)) in data {
+            if let Some(day) = local_day(key, tz)
+                && (first_day..=last_day).contains(&day)
+            {
+                let entry = buckets.entry(bucket_of(day)).or_insert((0, 0));
+                entry.0 += v;
+                entry.1 += pv;
+            }
+        }
+        let end_d = bucket_of(last_day);
+        let mut current = bucket_of(first_day);
         while current <= end_d {
-            let key = current.format("%Y-%m-%d").to_string();
-            let (v, pv) = data.get(&key).copied().unwrap_or((0, 0));
+            let (v, pv) = buckets.get(&current).copied().unwrap_or((0, 0));
+            let bucket_end = current + chrono::Duration::days(step - 1);
+            let covered = (bucket_end.min(last_day) - current.max(first_day)).num_days() + 1;
             series.push(SeriesPoint {
-                timestamp: key,
+                timestamp: current.format("%Y-%m-%d").to_string(),
                 visits: v,
                 page_views: pv,
+                days: (covered < step).then_some(covered as u32),
+                ..Default::default()
             });
-            current += chrono::Duration::days(1);
+            current += chrono::Duration::days(step);
         }
     }
 
     series
 }
 
-fn get_time_range(period: &str) -> (String, String, &'static str) {
-    let now = Utc::now();
+/// Monday of the week `day` is in.
+fn week_start(day: NaiveDate) -> NaiveDate {
+    day - chrono::Duration::days(day.weekday().num_days_from_monday() as i64)
+}
+
+/// The day in `tz` a series key falls on: a plain `YYYY-MM-DD` (a local day already, as
+/// the history store keeps them) is taken as is, a UTC timestamp is converted first.
+fn local_day(key: &str, tz: Tz) -> Option<NaiveDate> {
+    if let Ok(day) = NaiveDate::parse_from_str(key, "%Y-%m-%d") {
+        return Some(day);
+    }
+    DateTime::parse_from_rfc3339(key)
+        .ok()
+        .map(|dt| dt.with_timezone(&tz).date_naive())
+}
+
+/// Parse `Settings::timezone`: "auto" (or empty) is the system zone, `None` if it can't be detected.
+pub(crate) fn parse_timezone(name: &str) -> Result<Option<Tz>, String> {
+    match name.trim() {
+        "" | "auto" => Ok(iana_time_zone::get_timezone().ok().and_then(|n| n.parse().ok())),
+        name => name
+            .parse()
+            .map(Some)
+            .map_err(|_| format!("Unknown timezone \"{}\": use an IANA name such as Europe/Warsaw, or auto", name)),
+    }
+}
+
+/// The zone to bucket in, falling back to UTC.
+pub(crate) fn timezone(settings: &Settings) -> Tz {
+    parse_timezone(&settings.timezone).ok().flatten().unwrap_or(Tz::UTC)
+}
+
+/// The exact window and bucketing a fetch for a given period would use.
+#[derive(Serialize, Debug)]
+pub struct TimeRangePreview {
+    /// Boundaries in UTC, as sent to the API.
+    pub start: String,
+    pub end: String,
+    /// "15m", "hour", "day" or "week".
+    pub bucket: &'static str,
+    pub buckets: usize,
+    pub timezone: String,
+    /// e.g. "Jan 15 00:00 – Jan 21 13:00 in Europe/Warsaw"
+    pub description: String,
+}
+
+fn time_range_preview(start: &str, end: &str, ts_field: &str, tz: Tz) -> Result<TimeRangePreview, String> {
+    let parse = |s: &str| {
+        DateTime::parse_from_rfc3339(s)
+            .map(|dt| dt.with_timezone(&tz))
+            .map_err(|e| e.to_string())
+    };
+    let (local_start, local_end) = (parse(start)?, parse(end)?);
+
+    Ok(TimeRangePreview {
+        start: start.to_string(),
+        end: end.to_string(),
+        bucket: match ts_field {
+            QUARTER_HOUR_BUCKET => "15m",
+            HOUR_BUCKET => "hour",
+            WEEK_BUCKET => "week",
+            _ => "day",
+        },
+        buckets: fill_series_gaps(start, end, ts_field, tz, &HashMap::new()).len(),
+        timezone: tz.name().to_string(),
+        description: format!(
+            "{} – {} in {}",
+            local_start.format("%b %-d %H:%M"),
+            local_end.format("%b %-d %H:%M"),
+            tz.name()
+        ),
+    })
+}
+
+/// Preview the time range `period` would query in `timezone` (IANA name or "auto", default UTC),
+/// with `quarter_hour_24h` as in `Settings`.
+#[tauri::command]
+pub fn preview_time_range(
+    period: String,
+    timezone: Option<String>,
+    quarter_hour_24h: Option<bool>,
+) -> Result<TimeRangePreview, FetchError> {
+    let tz = match timezone {
+        Some(name) => parse_timezone(&name)?.unwrap_or(Tz::UTC),
+        None => Tz::UTC,
+    };
+    let (start, end, ts_field) = get_time_range(&period, tz);
+    let ts_field = quarter_hours(&period, quarter_hour_24h.unwrap_or(false), ts_field);
+    Ok(time_range_preview(&start, &end, ts_field, tz)?)
+}
+
+/// Periods the panel offers; anything else falls back to 30 days. "today", "this_week"
+/// and "this_month" follow the calendar in `Settings::timezone`, the rest are rolling.
+pub(crate) const PERIODS: &[&str] = &["3h", "6h", "24h", "7d", "30d", "90d", "today", "this_week", "this_month"];
+
+/// Bucket values returned by `get_time_range`. Quarter hours and hours are also the
+/// GraphQL dimensions to group by; days and weeks are summed up locally, see `query_dimension`.
+pub(crate) const QUARTER_HOUR_BUCKET: &str = "datetimeFifteenMinutes";
+pub(crate) const HOUR_BUCKET: &str = "datetimeHour";
+pub(crate) const DAY_BUCKET: &str = "date";
+pub(crate) const WEEK_BUCKET: &str = "week";
+
+/// Ranges of more days than this are bucketed by week, see `bucket_for_days`.
+pub(crate) const WEEKLY_AFTER_DAYS: i64 = 60;
+
+/// `WEEK_BUCKET` for ranges over `WEEKLY_AFTER_DAYS` days, where daily points get too
+/// dense to read, otherwise `DAY_BUCKET`.
+pub(crate) fn bucket_for_days(days: i64) -> &'static str {
+    if days > WEEKLY_AFTER_DAYS { WEEK_BUCKET } else { DAY_BUCKET }
+}
+
+/// GraphQL dimension to group by for `bucket`. The API's `date` is a UTC date, so days
+/// and weeks are queried by hour and summed into days in the timezone by `fill_series_gaps`.
+pub(crate) fn query_dimension(bucket: &str) -> &'static str {
+    match bucket {
+        QUARTER_HOUR_BUCKET => QUARTER_HOUR_BUCKET,
+        _ => HOUR_BUCKET,
+    }
+}
+
+/// `(start, end, bucket)` in UTC for `period`. The 3h and 6h windows start on a whole
+/// quarter hour and are bucketed by 15 minutes; the 24h window starts on a whole UTC hour
+/// and "today" at local midnight, both bucketed by hour; longer periods start at midnight
+/// in `tz` and are bucketed by day, or by week past `WEEKLY_AFTER_DAYS`.
+pub(crate) fn get_time_range(period: &str, tz: Tz) -> (String, String, &'static str) {
+    time_range_at(period, tz, Utc::now())
+}
+
+/// `get_time_range` for the configured period and timezone, in 15-minute buckets for 24h
+/// when `Settings::quarter_hour_24h` asks for it. At most 96 points per series, well
+/// within the API's 5000 rows.
+pub(crate) fn time_range(settings: &Settings) -> (String, String, &'static str) {
+    let (start, end, bucket) = get_time_range(&settings.period, timezone(settings));
+    (start, end, quarter_hours(&settings.period, settings.quarter_hour_24h, bucket))
+}
 
-    match period {
+fn quarter_hours(period: &str, quarter_hour_24h: bool, bucket: &'static str) -> &'static str {
+    if period == "24h" && quarter_hour_24h { QUARTER_HOUR_BUCKET } else { bucket }
+}
+
+fn time_range_at(period: &str, tz: Tz, now: DateTime<Utc>) -> (String, String, &'static str) {
+    let format = |dt: DateTime<Utc>| dt.format("%Y-%m-%dT%H:%M:%SZ").to_string();
+    let today = now.with_timezone(&tz).date_naive();
+
+    let (first_day, bucket) = match period {
+        "3h" | "6h" => {
+            let hours = if period == "3h" { 3 } else { 6 };
+            let start = now - chrono::Duration::hours(hours) + chrono::Duration::minutes(15);
+            let start = start
+                .with_minute(start.minute() - start.minute() % 15)
+                .unwrap()
+                .with_second(0)
+                .unwrap()
+                .with_nanosecond(0)
+                .unwrap();
+            return (format(start), format(now), QUARTER_HOUR_BUCKET);
+        }
         "24h" => {
             let start = (now - chrono::Duration::hours(23))
                 .with_minute(0)
@@ -373,28 +1940,35 @@ fn get_time_range(period: &str) -> (String, String, &'static str) {
                 .unwrap()
                 .with_nanosecond(0)
                 .unwrap();
-            (
-                start.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
-                now.format("%Y-%m-%dT%H:%M:%SZ").to_string(),
-                "datetimeHour",
-            )
-        }
-        "7d" => {
-            let start = (now - chrono::Duration::days(6))
-                .format("%Y-%m-%dT00:00:00Z")
-                .to_string();
-            let end = now.format("%Y-%m-%dT%H:%M:%SZ").to_string();
-            (start, end, "date")
+            return (format(start), format(now), HOUR_BUCKET);
         }
+        "today" => (today, HOUR_BUCKET),
+        "this_week" => (week_start(today), DAY_BUCKET),
+        "this_month" => (today.with_day(1).unwrap_or(today), DAY_BUCKET),
         _ => {
             // 30d default
-            let start = (now - chrono::Duration::days(29))
-                .format("%Y-%m-%dT00:00:00Z")
-                .to_string();
-            let end = now.format("%Y-%m-%dT%H:%M:%SZ").to_string();
-            (start, end, "date")
+            let days = match period {
+                "7d" => 7,
+                "90d" => 90,
+                _ => 30,
+            };
+            let first_day = today - chrono::Duration::days(days - 1);
+            match bucket_for_days(days) {
+                // Whole weeks, so the first bucket isn't a partial one
+                WEEK_BUCKET => (week_start(first_day), WEEK_BUCKET),
+                bucket => (first_day, bucket),
+            }
         }
-    }
+    };
+    (format(local_midnight(first_day, tz)), format(now), bucket)
+}
+
+/// Start of `day` in `tz`, as UTC. Where a DST change skips midnight, the first valid time that day.
+fn local_midnight(day: NaiveDate, tz: Tz) -> DateTime<Utc> {
+    (0..24)
+        .find_map(|hour| tz.from_local_datetime(&day.and_hms_opt(hour, 0, 0)?).earliest())
+        .map(|dt| dt.with_timezone(&Utc))
+        .unwrap_or_else(|| day.and_hms_opt(0, 0, 0).unwrap_or_default().and_utc())
 }
 
 #[cfg(test)]
@@ -406,7 +1980,7 @@ mod tests {
 
     #[test]
     fn test_get_time_range_24h_returns_datetime_hour() {
-        let (start, end, field) = get_time_range("24h");
+        let (start, end, field) = get_time_range("24h", Tz::UTC);
         assert_eq!(field, "datetimeHour");
         assert!(start.ends_with("Z"));
         assert!(end.ends_with("Z"));
@@ -422,7 +1996,7 @@ mod tests {
 
     #[test]
     fn test_get_time_range_7d_returns_date() {
-        let (start, end, field) = get_time_range("7d");
+        let (start, end, field) = get_time_range("7d", Tz::UTC);
         assert_eq!(field, "date");
         assert!(start.contains("T00:00:00Z"));
         let start_dt = NaiveDateTime::parse_from_str(&start, "%Y-%m-%dT%H:%M:%SZ").unwrap();
@@ -433,7 +2007,7 @@ mod tests {
 
     #[test]
     fn test_get_time_range_30d_returns_date() {
-        let (start, end, field) = get_time_range("30d");
+        let (start, end, field) = get_time_range("30d", Tz::UTC);
         assert_eq!(field, "date");
         assert!(start.contains("T00:00:00Z"));
         let start_dt = NaiveDateTime::parse_from_str(&start, "%Y-%m-%dT%H:%M:%SZ").unwrap();
@@ -442,9 +2016,72 @@ mod tests {
         assert!(diff >= Duration::days(28) && diff <= Duration::days(30));
     }
 
+    fn at(s: &str) -> DateTime<Utc> {
+        DateTime::parse_from_rfc3339(s).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn test_time_range_today_starts_at_local_midnight() {
+        let now = at("2024-01-17T10:20:00Z");
+        let (start, end, bucket) = time_range_at("today", chrono_tz::Europe::Warsaw, now);
+        assert_eq!(start, "2024-01-16T23:00:00Z");
+        assert_eq!(end, "2024-01-17T10:20:00Z");
+        assert_eq!(bucket, HOUR_BUCKET);
+    }
+
+    #[test]
+    fn test_time_range_calendar_presets() {
+        // A Wednesday
+        let now = at("2024-01-17T10:20:00Z");
+        assert_eq!(time_range_at("this_week", Tz::UTC, now).0, "2024-01-15T00:00:00Z");
+        assert_eq!(time_range_at("this_month", Tz::UTC, now).0, "2024-01-01T00:00:00Z");
+        assert_eq!(time_range_at("this_month", Tz::UTC, now).2, DAY_BUCKET);
+    }
+
+    #[test]
+    fn test_time_range_90d_starts_on_a_monday() {
+        let now = at("2024-04-17T10:20:00Z");
+        let (start, _, bucket) = time_range_at("90d", Tz::UTC, now);
+        // 89 days back is Thursday 2024-01-19, its week starts on the 15th
+        assert_eq!(start, "2024-01-15T00:00:00Z");
+        assert_eq!(bucket, WEEK_BUCKET);
+    }
+
+    #[test]
+    fn test_time_range_short_periods_use_quarter_hours() {
+        let now = at("2024-01-17T10:20:00Z");
+        assert_eq!(
+            time_range_at("3h", Tz::UTC, now),
+            ("2024-01-17T07:30:00Z".to_string(), "2024-01-17T10:20:00Z".to_string(), QUARTER_HOUR_BUCKET)
+        );
+        assert_eq!(time_range_at("6h", Tz::UTC, now).0, "2024-01-17T04:30:00Z");
+    }
+
+    #[test]
+    fn test_time_range_24h_quarter_hours_opt_in() {
+        let mut settings = Settings {
+            period: "24h".to_string(),
+            timezone: "UTC".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(time_range(&settings).2, HOUR_BUCKET);
+        settings.quarter_hour_24h = true;
+        assert_eq!(time_range(&settings).2, QUARTER_HOUR_BUCKET);
+        settings.period = "7d".to_string();
+        assert_eq!(time_range(&settings).2, DAY_BUCKET);
+    }
+
+    #[test]
+    fn test_query_dimension_queries_days_and_weeks_by_hour() {
+        assert_eq!(query_dimension(QUARTER_HOUR_BUCKET), "datetimeFifteenMinutes");
+        assert_eq!(query_dimension(HOUR_BUCKET), "datetimeHour");
+        assert_eq!(query_dimension(DAY_BUCKET), "datetimeHour");
+        assert_eq!(query_dimension(WEEK_BUCKET), "datetimeHour");
+    }
+
     #[test]
     fn test_get_time_range_unknown_period_defaults_to_30d() {
-        let (start, _, field) = get_time_range("unknown");
+        let (start, _, field) = get_time_range("unknown", Tz::UTC);
         assert_eq!(field, "date");
         assert!(start.contains("T00:00:00Z"));
     }
@@ -461,6 +2098,7 @@ mod tests {
             "2024-01-15T00:00:00Z",
             "2024-01-15T03:30:00Z",
             "datetimeHour",
+            Tz::UTC,
             &data,
         );
         assert_eq!(series.len(), 4); // 00, 01, 02, 03 (end truncated to 03:00)
@@ -483,6 +2121,7 @@ mod tests {
             "2024-01-15T00:00:00Z",
             "2024-01-18T00:00:00Z",
             "date",
+            Tz::UTC,
             &data,
         );
         assert_eq!(series.len(), 4); // 15, 16, 17, 18
@@ -499,6 +2138,7 @@ mod tests {
             "2024-01-15T00:00:00Z",
             "2024-01-15T02:30:00Z",
             "datetimeHour",
+            Tz::UTC,
             &data,
         );
         assert_eq!(series.len(), 3); // 00, 01, 02 (end truncated to 02:00)
@@ -515,6 +2155,7 @@ mod tests {
             "2024-01-15T00:00:00Z",
             "2024-01-16T23:59:59Z",
             "date",
+            Tz::UTC,
             &data,
         );
         assert_eq!(series.len(), 2);
@@ -522,6 +2163,428 @@ mod tests {
         assert_eq!(series[1].visits, 3);
     }
 
+    #[test]
+    fn test_fill_series_gaps_sums_hours_into_local_days() {
+        // Warsaw is UTC+1: 23:00Z is already the next day there, 22:00Z is before the range
+        let data: HashMap<String, (u64, u64)> = HashMap::from([
+            ("2024-01-14T22:00:00Z".to_string(), (1, 1)),
+            ("2024-01-14T23:00:00Z".to_string(), (2, 2)),
+            ("2024-01-15T12:00:00Z".to_string(), (4, 4)),
+            ("2024-01-15T23:00:00Z".to_string(), (8, 8)),
+        ]);
+        let series = fill_series_gaps(
+            "2024-01-14T23:00:00Z",
+            "2024-01-16T10:00:00Z",
+            "date",
+            chrono_tz::Europe::Warsaw,
+            &data,
+        );
+        let days: Vec<_> = series.iter().map(|p| (p.timestamp.as_str(), p.visits)).collect();
+        assert_eq!(days, vec![("2024-01-15", 6), ("2024-01-16", 8)]);
+    }
+
+    #[test]
+    fn test_fill_series_gaps_quarter_hours() {
+        let data: HashMap<String, (u64, u64)> = HashMap::from([("2024-01-15T10:15:00Z".to_string(), (3, 4))]);
+        let series = fill_series_gaps(
+            "2024-01-15T10:00:00Z",
+            "2024-01-15T10:50:00Z",
+            QUARTER_HOUR_BUCKET,
+            Tz::UTC,
+            &data,
+        );
+        let points: Vec<_> = series.iter().map(|p| (p.timestamp.as_str(), p.visits)).collect();
+        assert_eq!(
+            points,
+            vec![
+                ("2024-01-15T10:00:00+00:00", 0),
+                ("2024-01-15T10:15:00+00:00", 3),
+                ("2024-01-15T10:30:00+00:00", 0),
+                ("2024-01-15T10:45:00+00:00", 0),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_fill_series_gaps_weekly_buckets_start_on_monday() {
+        let data: HashMap<String, (u64, u64)> = HashMap::from([
+            ("2024-01-15T10:00:00Z".to_string(), (1, 1)),
+            ("2024-01-21".to_string(), (2, 2)),
+            ("2024-01-29".to_string(), (4, 4)),
+        ]);
+        let series = fill_series_gaps(
+            "2024-01-15T00:00:00Z",
+            "2024-02-01T10:00:00Z",
+            WEEK_BUCKET,
+            Tz::UTC,
+            &data,
+        );
+        let weeks: Vec<_> = series.iter().map(|p| (p.timestamp.as_str(), p.visits)).collect();
+        assert_eq!(weeks, vec![("2024-01-15", 3), ("2024-01-22", 0), ("2024-01-29", 4)]);
+        // Ends on a Thursday
+        assert_eq!(series.iter().map(|p| p.days).collect::<Vec<_>>(), vec![None, None, Some(4)]);
+    }
+
+    #[test]
+    fn test_fill_series_gaps_weekly_partial_edges() {
+        // Wednesday 2024-01-17 to Tuesday 2024-01-30; the 16th is before the range
+        let data: HashMap<String, (u64, u64)> = HashMap::from([
+            ("2024-01-16".to_string(), (8, 8)),
+            ("2024-01-17".to_string(), (1, 2)),
+            ("2024-01-28".to_string(), (3, 3)),
+            ("2024-01-30".to_string(), (5, 6)),
+        ]);
+        let series = fill_series_gaps("2024-01-17T00:00:00Z", "2024-01-30T12:00:00Z", WEEK_BUCKET, Tz::UTC, &data);
+        let weeks: Vec<_> = series.iter().map(|p| (p.timestamp.as_str(), p.visits, p.page_views, p.days)).collect();
+        assert_eq!(
+            weeks,
+            vec![("2024-01-15", 1, 2, Some(5)), ("2024-01-22", 3, 3, None), ("2024-01-29", 5, 6, Some(2))]
+        );
+    }
+
+    #[test]
+    fn test_bucket_for_days() {
+        assert_eq!(bucket_for_days(30), DAY_BUCKET);
+        assert_eq!(bucket_for_days(WEEKLY_AFTER_DAYS), DAY_BUCKET);
+        assert_eq!(bucket_for_days(90), WEEK_BUCKET);
+        assert_eq!(bucket_for_days(365), WEEK_BUCKET);
+    }
+
+    #[test]
+    fn test_fill_series_gaps_hourly_labels_carry_offset() {
+        let data: HashMap<String, (u64, u64)> = HashMap::from([("2024-01-15T10:00:00Z".to_string(), (5, 6))]);
+        let series = fill_series_gaps(
+            "2024-01-15T10:00:00Z",
+            "2024-01-15T11:15:00Z",
+            "datetimeHour",
+            chrono_tz::Asia::Kolkata,
+            &data,
+        );
+        assert_eq!(series[0].timestamp, "2024-01-15T15:30:00+05:30");
+        assert_eq!(series[0].visits, 5);
+        assert_eq!(series[1].timestamp, "2024-01-15T16:30:00+05:30");
+    }
+
+    #[test]
+    fn test_get_time_range_days_start_at_local_midnight() {
+        let (start, _, _) = get_time_range("7d", chrono_tz::Asia::Tokyo);
+        // Midnight in Tokyo (UTC+9) is 15:00Z the day before
+        assert!(start.ends_with("T15:00:00Z"), "{}", start);
+    }
+
+    #[test]
+    fn test_local_midnight_skipped_by_dst() {
+        // Santiago moved clocks from 00:00 to 01:00 on 2023-09-03
+        let day = NaiveDate::from_ymd_opt(2023, 9, 3).unwrap();
+        let start = local_midnight(day, chrono_tz::America::Santiago);
+        assert_eq!(start.format("%Y-%m-%dT%H:%M:%SZ").to_string(), "2023-09-03T04:00:00Z");
+    }
+
+    // --- all sites tests ---
+
+    fn site_with_series(name: &str, prev_visits: u64, points: &[(&str, u64)]) -> SiteData {
+        SiteData {
+            name: name.to_string(),
+            visits: points.iter().map(|p| p.1).sum(),
+            page_views: points.iter().map(|p| p.1 * 2).sum(),
+            prev_visits,
+            series: points
+                .iter()
+                .map(|&(ts, visits)| SeriesPoint {
+                    timestamp: ts.to_string(),
+                    visits,
+                    page_views: visits * 2,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_aggregate_sites_sums_totals_and_series() {
+        let sites = vec![
+            site_with_series("a.com", 10, &[("2024-01-15", 5), ("2024-01-16", 10)]),
+            site_with_series("b.com", 10, &[("2024-01-15", 1), ("2024-01-16", 4)]),
+            // A site fetched without a series (low-data mode) still counts toward totals
+            SiteData { name: "c.com".to_string(), visits: 5, ..Default::default() },
+        ];
+        let all = aggregate_sites(&sites);
+        assert_eq!(all.name, ALL_SITES);
+        assert!(all.aggregate);
+        assert_eq!(all.visits, 25);
+        assert_eq!(all.page_views, 40);
+        assert_eq!(all.prev_visits, 20);
+        assert_eq!(all.visits_change, Some(25.0));
+        let series: Vec<_> = all.series.iter().map(|p| (p.timestamp.as_str(), p.visits, p.page_views)).collect();
+        assert_eq!(series, vec![("2024-01-15", 6, 12), ("2024-01-16", 14, 28)]);
+    }
+
+    #[test]
+    fn test_arrange_sites_hides_and_pins() {
+        let settings = Settings {
+            hidden_sites: vec!["b.com".to_string()],
+            pinned_sites: vec!["d.com".to_string(), "c.com".to_string()],
+            ..Default::default()
+        };
+        let site = |name: &str, visits| SiteData { name: name.to_string(), visits, ..Default::default() };
+        let sites = vec![site("a.com", 50), site("b.com", 40), site("c.com", 5), site("d.com", 10)];
+        let arranged = arrange_sites(&settings, sites);
+        let names: Vec<_> = arranged.iter().map(|s| (s.name.as_str(), s.pinned)).collect();
+        assert_eq!(names, vec![("d.com", true), ("c.com", true), ("a.com", false)]);
+    }
+
+    #[test]
+    fn test_arrange_sites_sort_orders() {
+        let site = |name: &str, visits, page_views, change| SiteData {
+            name: name.to_string(),
+            visits,
+            page_views,
+            visits_change: change,
+            ..Default::default()
+        };
+        let sites = || vec![
+            site("b.com", 10, 90, Some(-50.0)),
+            site("A.com", 30, 10, None),
+            site("c.com", 20, 50, Some(5.0)),
+            site("d.com", 10, 20, Some(20.0)),
+        ];
+        let order = |sort_by: &str, direction: &str| {
+            let settings = Settings {
+                sort_by: sort_by.to_string(),
+                sort_direction: direction.to_string(),
+                ..Default::default()
+            };
+            arrange_sites(&settings, sites()).into_iter().map(|s| s.name).collect::<Vec<_>>()
+        };
+        // Ties keep their original order in both directions
+        assert_eq!(order("visits", "desc"), vec!["A.com", "c.com", "b.com", "d.com"]);
+        assert_eq!(order("visits", "asc"), vec!["b.com", "d.com", "c.com", "A.com"]);
+        assert_eq!(order("page_views", "desc"), vec!["b.com", "c.com", "d.com", "A.com"]);
+        assert_eq!(order("name", "asc"), vec!["A.com", "b.com", "c.com", "d.com"]);
+        assert_eq!(order("change", "desc"), vec!["b.com", "d.com", "c.com", "A.com"]);
+        assert_eq!(order("change", "asc"), vec!["c.com", "d.com", "b.com", "A.com"]);
+    }
+
+    #[test]
+    fn test_validate_sort_rejects_unknown_values() {
+        let mut settings = Settings::default();
+        assert!(validate_sort(&settings).is_ok());
+        settings.sort_by = "bounce_rate".to_string();
+        assert!(validate_sort(&settings).unwrap_err().contains("sort order"));
+        settings.sort_by = "name".to_string();
+        settings.sort_direction = "up".to_string();
+        assert!(validate_sort(&settings).unwrap_err().contains("sort direction"));
+    }
+
+    #[test]
+    fn test_with_all_sites_prepends_entry() {
+        let settings = Settings::default();
+        let sites = vec![
+            site_with_series("a.com", 0, &[("2024-01-15", 1)]),
+            site_with_series("b.com", 0, &[("2024-01-15", 2)]),
+        ];
+        let result = with_all_sites(&settings, sites);
+        let names: Vec<_> = result.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, vec![ALL_SITES, "a.com", "b.com"]);
+        assert_eq!(result[0].visits, 3);
+    }
+
+    #[test]
+    fn test_with_all_sites_skipped_when_disabled_or_single_site() {
+        let one = vec![site_with_series("a.com", 0, &[])];
+        assert_eq!(with_all_sites(&Settings::default(), one).len(), 1);
+
+        let settings = Settings { show_all_sites: false, ..Default::default() };
+        let two = vec![site_with_series("a.com", 0, &[]), site_with_series("b.com", 0, &[])];
+        assert_eq!(with_all_sites(&settings, two).len(), 2);
+    }
+
+    // --- previous period tests ---
+
+    #[test]
+    fn test_previous_range_same_length_before_start() {
+        let (start, end) = previous_range("2024-01-15T00:00:00Z", "2024-01-21T12:00:00Z");
+        assert_eq!(start, "2024-01-08T12:00:00Z");
+        assert_eq!(end, "2024-01-15T00:00:00Z");
+    }
+
+    #[test]
+    fn test_previous_range_hourly() {
+        let (start, end) = previous_range("2024-01-15T10:00:00Z", "2024-01-16T09:30:00Z");
+        assert_eq!(start, "2024-01-14T10:30:00Z");
+        assert_eq!(end, "2024-01-15T10:00:00Z");
+    }
+
+    #[test]
+    fn test_percent_change() {
+        assert_eq!(percent_change(112, 100), Some(12.0));
+        assert_eq!(percent_change(50, 100), Some(-50.0));
+        assert_eq!(percent_change(10, 0), None);
+        assert_eq!(percent_change(0, 0), None);
+    }
+
+    // --- parse_workers_response tests ---
+
+    #[test]
+    fn test_parse_workers_response_groups_series_by_script() {
+        let data = serde_json::json!({
+            "data": { "viewer": { "accounts": [{
+                "totals": [
+                    {
+                        "sum": { "requests": 30, "errors": 1 },
+                        "quantiles": { "cpuTimeP50": 1200.0, "cpuTimeP99": 5400.0 },
+                        "dimensions": { "scriptName": "api" }
+                    },
+                    {
+                        "sum": { "requests": 50, "errors": 0 },
+                        "quantiles": { "cpuTimeP50": 800.0, "cpuTimeP99": 900.0 },
+                        "dimensions": { "scriptName": "router" }
+                    }
+                ],
+                "series": [
+                    { "sum": { "requests": 10, "errors": 1 }, "dimensions": { "scriptName": "api", "ts": "2024-01-15T03:00:00Z" } },
+                    { "sum": { "requests": 20, "errors": 0 }, "dimensions": { "scriptName": "api", "ts": "2024-01-17T08:00:00Z" } },
+                    { "sum": { "requests": 50, "errors": 0 }, "dimensions": { "scriptName": "router", "ts": "2024-01-16T12:00:00Z" } }
+                ]
+            }]}}
+        });
+        let workers = parse_workers_response(
+            &data,
+            "2024-01-15T00:00:00Z",
+            "2024-01-17T12:00:00Z",
+            "date",
+            Tz::UTC,
+        );
+        assert_eq!(workers.len(), 2);
+        // sorted by requests descending
+        assert_eq!(workers[0].script_name, "router");
+        assert_eq!(workers[1].script_name, "api");
+        assert_eq!(workers[1].errors, 1);
+        assert_eq!(workers[1].cpu_time_p99, 5400.0);
+        let api_series: Vec<u64> = workers[1].series.iter().map(|p| p.requests).collect();
+        assert_eq!(api_series, vec![10, 0, 20]);
+        assert_eq!(workers[1].series[0].errors, 1);
+    }
+
+    #[test]
+    fn test_parse_workers_response_sums_hours_into_local_days() {
+        // New York is UTC-5: the range starts at its midnight, 05:00Z, and 03:00Z on the
+        // 16th is still the 15th there
+        let data = serde_json::json!({
+            "data": { "viewer": { "accounts": [{
+                "totals": [{ "sum": { "requests": 7, "errors": 0 }, "quantiles": {}, "dimensions": { "scriptName": "api" } }],
+                "series": [
+                    { "sum": { "requests": 3, "errors": 0 }, "dimensions": { "scriptName": "api", "ts": "2024-01-15T05:00:00Z" } },
+                    { "sum": { "requests": 4, "errors": 0 }, "dimensions": { "scriptName": "api", "ts": "2024-01-16T03:00:00Z" } }
+                ]
+            }]}}
+        });
+        let workers = parse_workers_response(
+            &data,
+            "2024-01-15T05:00:00Z",
+            "2024-01-16T12:00:00Z",
+            DAY_BUCKET,
+            chrono_tz::America::New_York,
+        );
+        let days: Vec<_> = workers[0].series.iter().map(|p| (p.timestamp.as_str(), p.requests)).collect();
+        assert_eq!(days, vec![("2024-01-15", 7), ("2024-01-16", 0)]);
+    }
+
+    #[test]
+    fn test_parse_workers_response_empty() {
+        let data = serde_json::json!({ "data": { "viewer": { "accounts": [{ "totals": [], "series": [] }] } } });
+        let workers = parse_workers_response(
+            &data,
+            "2024-01-15T00:00:00Z",
+            "2024-01-15T02:00:00Z",
+            "datetimeHour",
+            Tz::UTC,
+        );
+        assert!(workers.is_empty());
+    }
+
+    // --- retry tests ---
+
+    #[test]
+    fn test_parse_retry_after_seconds() {
+        assert_eq!(parse_retry_after("30"), Some(std::time::Duration::from_secs(30)));
+        assert_eq!(parse_retry_after(" 5 "), Some(std::time::Duration::from_secs(5)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+
+    #[test]
+    fn test_backoff_delay_grows_exponentially() {
+        assert_eq!(backoff_delay(1, None, 0.0).as_millis(), 500);
+        assert_eq!(backoff_delay(2, None, 0.0).as_millis(), 1000);
+        assert_eq!(backoff_delay(3, None, 0.0).as_millis(), 2000);
+    }
+
+    #[test]
+    fn test_backoff_delay_adds_bounded_jitter() {
+        assert_eq!(backoff_delay(1, None, 1.0).as_millis(), 750);
+        assert_eq!(backoff_delay(1, None, 5.0).as_millis(), 750);
+    }
+
+    #[test]
+    fn test_backoff_delay_honors_retry_after_and_caps() {
+        let retry_after = Some(std::time::Duration::from_secs(7));
+        assert_eq!(backoff_delay(1, retry_after, 0.9).as_secs(), 7);
+        let huge = Some(std::time::Duration::from_secs(3600));
+        assert_eq!(backoff_delay(1, huge, 0.0).as_millis(), MAX_RETRY_DELAY_MS as u128);
+        assert_eq!(backoff_delay(30, None, 0.0).as_millis(), MAX_RETRY_DELAY_MS as u128);
+    }
+
+    #[test]
+    fn test_correlation_id_is_short_hex() {
+        let id = new_correlation_id();
+        assert_eq!(id.len(), 12);
+        assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+    }
+
+    #[test]
+    fn test_tag_error_appends_correlation_id() {
+        assert_eq!(
+            tag_error("API error 403 Forbidden: {}", "0123456789ab"),
+            "API error 403 Forbidden: {} [request 0123456789ab]"
+        );
+    }
+
+    // --- api_client tests ---
+
+    #[test]
+    fn test_api_client_with_proxy() {
+        let settings = Settings {
+            proxy_url: "http://proxy.internal:3128".to_string(),
+            ..Default::default()
+        };
+        assert!(api_client(&settings).is_ok());
+
+        let settings = Settings {
+            proxy_url: "not a url".to_string(),
+            ..Default::default()
+        };
+        assert!(api_client(&settings).unwrap_err().starts_with("Invalid proxy URL"));
+    }
+
+    #[test]
+    fn test_api_client_rejects_bad_ca_file() {
+        let settings = Settings {
+            ca_cert_path: "/nonexistent/ca.pem".to_string(),
+            ..Default::default()
+        };
+        assert!(api_client(&settings).unwrap_err().starts_with("Can't read CA certificate"));
+
+        let path = crate::test_support::temp_dir("commands", "bad-ca").join("ca.pem");
+        fs::write(&path, "not a certificate").unwrap();
+        let settings = Settings {
+            ca_cert_path: path.to_string_lossy().to_string(),
+            ..Default::default()
+        };
+        assert!(api_client(&settings).unwrap_err().starts_with("No PEM certificates"));
+        fs::remove_file(&path).ok();
+    }
+
     // --- Settings defaults tests ---
 
     #[test]
@@ -553,19 +2616,201 @@ mod tests {
         assert!(!settings.exclude_bots);
     }
 
-    // --- parse_interval_ms tests ---
+    #[test]
+    fn test_settings_deserialize_launch_at_login_defaults_off() {
+        let json = r#"{"token":"t","account_id":"a","period":"24h"}"#;
+        let settings: Settings = serde_json::from_str(json).unwrap();
+        assert!(!settings.launch_at_login);
+    }
+
+    // --- analytics cache tests ---
+
+    fn snapshot(key: &str, fetched_at: &str) -> Snapshot {
+        Snapshot {
+            sites: Vec::new(),
+            fetched_at: DateTime::parse_from_rfc3339(fetched_at).unwrap().to_utc(),
+            site_fetched_at: HashMap::new(),
+            key: key.to_string(),
+            period: "24h".to_string(),
+            partial: false,
+        }
+    }
 
     #[test]
-    fn test_parse_interval_ms_known_values() {
-        assert_eq!(parse_interval_ms("5m"), 300_000);
-        assert_eq!(parse_interval_ms("15m"), 900_000);
-        assert_eq!(parse_interval_ms("60m"), 3_600_000);
+    fn test_snapshot_fresh_within_ttl() {
+        let s = snapshot("a|24h|true", "2024-01-15T10:00:00Z");
+        let now = DateTime::parse_from_rfc3339("2024-01-15T10:00:59Z").unwrap().to_utc();
+        assert!(s.is_fresh("a|24h|true", Duration::seconds(60), now));
+        assert!(!s.is_fresh("a|24h|true", Duration::seconds(30), now));
+        assert!(!s.is_fresh("a|24h|true", Duration::zero(), now));
     }
 
     #[test]
-    fn test_parse_interval_ms_unknown_defaults_to_15m() {
-        assert_eq!(parse_interval_ms("unknown"), 900_000);
-        assert_eq!(parse_interval_ms(""), 900_000);
+    fn test_snapshot_stale_when_settings_change() {
+        let s = snapshot("a|24h|true", "2024-01-15T10:00:00Z");
+        let now = s.fetched_at;
+        assert!(!s.is_fresh("a|7d|true", Duration::seconds(60), now));
+    }
+
+    #[test]
+    fn test_push_snapshot_keeps_newest_first() {
+        let mut history = VecDeque::new();
+        for (i, at) in ["2024-01-15T10:00:00Z", "2024-01-15T10:15:00Z", "2024-01-15T10:30:00Z"]
+            .into_iter()
+            .enumerate()
+        {
+            push_snapshot(&mut history, snapshot(&i.to_string(), at), 2);
+        }
+        let keys: Vec<_> = history.iter().map(|s| s.key.as_str()).collect();
+        assert_eq!(keys, vec!["2", "1"]);
+
+        push_snapshot(&mut history, snapshot("3", "2024-01-15T10:45:00Z"), 0);
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].key, "3");
+    }
+
+    #[test]
+    fn test_cache_key_ignores_display_settings() {
+        let mut settings = Settings {
+            account_id: "acc".to_string(),
+            period: "7d".to_string(),
+            ..Default::default()
+        };
+        let key = cache_key(&settings);
+        settings.theme = "dark".to_string();
+        settings.tray_title = "total".to_string();
+        assert_eq!(cache_key(&settings), key);
+        settings.exclude_bots = false;
+        assert_ne!(cache_key(&settings), key);
+        let key = cache_key(&settings);
+        settings.quarter_hour_24h = true;
+        assert_ne!(cache_key(&settings), key);
+        let key = cache_key(&settings);
+        settings.filters.country = "DE".to_string();
+        assert_ne!(cache_key(&settings), key);
+        let key = cache_key(&settings);
+        settings.timezone = "Asia/Tokyo".to_string();
+        assert_ne!(cache_key(&settings), key);
+        let key = cache_key(&settings);
+        settings.low_data_mode = "on".to_string();
+        assert_ne!(cache_key(&settings), key);
+    }
+
+    #[test]
+    fn test_dimension_filters_clauses_skip_empty_fields() {
+        assert!(DimensionFilters::default().clauses().is_empty());
+        let filters = DimensionFilters {
+            country: "DE".to_string(),
+            host: "blog.example.com".to_string(),
+            ..Default::default()
+        };
+        assert_eq!(
+            filters.clauses(),
+            vec![
+                serde_json::json!({"countryName": "DE"}),
+                serde_json::json!({"requestHost": "blog.example.com"})
+            ]
+        );
+    }
+
+    #[test]
+    fn test_dimension_filters_validate() {
+        assert!(DimensionFilters::default().validate().is_ok());
+        let filters = |country: &str, device_type: &str| DimensionFilters {
+            country: country.to_string(),
+            device_type: device_type.to_string(),
+            ..Default::default()
+        };
+        assert!(filters("US", "mobile").validate().is_ok());
+        assert!(filters("us", "").validate().is_err());
+        assert!(filters("USA", "").validate().is_err());
+        assert!(filters("", "phone").validate().is_err());
+    }
+
+    // --- account style tests ---
+
+    #[test]
+    fn test_account_style_for_configured_account() {
+        let style = AccountStyle {
+            label: "Work".to_string(),
+            color: "#f38020".to_string(),
+        };
+        let mut settings = Settings {
+            account_id: "acc".to_string(),
+            account_styles: HashMap::from([("acc".to_string(), style.clone())]),
+            ..Default::default()
+        };
+        assert_eq!(account_style(&settings), Some(style));
+
+        settings.account_id = "other".to_string();
+        assert_eq!(account_style(&settings), None);
+    }
+
+    #[test]
+    fn test_account_style_ignores_empty_style() {
+        let settings = Settings {
+            account_id: "acc".to_string(),
+            account_styles: HashMap::from([("acc".to_string(), AccountStyle::default())]),
+            ..Default::default()
+        };
+        assert_eq!(account_style(&settings), None);
+    }
+
+    #[test]
+    fn test_account_style_validate_color() {
+        let style = |color: &str| AccountStyle {
+            label: String::new(),
+            color: color.to_string(),
+        };
+        assert!(style("").validate().is_ok());
+        assert!(style("#F38020").validate().is_ok());
+        assert!(style("f38020").validate().is_err());
+        assert!(style("#f380").validate().is_err());
+        assert!(style("orange").validate().is_err());
+    }
+
+    // --- parse_interval tests ---
+
+    #[test]
+    fn test_parse_interval_units() {
+        assert_eq!(parse_interval("30s").unwrap().as_secs(), 30);
+        assert_eq!(parse_interval("2m").unwrap().as_secs(), 120);
+        assert_eq!(parse_interval("90m").unwrap().as_secs(), 5400);
+        assert_eq!(parse_interval("1h").unwrap().as_secs(), 3600);
+        assert_eq!(parse_interval(" 15m ").unwrap().as_secs(), 900);
+    }
+
+    #[test]
+    fn test_parse_interval_compound() {
+        assert_eq!(parse_interval("1h30m").unwrap().as_secs(), 5400);
+        assert_eq!(parse_interval("1m30s").unwrap().as_secs(), 90);
+    }
+
+    #[test]
+    fn test_parse_interval_rejects_malformed() {
+        for input in ["", "15", "m", "5x", "1.5h", "-5m", "5 m", "99999999999999999999h"] {
+            assert!(parse_interval(input).is_err(), "{:?} should be rejected", input);
+        }
+    }
+
+    #[test]
+    fn test_parse_interval_enforces_minimum() {
+        let err = parse_interval("10s").unwrap_err();
+        assert_eq!(err, "Refresh interval must be at least 30s");
+        assert!(parse_interval("0m").is_err());
+    }
+
+    #[test]
+    fn test_default_refresh_interval_is_valid() {
+        assert!(parse_interval(&Settings::default().refresh_interval).is_ok());
+    }
+
+    #[test]
+    fn test_settings_deserialize_missing_tray_title_defaults_off() {
+        let json = r#"{"token":"t","account_id":"a","period":"24h"}"#;
+        let settings: Settings = serde_json::from_str(json).unwrap();
+        assert_eq!(settings.tray_title, "off");
+        assert_eq!(settings.tray_site, "");
     }
 
     #[test]
@@ -574,4 +2819,398 @@ mod tests {
         let settings: Settings = serde_json::from_str(json).unwrap();
         assert_eq!(settings.refresh_interval, "15m");
     }
+
+    // --- site pagination tests ---
+
+    fn sites_page(names: &[&str], page: u64, per_page: u64, total_count: u64) -> serde_json::Value {
+        serde_json::json!({
+            "success": true,
+            "result": names
+                .iter()
+                .map(|n| serde_json::json!({"site_tag": format!("tag-{}", n), "ruleset": {"zone_name": n}}))
+                .collect::<Vec<_>>(),
+            "result_info": {"page": page, "per_page": per_page, "count": names.len(), "total_count": total_count}
+        })
+    }
+
+    #[test]
+    fn test_parse_sites_page_has_more_from_total_count() {
+        let (sites, has_more) = parse_sites_page(&sites_page(&["a.com", "b.com"], 1, 2, 3), 1).unwrap();
+        assert_eq!(sites, vec![
+            ("a.com".to_string(), "tag-a.com".to_string()),
+            ("b.com".to_string(), "tag-b.com".to_string()),
+        ]);
+        assert!(has_more);
+
+        let (_, has_more) = parse_sites_page(&sites_page(&["c.com"], 2, 2, 3), 2).unwrap();
+        assert!(!has_more);
+    }
+
+    #[test]
+    fn test_parse_sites_page_without_result_info_uses_page_size() {
+        let body = serde_json::json!({"result": [{"site_tag": "t", "ruleset": {"zone_name": "a.com"}}]});
+        let (sites, has_more) = parse_sites_page(&body, 1).unwrap();
+        assert_eq!(sites.len(), 1);
+        assert!(!has_more);
+    }
+
+    #[tokio::test]
+    async fn test_collect_site_pages_follows_all_pages() {
+        let pages = [
+            sites_page(&["a.com", "b.com"], 1, 2, 5),
+            sites_page(&["c.com", "d.com"], 2, 2, 5),
+            sites_page(&["e.com"], 3, 2, 5),
+        ];
+        let mut requested = Vec::new();
+        let mut page_sizes = Vec::new();
+        let sites = collect_site_pages(
+            |page| {
+                requested.push(page);
+                let body = pages[page as usize - 1].clone();
+                async move { Ok(body) }
+            },
+            |batch| page_sizes.push(batch.len()),
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(requested, vec![1, 2, 3]);
+        assert_eq!(page_sizes, vec![2, 2, 1]);
+        let names: Vec<_> = sites.into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["a.com", "b.com", "c.com", "d.com", "e.com"]);
+    }
+
+    #[tokio::test]
+    async fn test_collect_site_pages_stops_on_empty_page() {
+        let sites = collect_site_pages(|page| async move { Ok(sites_page(&[], page, 2, 10)) }, |_| {})
+            .await
+            .unwrap();
+        assert!(sites.is_empty());
+    }
+
+    // --- fetches against a mock client ---
+
+    use crate::client::mock::{json, MockClient};
+
+    fn mock_settings() -> Settings {
+        Settings { token: "token".to_string(), account_id: "acc".to_string(), ..Default::default() }
+    }
+
+    fn analytics_body(visits: u64, page_views: u64, prev_visits: u64) -> serde_json::Value {
+        serde_json::json!({"data": {"viewer": {"accounts": [{
+            "totals": [{"count": page_views, "sum": {"visits": visits}}],
+            "previous": [{"count": 0, "sum": {"visits": prev_visits}}],
+            "series": []
+        }]}}})
+    }
+
+    #[tokio::test]
+    async fn test_fetch_sites_requests_every_page() {
+        let client = MockClient::new(|req| {
+            let page: u64 = req.param("page").unwrap().parse().unwrap();
+            let names: &[&str] = if page == 1 { &["a.com", "b.com"] } else { &["c.com"] };
+            json(200, sites_page(names, page, 2, 3))
+        });
+
+        let sites = fetch_sites(&client, &mock_settings().credentials(), "acc").await.unwrap();
+
+        let names: Vec<_> = sites.into_iter().map(|(name, _)| name).collect();
+        assert_eq!(names, vec!["a.com", "b.com", "c.com"]);
+        let requests = client.requests();
+        assert_eq!(requests.len(), 2);
+        assert_eq!(requests[0].url, format!("{}/accounts/acc/rum/site_info/list", API_BASE));
+        assert_eq!(requests[1].param("page"), Some("2"));
+        assert_eq!(requests[1].param("per_page"), Some("100"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_sites_reports_error_body() {
+        let client = MockClient::new(|_| json(403, serde_json::json!({"errors": [{"code": 10000, "message": "Authentication error"}]})));
+        let err = fetch_sites(&client, &mock_settings().credentials(), "acc").await.unwrap_err();
+        assert!(matches!(err, FetchError::Auth { .. }), "{:?}", err);
+        assert!(err.message().starts_with("API error 403 Forbidden"), "{}", err);
+        assert!(err.message().contains("Authentication error"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_graphql_request_surfaces_errors() {
+        let client = MockClient::new(|_| json(200, serde_json::json!({"data": null, "errors": [{"message": "quota exceeded"}]})));
+        let err = graphql_request(&client, &mock_settings().credentials(), "{}", serde_json::json!({})).await.unwrap_err();
+        let FetchError::Graphql { message, errors } = err else { panic!("{:?}", err) };
+        assert!(message.starts_with("GraphQL errors"), "{}", message);
+        assert_eq!(errors, vec![serde_json::json!({"message": "quota exceeded"})]);
+
+        let client = MockClient::new(|_| (400, "bad request".to_string()));
+        let err = graphql_request(&client, &mock_settings().credentials(), "{}", serde_json::json!({})).await.unwrap_err();
+        assert!(matches!(err, FetchError::Api { status: 400, .. }), "{:?}", err);
+        assert!(err.message().starts_with("GraphQL error: 400"), "{}", err);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_site_analytics_parses_totals() {
+        let client = MockClient::new(|_| json(200, analytics_body(150, 400, 100)));
+        let filters = DimensionFilters { country: "DE".to_string(), ..Default::default() };
+        let settings = Settings { exclude_bots: true, filters, ..mock_settings() };
+
+        let site = fetch_site_analytics(&client, &settings, "a.com", "tag-a", QueryPlan::Full).await.unwrap();
+
+        assert_eq!(site.name, "a.com");
+        assert_eq!((site.visits, site.page_views, site.prev_visits), (150, 400, 100));
+        assert_eq!(site.visits_change, Some(50.0));
+        let requests = client.requests();
+        assert_eq!(requests[0].url, format!("{}/graphql", API_BASE));
+        assert_eq!(requests[0].body.as_ref().unwrap()["variables"]["accountTag"], "acc");
+        let variables = requests[0].variables();
+        assert!(variables.contains(r#"{"siteTag":"tag-a"}"#));
+        assert!(variables.contains(r#"{"bot":0}"#));
+        assert_eq!(variables.matches(r#"{"countryName":"DE"}"#).count(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_site_analytics_splits_bot_traffic() {
+        let client = MockClient::new(|_| {
+            json(200, serde_json::json!({"data": {"viewer": {"accounts": [{
+                "totals": [
+                    {"count": 400, "sum": {"visits": 150}, "dimensions": {"bot": 0}},
+                    {"count": 90, "sum": {"visits": 60}, "dimensions": {"bot": 1}}
+                ],
+                "previous": [
+                    {"count": 10, "sum": {"visits": 100}, "dimensions": {"bot": 0}},
+                    {"count": 10, "sum": {"visits": 900}, "dimensions": {"bot": 1}}
+                ],
+                "series": []
+            }]}}}))
+        });
+        let settings = Settings { exclude_bots: true, split_bots: true, ..mock_settings() };
+
+        let site = fetch_site_analytics(&client, &settings, "a.com", "tag-a", QueryPlan::Full).await.unwrap();
+
+        assert_eq!((site.visits, site.page_views, site.prev_visits), (150, 400, 100));
+        assert_eq!((site.bot_visits, site.bot_page_views), (Some(60), Some(90)));
+        assert!(site.series.iter().all(|p| p.bot_visits == Some(0)));
+        let request = &client.requests()[0];
+        assert!(!request.variables().contains(r#"{"bot":0}"#));
+        assert!(request.body.as_ref().unwrap()["query"].as_str().unwrap().contains("dimensions { bot }"));
+    }
+
+    #[test]
+    fn test_aggregate_sites_sums_bots_only_when_counted() {
+        let site = |bots| SiteData { visits: 10, bot_visits: bots, ..Default::default() };
+        assert_eq!(aggregate_sites(&[site(Some(3)), site(None), site(Some(4))]).bot_visits, Some(7));
+        assert_eq!(aggregate_sites(&[site(None), site(None)]).bot_visits, None);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_all_sites_reports_failures_and_skips_hidden() {
+        let client = MockClient::new(|req| {
+            if req.method == "GET" {
+                return json(200, sites_page(&["a.com", "b.com", "c.com"], 1, 100, 3));
+            }
+            if req.variables().contains("tag-b.com") {
+                return (400, "bad request".to_string());
+            }
+            json(200, analytics_body(10, 20, 0))
+        });
+        let settings = Settings {
+            hidden_sites: vec!["c.com".to_string()],
+            pinned_sites: vec!["a.com".to_string()],
+            ..mock_settings()
+        };
+
+        let mut streamed = Vec::new();
+        let (sites, failures) = fetch_all_sites_reporting(&client, &settings, |site| {
+            streamed.push((site.name.clone(), site.pinned));
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(sites.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["a.com"]);
+        assert_eq!(streamed, vec![("a.com".to_string(), true)]);
+        assert_eq!(failures.len(), 1);
+        assert_eq!(failures[0].0, "b.com");
+        assert!(!client.requests().iter().any(|r| r.variables().contains("tag-c.com")));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_all_sites_with_zero_concurrency_still_fetches() {
+        let client = MockClient::new(|req| {
+            if req.method == "GET" {
+                return json(200, sites_page(&["a.com", "b.com"], 1, 100, 2));
+            }
+            json(200, analytics_body(10, 20, 0))
+        });
+        let settings = Settings { max_concurrent_requests: 0, ..mock_settings() };
+
+        let (sites, failures) = fetch_all_sites_reporting(&client, &settings, |_| {}).await.unwrap();
+
+        assert_eq!(sites.len(), 2);
+        assert!(failures.is_empty());
+    }
+
+    /// `count` series rows, one per hour from `2024-01-15T00:00:00Z`.
+    fn series_rows(count: usize) -> serde_json::Value {
+        let rows: Vec<_> = (0..count)
+            .map(|i| serde_json::json!({"count": 2, "sum": {"visits": 1}, "dimensions": {"ts": format!("2024-01-15T{:02}:00:00Z", i % 24)}}))
+            .collect();
+        serde_json::json!({"data": {"viewer": {"accounts": [{"series": rows}]}}})
+    }
+
+    /// Hours covered by the time range filter of a series request.
+    fn requested_hours(req: &crate::client::mock::Request) -> i64 {
+        let range = &req.body.as_ref().unwrap()["variables"]["filter"]["AND"][0];
+        let parse = |ts: &serde_json::Value| DateTime::parse_from_rfc3339(ts.as_str().unwrap()).unwrap();
+        let end = range.get("datetime_lt").or(range.get("datetime_leq")).unwrap();
+        (parse(end) - parse(&range["datetime_geq"])).num_hours()
+    }
+
+    #[tokio::test]
+    async fn test_fetch_series_chunked_splits_until_under_limit() {
+        // Anything longer than 6 hours overflows
+        let client = MockClient::new(|req| {
+            json(200, series_rows(if requested_hours(req) > 6 { SERIES_LIMIT } else { 3 }))
+        });
+        let scope = vec![serde_json::json!({ "siteTag": "tag-a.com" })];
+
+        let (rows, truncated) = fetch_series_chunked(
+            &client,
+            &mock_settings(),
+            &series_selection(HOUR_BUCKET, ""),
+            &scope,
+            "2024-01-15T00:00:00Z",
+            "2024-01-16T00:00:00Z",
+        )
+        .await
+        .unwrap();
+
+        assert!(!truncated);
+        // Two 12-hour halves overflow, their four 6-hour quarters don't
+        assert_eq!(rows.len(), 4 * 3);
+        let requests = client.requests();
+        assert_eq!(requests.len(), 6);
+        let ranges: Vec<_> = requests.iter().map(|r| r.body.as_ref().unwrap()["variables"]["filter"]["AND"][0].clone()).collect();
+        assert_eq!(ranges[0], serde_json::json!({"datetime_geq": "2024-01-15T00:00:00Z", "datetime_lt": "2024-01-15T12:00:00Z"}));
+        assert_eq!(ranges[5], serde_json::json!({"datetime_geq": "2024-01-15T18:00:00Z", "datetime_leq": "2024-01-16T00:00:00Z"}));
+        assert!(requests.iter().all(|r| r.variables().contains("tag-a.com")));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_series_chunked_flags_truncation_after_last_split() {
+        let client = MockClient::new(|_| json(200, series_rows(SERIES_LIMIT)));
+
+        let (rows, truncated) = fetch_series_chunked(
+            &client,
+            &mock_settings(),
+            &series_selection(QUARTER_HOUR_BUCKET, ""),
+            &[],
+            "2024-01-15T00:00:00Z",
+            "2024-01-16T00:00:00Z",
+        )
+        .await
+        .unwrap();
+
+        assert!(truncated);
+        assert_eq!(client.requests().len(), 2 + 4 + 8 + 16);
+        assert_eq!(rows.len(), 16 * SERIES_LIMIT);
+    }
+
+    #[tokio::test]
+    async fn test_fetch_hourly_visits_fills_every_hour() {
+        // One row in the first hour of the range, the rest of the day without traffic
+        let client = MockClient::new(|req| {
+            let start = req.body.as_ref().unwrap()["variables"]["filter"]["AND"][0]["datetime_geq"].clone();
+            json(200, serde_json::json!({"data": {"viewer": {"accounts": [{"series": [
+                {"count": 9, "sum": {"visits": 7}, "dimensions": {"ts": start}}
+            ]}]}}}))
+        });
+
+        let visits = fetch_hourly_visits(&client, &mock_settings(), "tag-a.com").await.unwrap();
+
+        assert!(visits.len() >= 24);
+        assert_eq!(visits[0], 7);
+        assert_eq!(visits.iter().sum::<u64>(), 7);
+        assert!(client.requests()[0].variables().contains("tag-a.com"));
+    }
+
+    #[tokio::test]
+    async fn test_fetch_sites_reporting_leaves_out_skipped() {
+        let client = MockClient::new(|req| {
+            if req.method == "GET" {
+                return json(200, sites_page(&["a.com", "b.com"], 1, 100, 2));
+            }
+            json(200, analytics_body(10, 20, 0))
+        });
+        let skip = HashSet::from(["b.com".to_string()]);
+
+        let (sites, _) = fetch_sites_reporting(&client, &mock_settings(), &skip, |_| {}).await.unwrap();
+
+        assert_eq!(sites.iter().map(|s| s.name.as_str()).collect::<Vec<_>>(), vec!["a.com"]);
+        assert!(!client.requests().iter().any(|r| r.variables().contains("tag-b.com")));
+    }
+
+    #[test]
+    fn test_site_fetch_times_keep_skipped_sites_times() {
+        let site = |name: &str| SiteData { name: name.to_string(), ..Default::default() };
+        let mut previous = snapshot("k", "2024-01-15T09:00:00Z");
+        previous.site_fetched_at.insert("a.com".to_string(), previous.fetched_at);
+        previous.site_fetched_at.insert("b.com".to_string(), previous.fetched_at + Duration::minutes(30));
+        let now = previous.fetched_at + Duration::hours(1);
+        let sites = vec![site("a.com"), site("b.com"), site("old.com")];
+        let fresh = HashSet::from(["a.com".to_string()]);
+
+        let times = site_fetch_times(&sites, &fresh, Some(&previous), now);
+
+        assert_eq!(times["a.com"], now);
+        assert_eq!(times["b.com"], previous.fetched_at + Duration::minutes(30));
+        // Saved before per-site times, so only the snapshot's own time is known
+        assert_eq!(times["old.com"], previous.fetched_at);
+        assert!(site_fetch_times(&sites, &fresh, None, now).values().all(|&t| t == now));
+    }
+
+    #[test]
+    fn test_merge_sites_keeps_skipped_and_takes_fresh() {
+        let site = |name: &str, visits| SiteData { name: name.to_string(), visits, ..Default::default() };
+        let previous = vec![site("a.com", 1), site("b.com", 2), site("gone.com", 3)];
+        let fresh = vec![site("a.com", 10), site("new.com", 4)];
+        let skipped = HashSet::from(["b.com".to_string()]);
+        let merged: Vec<_> = merge_sites(previous, fresh, &skipped).into_iter().map(|s| (s.name, s.visits)).collect();
+        // gone.com was due but not returned (removed or failed), so it drops out like in a full refresh
+        assert_eq!(
+            merged,
+            vec![("b.com".to_string(), 2), ("a.com".to_string(), 10), ("new.com".to_string(), 4)]
+        );
+    }
+
+    // --- time range preview tests ---
+
+    #[test]
+    fn test_time_range_preview_converts_to_timezone() {
+        let preview = time_range_preview("2024-01-15T00:00:00Z", "2024-01-21T13:00:00Z", "date", "Europe/Warsaw".parse().unwrap()).unwrap();
+        assert_eq!(preview.description, "Jan 15 01:00 – Jan 21 14:00 in Europe/Warsaw");
+        assert_eq!(preview.bucket, "day");
+        assert_eq!(preview.buckets, 7);
+        assert_eq!(preview.start, "2024-01-15T00:00:00Z");
+    }
+
+    #[test]
+    fn test_time_range_preview_hourly_buckets() {
+        let preview = time_range_preview("2024-01-15T10:00:00Z", "2024-01-16T09:30:00Z", "datetimeHour", Tz::UTC).unwrap();
+        assert_eq!(preview.bucket, "hour");
+        assert_eq!(preview.buckets, 24);
+        assert_eq!(preview.description, "Jan 15 10:00 – Jan 16 09:30 in UTC");
+    }
+
+    #[test]
+    fn test_time_range_preview_weekly_buckets() {
+        let preview = time_range_preview("2024-01-15T00:00:00Z", "2024-04-17T10:00:00Z", WEEK_BUCKET, Tz::UTC).unwrap();
+        assert_eq!(preview.bucket, "week");
+        assert_eq!(preview.buckets, 14);
+    }
+
+    #[test]
+    fn test_parse_timezone_rejects_unknown_timezone() {
+        let err = parse_timezone("Mars/Olympus").unwrap_err();
+        assert!(err.starts_with("Unknown timezone \"Mars/Olympus\""));
+        assert_eq!(parse_timezone("Europe/Warsaw").unwrap(), Some(chrono_tz::Europe::Warsaw));
+        assert!(parse_timezone("auto").is_ok());
+    }
 }
@@ -0,0 +1,133 @@
+//! Offline mode. When a refresh can't reach Cloudflare and a quick `HEAD` probe of the API
+//! fails too, the app goes offline: scheduled refreshes are skipped, the panel is served
+//! the last saved fetch marked `offline` instead of errors, and the API is probed every
+//! `PROBE_INTERVAL` until it answers, which triggers a refresh right away.
+
+use crate::client::{shared_client, API_BASE};
+use crate::commands::{get_settings, AnalyticsSnapshot};
+use crate::errors::FetchError;
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::{AppHandle, Emitter, Manager};
+use tracing::info;
+
+/// A probe is a single request with no retries; a reachable API answers well within this.
+const PROBE_TIMEOUT: Duration = Duration::from_secs(5);
+const PROBE_INTERVAL: Duration = Duration::from_secs(15);
+
+/// Whether the app is in offline mode.
+pub struct Offline(pub Mutex<bool>);
+
+/// Payload of the `connectivity-changed` event.
+#[derive(Serialize, Clone, Debug)]
+pub struct ConnectivityEvent {
+    pub online: bool,
+}
+
+pub fn is_offline(app: &AppHandle) -> bool {
+    *app.state::<Offline>().0.lock().unwrap()
+}
+
+/// Whether a failed refresh may have been caused by the connection rather than Cloudflare
+/// or the settings.
+fn is_connection_error(error: &FetchError) -> bool {
+    matches!(error, FetchError::Network { .. } | FetchError::Timeout { .. })
+}
+
+/// Whether Cloudflare's API answers at all; any HTTP status counts. Without usable network
+/// settings there is nothing to probe with, so the fetch is left to report the problem.
+async fn probe(app: &AppHandle) -> bool {
+    let Ok(client) = get_settings(app.clone()).map_err(String::from).and_then(|s| shared_client(app, &s)) else {
+        return true;
+    };
+    client.head(API_BASE).timeout(PROBE_TIMEOUT).send().await.is_ok()
+}
+
+/// Switch offline mode on or off, announcing the change. Returns whether it changed.
+fn set_offline(app: &AppHandle, offline: bool) -> bool {
+    let was = std::mem::replace(&mut *app.state::<Offline>().0.lock().unwrap(), offline);
+    if was == offline {
+        return false;
+    }
+    info!(offline, "Connectivity changed");
+    let _ = app.emit("connectivity-changed", ConnectivityEvent { online: !offline });
+    true
+}
+
+/// After a failed refresh: whether it failed because the machine is offline. If so, offline
+/// mode starts and the API is probed until it can be reached again.
+pub async fn went_offline(app: &AppHandle, error: &FetchError) -> bool {
+    if !is_connection_error(error) {
+        return false;
+    }
+    if is_offline(app) {
+        return true;
+    }
+    if probe(app).await {
+        return false;
+    }
+    if set_offline(app, true) {
+        tauri::async_runtime::spawn(watch(app.clone()));
+    }
+    true
+}
+
+/// Probe every `PROBE_INTERVAL` until the API answers, then leave offline mode and refresh.
+/// Boxed because the refresh can end up back in `went_offline`, which spawns this.
+fn watch(app: AppHandle) -> Pin<Box<dyn Future<Output = ()> + Send>> {
+    Box::pin(async move { watch_until_online(&app).await })
+}
+
+async fn watch_until_online(app: &AppHandle) {
+    loop {
+        tokio::time::sleep(PROBE_INTERVAL).await;
+        // A refresh from the panel may have found the connection back already
+        if !is_offline(app) {
+            return;
+        }
+        if probe(app).await {
+            break;
+        }
+    }
+    set_offline(app, false);
+    if !crate::refresh::is_paused(app) && !crate::refresh::check_auto_pause(app).await {
+        crate::refresh::run_now(app).await;
+    }
+}
+
+/// Whether the app is still offline, probing once if it was, so a refresh asked for from
+/// the panel doesn't wait for the next probe to notice the connection is back.
+pub async fn still_offline(app: &AppHandle) -> bool {
+    if !is_offline(app) {
+        return false;
+    }
+    if probe(app).await {
+        set_offline(app, false);
+        return false;
+    }
+    true
+}
+
+/// The last saved fetch marked `offline`, shown by the panel instead of an error.
+pub fn last_snapshot(app: &AppHandle) -> Result<AnalyticsSnapshot, FetchError> {
+    let saved = crate::lastfetch::get_cached_analytics(app.clone())?;
+    saved
+        .map(|snapshot| AnalyticsSnapshot { offline: true, ..snapshot })
+        .ok_or_else(|| FetchError::Network { message: "Offline, and there are no earlier results to show".to_string() })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_only_connection_errors_suggest_offline() {
+        assert!(is_connection_error(&FetchError::Network { message: "connect failed".to_string() }));
+        assert!(is_connection_error(&FetchError::Timeout { message: "timed out".to_string() }));
+        assert!(!is_connection_error(&FetchError::Auth { message: "bad token".to_string() }));
+        assert!(!is_connection_error(&FetchError::RateLimit { message: "slow down".to_string(), retry_after_secs: None }));
+    }
+}
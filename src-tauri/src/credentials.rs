@@ -0,0 +1,335 @@
+use crate::client::{shared_client, ApiResponse, AuthMethod, CloudflareClient, Credentials, API_BASE};
+use crate::commands::get_settings;
+use crate::errors::FetchError;
+use reqwest::StatusCode;
+use serde::Serialize;
+use tauri::AppHandle;
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum CredentialStatus {
+    Valid,
+    /// Cloudflare doesn't recognise the token at all.
+    Invalid,
+    /// The token exists but has expired or been disabled.
+    Expired,
+    /// The token can't see the configured account.
+    WrongAccount,
+    /// The token can see the account but not its Web Analytics.
+    MissingScope,
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Account {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Serialize, Debug)]
+pub struct CredentialCheck {
+    pub status: CredentialStatus,
+    pub message: String,
+}
+
+impl CredentialCheck {
+    fn new(status: CredentialStatus, message: impl Into<String>) -> Self {
+        Self {
+            status,
+            message: message.into(),
+        }
+    }
+}
+
+/// Interpret a `/user/tokens/verify` response.
+fn token_status(status: StatusCode, body: &serde_json::Value) -> CredentialStatus {
+    if !status.is_success() || body["success"].as_bool() != Some(true) {
+        return CredentialStatus::Invalid;
+    }
+    match body["result"]["status"].as_str() {
+        Some("active") => CredentialStatus::Valid,
+        _ => CredentialStatus::Expired,
+    }
+}
+
+/// Interpret a `/user` response, the closest thing to `/user/tokens/verify` for a Global API key.
+fn key_status(status: StatusCode, body: &serde_json::Value) -> CredentialStatus {
+    if status.is_success() && body["success"].as_bool() == Some(true) {
+        CredentialStatus::Valid
+    } else {
+        CredentialStatus::Invalid
+    }
+}
+
+/// Map the response to an account-scoped request to what it says about the token: `denied`
+/// when refused, `WrongAccount` when the account isn't found. Any other failure (a 400 or
+/// 5xx) says nothing about the token and is returned as the error.
+fn access_status(resp: &ApiResponse, denied: CredentialStatus) -> Result<CredentialStatus, FetchError> {
+    match resp.status {
+        status if status.is_success() => Ok(CredentialStatus::Valid),
+        StatusCode::NOT_FOUND => Ok(CredentialStatus::WrongAccount),
+        StatusCode::FORBIDDEN | StatusCode::UNAUTHORIZED => Ok(denied),
+        _ => resp.success_json().map(|_| CredentialStatus::Valid),
+    }
+}
+
+/// What the messages call the secret for `method`.
+fn subject(method: AuthMethod) -> &'static str {
+    match method {
+        AuthMethod::Token => "Token",
+        AuthMethod::ApiKey => "API key",
+    }
+}
+
+fn message_for(method: AuthMethod, status: CredentialStatus) -> String {
+    let problem = match status {
+        CredentialStatus::Valid => "is valid",
+        CredentialStatus::Invalid => match method {
+            AuthMethod::Token => "not recognised by Cloudflare",
+            AuthMethod::ApiKey => "or email not recognised by Cloudflare",
+        },
+        CredentialStatus::Expired => "has expired or is disabled",
+        CredentialStatus::WrongAccount => "has no access to this account",
+        CredentialStatus::MissingScope => "is missing Account Analytics read permission",
+    };
+    format!("{} {}", subject(method), problem)
+}
+
+/// GET `url`, returning the status and the JSON body (`Null` if it isn't JSON).
+async fn get(
+    client: &impl CloudflareClient,
+    auth: &Credentials,
+    url: &str,
+) -> Result<(StatusCode, serde_json::Value), FetchError> {
+    let resp = client.get(auth, url, &[]).await?;
+    let body = resp.json().unwrap_or(serde_json::Value::Null);
+    Ok((resp.status, body))
+}
+
+/// Parse one page of `GET /accounts`, returning the accounts and the total page count.
+fn parse_accounts(body: &serde_json::Value) -> Result<(Vec<Account>, u64), String> {
+    let accounts = body["result"]
+        .as_array()
+        .ok_or("Invalid response: missing result array")?
+        .iter()
+        .filter_map(|account| {
+            Some(Account {
+                id: account["id"].as_str()?.to_string(),
+                name: account["name"].as_str()?.to_string(),
+            })
+        })
+        .collect();
+    let total_pages = body["result_info"]["total_pages"].as_u64().unwrap_or(1);
+    Ok((accounts, total_pages))
+}
+
+/// Credentials as entered in the settings form, which may not be saved yet.
+fn entered(token: &str, auth_method: Option<AuthMethod>, email: Option<String>) -> Credentials {
+    Credentials::new(auth_method.unwrap_or_default(), token, email.as_deref().unwrap_or_default())
+}
+
+/// Accounts the credentials can access, so the account ID can be picked instead of pasted.
+#[tauri::command]
+pub async fn list_accounts(
+    app: AppHandle,
+    token: String,
+    auth_method: Option<AuthMethod>,
+    email: Option<String>,
+) -> Result<Vec<Account>, FetchError> {
+    let auth = entered(&token, auth_method, email);
+    if !auth.is_complete() {
+        return Err(FetchError::Auth { message: "API credentials are required".to_string() });
+    }
+
+    let client = shared_client(&app, &get_settings(app.clone())?)?;
+    let mut accounts = Vec::new();
+    let mut page = 1;
+    loop {
+        let url = format!("{}/accounts?page={}&per_page=50", API_BASE, page);
+        let body = CloudflareClient::get(&client, &auth, &url, &[]).await?.success_json()?;
+        let (batch, total_pages) = parse_accounts(&body).map_err(FetchError::parse)?;
+        accounts.extend(batch);
+        if page >= total_pages {
+            break;
+        }
+        page += 1;
+    }
+    accounts.sort_by_key(|a| a.name.to_lowercase());
+    Ok(accounts)
+}
+
+/// Check the token (or the Global API key and email, per `auth_method`) is active, can
+/// see `account_id`, and can read its Web Analytics.
+#[tauri::command]
+pub async fn verify_credentials(
+    app: AppHandle,
+    token: String,
+    account_id: String,
+    auth_method: Option<AuthMethod>,
+    email: Option<String>,
+) -> Result<CredentialCheck, FetchError> {
+    let client = shared_client(&app, &get_settings(app.clone())?)?;
+    check_credentials(&client, &entered(&token, auth_method, email), &account_id).await
+}
+
+pub async fn check_credentials(
+    client: &impl CloudflareClient,
+    auth: &Credentials,
+    account_id: &str,
+) -> Result<CredentialCheck, FetchError> {
+    let account_id = account_id.trim();
+    if auth.secret.is_empty() {
+        return Ok(CredentialCheck::new(CredentialStatus::Invalid, format!("{} is empty", subject(auth.method))));
+    }
+
+    let mut result = match auth.method {
+        AuthMethod::Token => {
+            let (status, body) = get(client, auth, &format!("{}/user/tokens/verify", API_BASE)).await?;
+            token_status(status, &body)
+        }
+        AuthMethod::ApiKey => {
+            if auth.email.is_empty() {
+                return Ok(CredentialCheck::new(CredentialStatus::Invalid, "Email is required with a Global API key"));
+            }
+            let (status, body) = get(client, auth, &format!("{}/user", API_BASE)).await?;
+            key_status(status, &body)
+        }
+    };
+
+    if result == CredentialStatus::Valid && !account_id.is_empty() {
+        let resp = client.get(auth, &format!("{}/accounts/{}", API_BASE, account_id), &[]).await?;
+        result = access_status(&resp, CredentialStatus::WrongAccount)?;
+    }
+    if result == CredentialStatus::Valid && !account_id.is_empty() {
+        let url = format!("{}/accounts/{}/rum/site_info/list", API_BASE, account_id);
+        let resp = client.get(auth, &url, &[]).await?;
+        result = access_status(&resp, CredentialStatus::MissingScope)?;
+    }
+
+    Ok(CredentialCheck::new(result, message_for(auth.method, result)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{json as json_response, MockClient};
+    use serde_json::json;
+
+    #[test]
+    fn test_token_status_active() {
+        let body = json!({"success": true, "result": {"id": "abc", "status": "active"}});
+        assert_eq!(token_status(StatusCode::OK, &body), CredentialStatus::Valid);
+    }
+
+    #[test]
+    fn test_token_status_expired_or_disabled() {
+        let body = json!({"success": true, "result": {"id": "abc", "status": "expired"}});
+        assert_eq!(token_status(StatusCode::OK, &body), CredentialStatus::Expired);
+        let body = json!({"success": true, "result": {"id": "abc", "status": "disabled"}});
+        assert_eq!(token_status(StatusCode::OK, &body), CredentialStatus::Expired);
+    }
+
+    #[test]
+    fn test_token_status_rejected() {
+        let body = json!({"success": false, "errors": [{"code": 1000, "message": "Invalid API Token"}]});
+        assert_eq!(token_status(StatusCode::UNAUTHORIZED, &body), CredentialStatus::Invalid);
+    }
+
+    #[test]
+    fn test_key_status() {
+        let body = json!({"success": true, "result": {"id": "u1", "email": "me@example.com"}});
+        assert_eq!(key_status(StatusCode::OK, &body), CredentialStatus::Valid);
+        let body = json!({"success": false, "errors": [{"code": 9103, "message": "Unknown X-Auth-Key or X-Auth-Email"}]});
+        assert_eq!(key_status(StatusCode::FORBIDDEN, &body), CredentialStatus::Invalid);
+    }
+
+    #[test]
+    fn test_message_names_the_auth_method() {
+        assert_eq!(message_for(AuthMethod::Token, CredentialStatus::Valid), "Token is valid");
+        assert_eq!(
+            message_for(AuthMethod::ApiKey, CredentialStatus::Invalid),
+            "API key or email not recognised by Cloudflare"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_check_credentials_uses_user_endpoint_for_api_key() {
+        let client = MockClient::new(|request| {
+            assert!(!request.url.ends_with("/user/tokens/verify"));
+            json_response(200, json!({"success": true, "result": {}}))
+        });
+        let auth = Credentials::new(AuthMethod::ApiKey, "key", "me@example.com");
+        let check = check_credentials(&client, &auth, "acc").await.unwrap();
+        assert_eq!(check.status, CredentialStatus::Valid);
+        let urls: Vec<_> = client.requests().into_iter().map(|r| r.url).collect();
+        assert_eq!(urls[0], format!("{}/user", API_BASE));
+        assert_eq!(urls.len(), 3);
+
+        let auth = Credentials::new(AuthMethod::ApiKey, "key", "");
+        let check = check_credentials(&client, &auth, "acc").await.unwrap();
+        assert_eq!(check.status, CredentialStatus::Invalid);
+        assert_eq!(client.requests().len(), 3);
+    }
+
+    fn response(status: u16) -> ApiResponse {
+        ApiResponse {
+            status: StatusCode::from_u16(status).unwrap(),
+            body: r#"{"success": false}"#.to_string(),
+            request_id: String::new(),
+        }
+    }
+
+    #[test]
+    fn test_access_status() {
+        let status = |code, denied| access_status(&response(code), denied).unwrap();
+        assert_eq!(status(403, CredentialStatus::MissingScope), CredentialStatus::MissingScope);
+        assert_eq!(status(401, CredentialStatus::MissingScope), CredentialStatus::MissingScope);
+        assert_eq!(status(404, CredentialStatus::MissingScope), CredentialStatus::WrongAccount);
+        assert_eq!(status(200, CredentialStatus::WrongAccount), CredentialStatus::Valid);
+        assert_eq!(status(204, CredentialStatus::WrongAccount), CredentialStatus::Valid);
+    }
+
+    #[test]
+    fn test_access_status_other_failures_are_errors() {
+        for code in [400, 429, 500, 503] {
+            assert!(access_status(&response(code), CredentialStatus::WrongAccount).is_err(), "{}", code);
+        }
+    }
+
+    #[test]
+    fn test_parse_accounts() {
+        let body = json!({
+            "success": true,
+            "result": [
+                {"id": "a1", "name": "Personal", "type": "standard"},
+                {"id": "b2", "name": "Work"},
+                {"name": "missing id"}
+            ],
+            "result_info": {"page": 1, "per_page": 50, "total_pages": 3}
+        });
+        let (accounts, total_pages) = parse_accounts(&body).unwrap();
+        assert_eq!(
+            accounts,
+            vec![
+                Account { id: "a1".to_string(), name: "Personal".to_string() },
+                Account { id: "b2".to_string(), name: "Work".to_string() },
+            ]
+        );
+        assert_eq!(total_pages, 3);
+    }
+
+    #[test]
+    fn test_parse_accounts_without_result_info() {
+        let (accounts, total_pages) = parse_accounts(&json!({"result": []})).unwrap();
+        assert!(accounts.is_empty());
+        assert_eq!(total_pages, 1);
+        assert!(parse_accounts(&json!({"success": false})).is_err());
+    }
+
+    #[test]
+    fn test_status_serializes_snake_case() {
+        assert_eq!(
+            serde_json::to_string(&CredentialStatus::MissingScope).unwrap(),
+            "\"missing_scope\""
+        );
+    }
+}
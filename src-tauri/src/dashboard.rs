@@ -0,0 +1,166 @@
+//! A resizable, always-on-top window for keeping stats on a second monitor. It loads the
+//! panel's page with `?view=dashboard`, so it gets the same events, and it isn't a panel,
+//! so it stays open when it loses focus. Its position and size are kept in `dashboard.json`.
+
+use crate::errors::FetchError;
+use crate::journal::write_atomic;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager, WebviewUrl, WebviewWindow, WebviewWindowBuilder, WindowEvent};
+use tracing::warn;
+
+pub const LABEL: &str = "dashboard";
+
+const DEFAULT_SIZE: (f64, f64) = (420.0, 640.0);
+const MIN_SIZE: (f64, f64) = (320.0, 240.0);
+
+/// Position and size of the dashboard window, in logical pixels.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct WindowBounds {
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+}
+
+/// Whether the dashboard window is open, so background refreshes shouldn't pause.
+pub fn is_open(app: &AppHandle) -> bool {
+    app.get_webview_window(LABEL)
+        .is_some_and(|window| window.is_visible().unwrap_or(false))
+}
+
+fn current_bounds(window: &WebviewWindow) -> Option<WindowBounds> {
+    let scale = window.scale_factor().ok()?;
+    let position = window.outer_position().ok()?.to_logical::<f64>(scale);
+    let size = window.inner_size().ok()?.to_logical::<f64>(scale);
+    Some(WindowBounds {
+        x: position.x,
+        y: position.y,
+        width: size.width,
+        height: size.height,
+    })
+}
+
+/// Where the window's bounds are kept. Apart from `settings.json`, so saving them on every
+/// focus change can't overwrite settings the panel is editing, nor depend on those validating.
+fn bounds_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join("dashboard.json"))
+}
+
+fn read_bounds(path: &Path) -> Option<WindowBounds> {
+    let data = fs::read_to_string(path).ok()?;
+    serde_json::from_str(&data)
+        .inspect_err(|e| warn!(error = %e, "Ignoring unreadable dashboard window position"))
+        .ok()
+}
+
+/// Write `bounds` to `path` unless they're already there.
+fn write_bounds(path: &Path, bounds: WindowBounds) -> std::io::Result<()> {
+    if read_bounds(path) == Some(bounds) {
+        return Ok(());
+    }
+    write_atomic(path, &bounds)
+}
+
+fn save_bounds(app: &AppHandle, bounds: WindowBounds) {
+    if let Err(e) = bounds_path(app).and_then(|path| write_bounds(&path, bounds).map_err(|e| e.to_string())) {
+        warn!(error = %e, "Error saving dashboard window position");
+    }
+}
+
+/// Whether the top-left corner of `bounds` lies on one of `screens` (`x, y, width,
+/// height`, logical), so a window saved on a since unplugged monitor isn't restored off-screen.
+fn on_screen(bounds: &WindowBounds, screens: &[(f64, f64, f64, f64)]) -> bool {
+    screens.iter().any(|&(x, y, width, height)| {
+        bounds.x >= x && bounds.x < x + width && bounds.y >= y && bounds.y < y + height
+    })
+}
+
+fn screens(app: &AppHandle) -> Vec<(f64, f64, f64, f64)> {
+    let monitors = app.available_monitors().unwrap_or_default();
+    monitors
+        .iter()
+        .map(|monitor| {
+            let scale = monitor.scale_factor();
+            let position = monitor.position().to_logical::<f64>(scale);
+            let size = monitor.size().to_logical::<f64>(scale);
+            (position.x, position.y, size.width, size.height)
+        })
+        .collect()
+}
+
+/// Open the dashboard window, or bring it to the front if it's already open.
+#[tauri::command]
+pub async fn open_dashboard(app: AppHandle) -> Result<(), FetchError> {
+    if let Some(window) = app.get_webview_window(LABEL) {
+        window.show().map_err(|e| e.to_string())?;
+        window.set_focus().map_err(|e| e.to_string())?;
+        return Ok(());
+    }
+
+    let saved = read_bounds(&bounds_path(&app)?);
+    let (width, height) = saved.map_or(DEFAULT_SIZE, |b| (b.width, b.height));
+    let builder = WebviewWindowBuilder::new(&app, LABEL, WebviewUrl::App("index.html?view=dashboard".into()))
+        .title("FlareStats")
+        .inner_size(width.max(MIN_SIZE.0), height.max(MIN_SIZE.1))
+        .min_inner_size(MIN_SIZE.0, MIN_SIZE.1)
+        .resizable(true)
+        .always_on_top(true);
+    let builder = match saved {
+        Some(bounds) if on_screen(&bounds, &screens(&app)) => builder.position(bounds.x, bounds.y),
+        _ => builder.center(),
+    };
+    let window = builder.build().map_err(|e| e.to_string())?;
+
+    let handle = app.clone();
+    let tracked = window.clone();
+    window.on_window_event(move |event| match event {
+        WindowEvent::CloseRequested { .. } | WindowEvent::Focused(false) => {
+            if let Some(bounds) = current_bounds(&tracked) {
+                save_bounds(&handle, bounds);
+            }
+        }
+        WindowEvent::Destroyed => {
+            let panel_visible = handle
+                .get_webview_window("main")
+                .is_some_and(|panel| panel.is_visible().unwrap_or(false));
+            if !panel_visible {
+                crate::refresh::panel_hidden(&handle);
+            }
+        }
+        _ => {}
+    });
+    crate::refresh::panel_shown(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bounds(x: f64, y: f64) -> WindowBounds {
+        WindowBounds { x, y, width: 420.0, height: 640.0 }
+    }
+
+    #[test]
+    fn test_bounds_round_trip() {
+        let path = crate::test_support::temp_dir("dashboard", "bounds").join("dashboard.json");
+        assert_eq!(read_bounds(&path), None);
+        write_bounds(&path, bounds(10.0, 20.0)).unwrap();
+        assert_eq!(read_bounds(&path), Some(bounds(10.0, 20.0)));
+        fs::write(&path, "{").unwrap();
+        assert_eq!(read_bounds(&path), None);
+    }
+
+    #[test]
+    fn test_on_screen() {
+        let screens = [(0.0, 0.0, 1440.0, 900.0), (1440.0, -200.0, 1920.0, 1080.0)];
+        assert!(on_screen(&bounds(100.0, 50.0), &screens));
+        assert!(on_screen(&bounds(2000.0, -100.0), &screens));
+        assert!(!on_screen(&bounds(3500.0, 100.0), &screens));
+        assert!(!on_screen(&bounds(100.0, 50.0), &[]));
+    }
+}
@@ -0,0 +1,116 @@
+//! `flarestats://` links, e.g. `flarestats://site/example.com?period=7d`.
+
+use crate::commands::{get_settings, save_settings, PERIODS};
+use reqwest::Url;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter};
+use tracing::warn;
+
+const SCHEME: &str = "flarestats";
+
+/// What a link asked the panel to show, sent to the webview as `deep-link`.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct DeepLinkTarget {
+    pub site: Option<String>,
+    pub period: Option<String>,
+}
+
+/// Accepts `flarestats://`, `flarestats://open` and `flarestats://site/<name>`,
+/// each with an optional `?period=` from `PERIODS`, e.g. `7d` or `this_month`.
+fn parse(url: &str) -> Result<DeepLinkTarget, String> {
+    let url = Url::parse(url).map_err(|e| format!("Invalid link {}: {}", url, e))?;
+    if url.scheme() != SCHEME {
+        return Err(format!("Unsupported link scheme {}", url.scheme()));
+    }
+
+    let site = match url.host_str().unwrap_or("") {
+        "" | "open" => None,
+        "site" => {
+            let name = url.path().trim_matches('/');
+            if name.is_empty() {
+                return Err("Link is missing the site name".to_string());
+            }
+            Some(name.to_string())
+        }
+        other => return Err(format!("Unknown link target {}", other)),
+    };
+
+    let period = url
+        .query_pairs()
+        .find(|(key, _)| key == "period")
+        .map(|(_, value)| value.into_owned());
+    if let Some(period) = &period
+        && !PERIODS.contains(&period.as_str())
+    {
+        return Err(format!("Unknown period {}, use one of {}", period, PERIODS.join(", ")));
+    }
+
+    Ok(DeepLinkTarget { site, period })
+}
+
+/// Open the panel on the linked site, saving a requested period as the new default.
+pub fn handle(app: &AppHandle, url: &str) {
+    let target = match parse(url) {
+        Ok(target) => target,
+        Err(e) => {
+            warn!(url, error = %e, "Ignoring deep link");
+            return;
+        }
+    };
+
+    if let Some(period) = &target.period
+        && let Ok(mut settings) = get_settings(app.clone())
+        && settings.period != *period
+    {
+        settings.period = period.clone();
+        if let Err(e) = save_settings(app.clone(), settings) {
+            warn!(error = %e, "Error saving period from deep link");
+        }
+    }
+
+    crate::show_panel(app);
+    let _ = app.emit("deep-link", &target);
+}
+
+/// Open the panel scrolled to `site`, like a `flarestats://site/` link.
+pub fn open_site(app: &AppHandle, site: &str) {
+    crate::show_panel(app);
+    let target = DeepLinkTarget {
+        site: Some(site.to_string()),
+        period: None,
+    };
+    let _ = app.emit("deep-link", &target);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_site_with_period() {
+        assert_eq!(
+            parse("flarestats://site/example.com?period=7d").unwrap(),
+            DeepLinkTarget {
+                site: Some("example.com".to_string()),
+                period: Some("7d".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_open_without_target() {
+        assert_eq!(parse("flarestats://").unwrap(), DeepLinkTarget::default());
+        assert_eq!(
+            parse("flarestats://open?period=30d").unwrap().period.as_deref(),
+            Some("30d")
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_bad_links() {
+        assert!(parse("https://site/example.com").is_err());
+        assert!(parse("flarestats://site/").is_err());
+        assert!(parse("flarestats://settings").is_err());
+        assert!(parse("flarestats://site/example.com?period=1y").is_err());
+    }
+}
@@ -0,0 +1,212 @@
+//! Typed failures returned by every command, so the UI can tell a token that needs
+//! replacing from a rate limit worth waiting out. Failed refreshes are also reported
+//! as `analytics-error` events, and per-fetch failure counts as `analytics-summary`.
+
+use serde::Serialize;
+use std::fmt;
+use tauri::{AppHandle, Emitter};
+
+/// Serialized as `{"kind": "...", "message": "..."}` plus the variant's other fields.
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FetchError {
+    /// Missing credentials, or a 401/403: the token needs attention, retrying won't help.
+    Auth { message: String },
+    /// Still rate limited after retrying; `retry_after_secs` is Cloudflare's `Retry-After`.
+    RateLimit { message: String, retry_after_secs: Option<u64> },
+    /// The GraphQL API answered with an `errors` array, kept as sent.
+    Graphql { message: String, errors: Vec<serde_json::Value> },
+    /// The request timed out.
+    Timeout { message: String },
+    /// Couldn't connect, or Cloudflare kept answering with 5xx.
+    Network { message: String },
+    /// The API answered with something we couldn't understand.
+    Parse { message: String },
+    /// Any other non-2xx response.
+    Api { status: u16, message: String },
+    /// Not a problem talking to Cloudflare: invalid settings, file system errors.
+    Other { message: String },
+}
+
+impl FetchError {
+    /// The error for a non-2xx `status` that wasn't retried away.
+    pub fn for_status(status: reqwest::StatusCode, message: String) -> Self {
+        match status.as_u16() {
+            401 | 403 => FetchError::Auth { message },
+            429 => FetchError::RateLimit { message, retry_after_secs: None },
+            500..=599 => FetchError::Network { message },
+            code => FetchError::Api { status: code, message },
+        }
+    }
+
+    pub fn missing_credentials() -> Self {
+        FetchError::Auth { message: "Please configure API token and Account ID in settings".to_string() }
+    }
+
+    pub fn parse(message: impl fmt::Display) -> Self {
+        FetchError::Parse { message: message.to_string() }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            FetchError::Auth { message }
+            | FetchError::RateLimit { message, .. }
+            | FetchError::Graphql { message, .. }
+            | FetchError::Timeout { message }
+            | FetchError::Network { message }
+            | FetchError::Parse { message }
+            | FetchError::Api { message, .. }
+            | FetchError::Other { message } => message,
+        }
+    }
+
+    fn message_mut(&mut self) -> &mut String {
+        match self {
+            FetchError::Auth { message }
+            | FetchError::RateLimit { message, .. }
+            | FetchError::Graphql { message, .. }
+            | FetchError::Timeout { message }
+            | FetchError::Network { message }
+            | FetchError::Parse { message }
+            | FetchError::Api { message, .. }
+            | FetchError::Other { message } => message,
+        }
+    }
+
+    /// The same error with its message rewritten by `f`, e.g. to add context.
+    pub fn map_message(mut self, f: impl FnOnce(&str) -> String) -> Self {
+        let message = self.message_mut();
+        *message = f(message);
+        self
+    }
+
+    /// Whether trying again later may succeed, as opposed to needing the user to act.
+    pub fn is_transient(&self) -> bool {
+        matches!(
+            self,
+            FetchError::RateLimit { .. } | FetchError::Timeout { .. } | FetchError::Network { .. }
+        )
+    }
+}
+
+impl fmt::Display for FetchError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(self.message())
+    }
+}
+
+impl From<String> for FetchError {
+    fn from(message: String) -> Self {
+        FetchError::Other { message }
+    }
+}
+
+impl From<&str> for FetchError {
+    fn from(message: &str) -> Self {
+        FetchError::Other { message: message.to_string() }
+    }
+}
+
+/// For helpers that still report plain messages, e.g. self-test steps.
+impl From<FetchError> for String {
+    fn from(error: FetchError) -> Self {
+        error.message().to_string()
+    }
+}
+
+/// Payload of the `analytics-error` event.
+#[derive(Serialize, Clone, Debug)]
+pub struct AnalyticsErrorEvent {
+    /// The site that failed, or `None` when the whole refresh failed.
+    pub site: Option<String>,
+    pub error: FetchError,
+}
+
+pub fn emit_error(app: &AppHandle, site: Option<&str>, error: &FetchError) {
+    let event = AnalyticsErrorEvent {
+        site: site.map(str::to_string),
+        error: error.clone(),
+    };
+    let _ = app.emit("analytics-error", &event);
+}
+
+/// Payload of the `analytics-summary` event, sent when a fetch has heard back from every
+/// site (after the last `analytics-site-loaded`), so partial failures show up as
+/// "3 of 52 sites" rather than only as a list of names.
+#[derive(Serialize, Clone, Debug)]
+pub struct FetchSummary {
+    pub loaded: usize,
+    pub failed: usize,
+}
+
+pub fn emit_summary(app: &AppHandle, loaded: usize, failed: usize) {
+    let _ = app.emit("analytics-summary", &FetchSummary { loaded, failed });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use reqwest::StatusCode;
+
+    #[test]
+    fn test_for_status_auth() {
+        assert!(matches!(
+            FetchError::for_status(StatusCode::FORBIDDEN, "API error 403".to_string()),
+            FetchError::Auth { .. }
+        ));
+        assert!(matches!(
+            FetchError::for_status(StatusCode::UNAUTHORIZED, "API error 401".to_string()),
+            FetchError::Auth { .. }
+        ));
+    }
+
+    #[test]
+    fn test_for_status_rate_limit_network_and_other() {
+        assert!(matches!(
+            FetchError::for_status(StatusCode::TOO_MANY_REQUESTS, "slow down".to_string()),
+            FetchError::RateLimit { retry_after_secs: None, .. }
+        ));
+        assert!(matches!(
+            FetchError::for_status(StatusCode::BAD_GATEWAY, "bad gateway".to_string()),
+            FetchError::Network { .. }
+        ));
+        assert_eq!(
+            FetchError::for_status(StatusCode::NOT_FOUND, "missing".to_string()),
+            FetchError::Api { status: 404, message: "missing".to_string() }
+        );
+    }
+
+    #[test]
+    fn test_is_transient() {
+        assert!(FetchError::Timeout { message: "x".to_string() }.is_transient());
+        assert!(!FetchError::Auth { message: "x".to_string() }.is_transient());
+        assert!(!FetchError::parse("x").is_transient());
+    }
+
+    #[test]
+    fn test_plain_messages_convert_both_ways() {
+        let error: FetchError = "Invalid interval".into();
+        assert_eq!(error, FetchError::Other { message: "Invalid interval".to_string() });
+        assert_eq!(String::from(FetchError::parse("bad json")), "bad json");
+    }
+
+    #[test]
+    fn test_serializes_with_kind_tag() {
+        let json = serde_json::to_value(FetchError::RateLimit {
+            message: "slow down".to_string(),
+            retry_after_secs: Some(30),
+        })
+        .unwrap();
+        assert_eq!(
+            json,
+            serde_json::json!({"kind": "rate_limit", "message": "slow down", "retry_after_secs": 30})
+        );
+        let json = serde_json::to_value(FetchError::Graphql {
+            message: "GraphQL errors".to_string(),
+            errors: vec![serde_json::json!({"message": "boom"})],
+        })
+        .unwrap();
+        assert_eq!(json["kind"], "graphql");
+        assert_eq!(json["errors"][0]["message"], "boom");
+    }
+}
@@ -0,0 +1,112 @@
+use crate::commands::{fetch_all_sites, get_settings, SiteData};
+use crate::errors::FetchError;
+use std::fs;
+use tauri::AppHandle;
+use tauri_plugin_dialog::DialogExt;
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// One row per series point: `site,timestamp,visits,page_views`.
+fn to_csv(sites: &[SiteData]) -> String {
+    let mut out = String::from("site,timestamp,visits,page_views\n");
+    for site in sites {
+        for point in &site.series {
+            out.push_str(&format!(
+                "{},{},{},{}\n",
+                csv_field(&site.name),
+                point.timestamp,
+                point.visits,
+                point.page_views
+            ));
+        }
+    }
+    out
+}
+
+/// Fetch analytics for `period` (default: the configured one) and write them to
+/// a CSV or JSON file picked in a save dialog. Returns the written path, or
+/// `None` if the dialog was cancelled.
+#[tauri::command]
+pub async fn export_analytics(
+    app: AppHandle,
+    format: String,
+    period: Option<String>,
+) -> Result<Option<String>, FetchError> {
+    let (filter_name, ext) = match format.as_str() {
+        "csv" => ("CSV", "csv"),
+        "json" => ("JSON", "json"),
+        _ => return Err(format!("Unsupported export format: {}", format).into()),
+    };
+
+    let mut settings = get_settings(app.clone())?;
+    if let Some(period) = period {
+        settings.period = period;
+    }
+    let client = crate::budget::metered_client(&app, &settings)?;
+    let sites = fetch_all_sites(&client, &settings).await?;
+    let contents = match ext {
+        "csv" => to_csv(&sites),
+        _ => serde_json::to_string_pretty(&sites).map_err(|e| e.to_string())?,
+    };
+
+    let (tx, rx) = tokio::sync::oneshot::channel();
+    app.dialog()
+        .file()
+        .add_filter(filter_name, &[ext])
+        .set_file_name(format!("flarestats-{}.{}", settings.period, ext))
+        .save_file(move |path| {
+            let _ = tx.send(path);
+        });
+    let Some(path) = rx.await.map_err(|e| e.to_string())? else {
+        return Ok(None);
+    };
+
+    let path = path.into_path().map_err(|e| e.to_string())?;
+    fs::write(&path, contents).map_err(|e| e.to_string())?;
+    Ok(Some(path.display().to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::SeriesPoint;
+
+    #[test]
+    fn test_to_csv_one_row_per_point() {
+        let sites = vec![SiteData {
+            name: "a.com".to_string(),
+            series: vec![
+                SeriesPoint {
+                    timestamp: "2024-01-15".to_string(),
+                    visits: 1,
+                    page_views: 2,
+                    ..Default::default()
+                },
+                SeriesPoint {
+                    timestamp: "2024-01-16".to_string(),
+                    visits: 3,
+                    page_views: 4,
+                    ..Default::default()
+                },
+            ],
+            ..Default::default()
+        }];
+        assert_eq!(
+            to_csv(&sites),
+            "site,timestamp,visits,page_views\na.com,2024-01-15,1,2\na.com,2024-01-16,3,4\n"
+        );
+    }
+
+    #[test]
+    fn test_csv_field_quotes_special_characters() {
+        assert_eq!(csv_field("a.com"), "a.com");
+        assert_eq!(csv_field("a,b"), "\"a,b\"");
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+}
@@ -0,0 +1,291 @@
+use crate::commands::{
+    bucket_for_days, fill_series_gaps, get_settings, time_range, timezone, SeriesPoint, Settings, SiteData, DAY_BUCKET,
+    WEEK_BUCKET,
+};
+use crate::errors::FetchError;
+use chrono::{Duration, NaiveDate, Utc};
+use chrono_tz::Tz;
+use rusqlite::{params, Connection};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+/// Daily totals older than this are pruned after every write.
+const RETENTION_DAYS: i64 = 400;
+
+/// Lazily opened connection to the local history database.
+pub struct HistoryDb(pub Mutex<Option<Connection>>);
+
+fn open(path: &std::path::Path) -> rusqlite::Result<Connection> {
+    let conn = Connection::open(path)?;
+    init_schema(&conn)?;
+    Ok(conn)
+}
+
+fn init_schema(conn: &Connection) -> rusqlite::Result<()> {
+    // auto_vacuum only takes effect before the first table is created
+    conn.execute_batch(
+        "PRAGMA auto_vacuum = INCREMENTAL;
+         CREATE TABLE IF NOT EXISTS daily_totals (
+             site TEXT NOT NULL,
+             date TEXT NOT NULL,
+             visits INTEGER NOT NULL,
+             page_views INTEGER NOT NULL,
+             PRIMARY KEY (site, date)
+         );",
+    )
+}
+
+fn with_conn<T>(
+    app: &AppHandle,
+    f: impl FnOnce(&mut Connection) -> rusqlite::Result<T>,
+) -> Result<T, String> {
+    let state = app.state::<HistoryDb>();
+    let mut guard = state.0.lock().map_err(|e| e.to_string())?;
+    if guard.is_none() {
+        let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+        std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+        *guard = Some(open(&dir.join("history.sqlite3")).map_err(|e| e.to_string())?);
+    }
+    f(guard.as_mut().unwrap()).map_err(|e| e.to_string())
+}
+
+/// Sum a site's series into per-day totals keyed by `YYYY-MM-DD`.
+fn daily_totals(site: &SiteData) -> HashMap<String, (u64, u64)> {
+    let mut days: HashMap<String, (u64, u64)> = HashMap::new();
+    for point in &site.series {
+        let Some(date) = point.timestamp.get(..10) else {
+            continue;
+        };
+        let entry = days.entry(date.to_string()).or_insert((0, 0));
+        entry.0 += point.visits;
+        entry.1 += point.page_views;
+    }
+    days
+}
+
+/// Store the daily totals of `sites`, whose series are bucketed by `bucket`. Weekly points
+/// are labelled with their Monday and would pass for that day's total, so they're skipped.
+fn record(conn: &mut Connection, sites: &[SiteData], bucket: &str, today: NaiveDate) -> rusqlite::Result<()> {
+    let tx = conn.transaction()?;
+    if bucket != WEEK_BUCKET {
+        // A refresh may only cover part of a day (e.g. the 24h window), so never
+        // let a partial total overwrite a more complete one recorded earlier.
+        let mut stmt = tx.prepare(
+            "INSERT INTO daily_totals (site, date, visits, page_views) VALUES (?1, ?2, ?3, ?4)
+             ON CONFLICT (site, date) DO UPDATE SET
+                 visits = MAX(visits, excluded.visits),
+                 page_views = MAX(page_views, excluded.page_views)",
+        )?;
+        for site in sites {
+            for (date, (visits, page_views)) in daily_totals(site) {
+                stmt.execute(params![site.name, date, visits, page_views])?;
+            }
+        }
+    }
+    let cutoff = (today - Duration::days(RETENTION_DAYS))
+        .format("%Y-%m-%d")
+        .to_string();
+    tx.execute("DELETE FROM daily_totals WHERE date < ?1", params![cutoff])?;
+    tx.commit()?;
+    conn.execute_batch("PRAGMA incremental_vacuum;")
+}
+
+fn query(
+    conn: &Connection,
+    site: &str,
+    start: NaiveDate,
+    end: NaiveDate,
+    bucket: &str,
+) -> rusqlite::Result<Vec<SeriesPoint>> {
+    let mut stmt = conn.prepare(
+        "SELECT date, visits, page_views FROM daily_totals
+         WHERE site = ?1 AND date >= ?2 AND date <= ?3",
+    )?;
+    let rows = stmt.query_map(
+        params![
+            site,
+            start.format("%Y-%m-%d").to_string(),
+            end.format("%Y-%m-%d").to_string()
+        ],
+        |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                (row.get::<_, u64>(1)?, row.get::<_, u64>(2)?),
+            ))
+        },
+    )?;
+    let data = rows.collect::<rusqlite::Result<HashMap<_, _>>>()?;
+
+    Ok(fill_series_gaps(
+        &start.format("%Y-%m-%dT00:00:00Z").to_string(),
+        &end.format("%Y-%m-%dT00:00:00Z").to_string(),
+        bucket,
+        Tz::UTC,
+        &data,
+    ))
+}
+
+/// The current date in `Settings::timezone`, which rows are keyed by.
+fn today(settings: &Settings) -> NaiveDate {
+    Utc::now().with_timezone(&timezone(settings)).date_naive()
+}
+
+fn parse_range_days(range: &str) -> Option<i64> {
+    let days: i64 = range.strip_suffix('d')?.parse().ok()?;
+    (1..=RETENTION_DAYS).contains(&days).then_some(days)
+}
+
+/// Append the per-site daily totals of a refresh fetched with `settings` to the history database.
+pub fn record_refresh(app: &AppHandle, settings: &Settings, sites: &[SiteData]) {
+    let today = today(settings);
+    let (_, _, bucket) = time_range(settings);
+    if let Err(e) = with_conn(app, |conn| record(conn, sites, bucket, today)) {
+        warn!(error = %e, "Error recording history");
+    }
+}
+
+/// Daily points for the trailing `days` of each named site.
+pub fn recent(
+    app: &AppHandle,
+    settings: &Settings,
+    sites: &[String],
+    days: i64,
+) -> Result<HashMap<String, Vec<SeriesPoint>>, String> {
+    let end = today(settings);
+    let start = end - Duration::days(days - 1);
+    with_conn(app, |conn| {
+        sites
+            .iter()
+            .map(|site| Ok((site.clone(), query(conn, site, start, end, DAY_BUCKET)?)))
+            .collect()
+    })
+}
+
+/// Daily points, or weekly ones for ranges past `WEEKLY_AFTER_DAYS`.
+#[tauri::command]
+pub fn get_history(app: AppHandle, site: String, range: String) -> Result<Vec<SeriesPoint>, FetchError> {
+    let days = parse_range_days(&range).ok_or(format!("Invalid history range: {}", range))?;
+    let end = today(&get_settings(app.clone())?);
+    let start = end - Duration::days(days - 1);
+    Ok(with_conn(&app, |conn| query(conn, &site, start, end, bucket_for_days(days)))?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn memory_db() -> Connection {
+        let conn = Connection::open_in_memory().unwrap();
+        init_schema(&conn).unwrap();
+        conn
+    }
+
+    fn site(name: &str, points: &[(&str, u64, u64)]) -> SiteData {
+        SiteData {
+            name: name.to_string(),
+            visits: points.iter().map(|p| p.1).sum(),
+            page_views: points.iter().map(|p| p.2).sum(),
+            series: points
+                .iter()
+                .map(|&(ts, visits, page_views)| SeriesPoint {
+                    timestamp: ts.to_string(),
+                    visits,
+                    page_views,
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    fn date(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_daily_totals_sums_hourly_points_per_day() {
+        let s = site(
+            "a.com",
+            &[
+                ("2024-01-15T22:00:00Z", 1, 2),
+                ("2024-01-15T23:00:00Z", 3, 4),
+                ("2024-01-16T00:00:00Z", 5, 6),
+            ],
+        );
+        let days = daily_totals(&s);
+        assert_eq!(days["2024-01-15"], (4, 6));
+        assert_eq!(days["2024-01-16"], (5, 6));
+    }
+
+    #[test]
+    fn test_record_keeps_most_complete_total() {
+        let mut conn = memory_db();
+        let today = date("2024-01-16");
+        record(&mut conn, &[site("a.com", &[("2024-01-15", 100, 200)])], DAY_BUCKET, today).unwrap();
+        // a later 24h refresh only sees the tail of the 15th
+        record(&mut conn, &[site("a.com", &[("2024-01-15T23:00:00Z", 10, 20)])], DAY_BUCKET, today).unwrap();
+
+        let series = query(&conn, "a.com", date("2024-01-14"), date("2024-01-16"), DAY_BUCKET).unwrap();
+        assert_eq!(series.len(), 3);
+        assert_eq!(series[0].visits, 0);
+        assert_eq!(series[1].visits, 100);
+        assert_eq!(series[1].page_views, 200);
+        assert_eq!(series[2].visits, 0);
+    }
+
+    #[test]
+    fn test_record_skips_weekly_points() {
+        let mut conn = memory_db();
+        let today = date("2024-01-22");
+        record(&mut conn, &[site("a.com", &[("2024-01-15", 100, 200)])], DAY_BUCKET, today).unwrap();
+        // A 90d refresh labels the week of the 15th with that Monday
+        let (_, _, bucket) = time_range(&Settings { period: "90d".to_string(), ..Default::default() });
+        record(&mut conn, &[site("a.com", &[("2024-01-15", 700, 1400)])], bucket, today).unwrap();
+
+        let series = query(&conn, "a.com", date("2024-01-15"), date("2024-01-15"), DAY_BUCKET).unwrap();
+        assert_eq!((series[0].visits, series[0].page_views), (100, 200));
+    }
+
+    #[test]
+    fn test_record_prunes_rows_past_retention() {
+        let mut conn = memory_db();
+        let today = date("2024-06-01");
+        let old = (today - Duration::days(RETENTION_DAYS + 1)).format("%Y-%m-%d").to_string();
+        record(&mut conn, &[site("a.com", &[(&old, 5, 5), ("2024-05-31", 7, 7)])], DAY_BUCKET, today).unwrap();
+
+        let count: i64 = conn
+            .query_row("SELECT COUNT(*) FROM daily_totals", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(count, 1);
+    }
+
+    #[test]
+    fn test_query_filters_by_site() {
+        let mut conn = memory_db();
+        let today = date("2024-01-16");
+        record(
+            &mut conn,
+            &[
+                site("a.com", &[("2024-01-16", 1, 1)]),
+                site("b.com", &[("2024-01-16", 9, 9)]),
+            ],
+            DAY_BUCKET,
+            today,
+        )
+        .unwrap();
+        let series = query(&conn, "b.com", today, today, DAY_BUCKET).unwrap();
+        assert_eq!(series.len(), 1);
+        assert_eq!(series[0].visits, 9);
+    }
+
+    #[test]
+    fn test_parse_range_days() {
+        assert_eq!(parse_range_days("180d"), Some(180));
+        assert_eq!(parse_range_days("365d"), Some(365));
+        assert_eq!(parse_range_days("0d"), None);
+        assert_eq!(parse_range_days("12m"), None);
+        assert_eq!(parse_range_days("9999d"), None);
+    }
+}
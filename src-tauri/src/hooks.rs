@@ -0,0 +1,136 @@
+use crate::commands::{get_settings, SiteData};
+use std::process::{Output, Stdio};
+use std::time::Duration;
+use tauri::AppHandle;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use tracing::{info, warn};
+
+const HOOK_TIMEOUT: Duration = Duration::from_secs(30);
+
+fn shell_command(command: &str) -> Command {
+    #[cfg(windows)]
+    {
+        let mut cmd = Command::new("cmd");
+        cmd.arg("/C").arg(command);
+        cmd
+    }
+    #[cfg(not(windows))]
+    {
+        let mut cmd = Command::new("sh");
+        cmd.arg("-c").arg(command);
+        cmd
+    }
+}
+
+/// Run `command` through the shell with `input` on stdin, killing it after `timeout`.
+/// Stdin is written while the output is read, so a hook that echoes a payload larger
+/// than the pipe buffer can't block both sides.
+async fn run_command(command: &str, input: &[u8], timeout: Duration) -> Result<Output, String> {
+    let mut child = shell_command(command)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true)
+        .spawn()
+        .map_err(|e| e.to_string())?;
+
+    let stdin = child.stdin.take();
+    let write_input = async move {
+        if let Some(mut stdin) = stdin {
+            // a hook that doesn't read stdin closes the pipe early; that's fine
+            let _ = stdin.write_all(input).await;
+        }
+        // stdin is dropped here, so the hook sees the end of its input
+    };
+    let exchange = async move { tokio::join!(write_input, child.wait_with_output()).1 };
+
+    // Dropping the exchange on timeout drops the child, which kills it
+    match tokio::time::timeout(timeout, exchange).await {
+        Ok(result) => result.map_err(|e| e.to_string()),
+        Err(_) => Err(format!("timed out after {}s", timeout.as_secs())),
+    }
+}
+
+/// Pipe the refreshed analytics as JSON into the user's `refresh_hook` command, if set.
+pub async fn run_refresh_hook(app: &AppHandle, sites: &[SiteData]) {
+    let Ok(settings) = get_settings(app.clone()) else {
+        return;
+    };
+    let command = settings.refresh_hook.trim();
+    if command.is_empty() {
+        return;
+    }
+
+    let input = match serde_json::to_vec(sites) {
+        Ok(input) => input,
+        Err(e) => {
+            warn!(error = %e, "Refresh hook error");
+            return;
+        }
+    };
+
+    match run_command(command, &input, HOOK_TIMEOUT).await {
+        Ok(output) => {
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            if !output.status.success() {
+                warn!(status = %output.status, "Refresh hook failed");
+            }
+            if !stdout.trim().is_empty() {
+                info!(stdout = stdout.trim_end(), "Refresh hook output");
+            }
+            if !stderr.trim().is_empty() {
+                warn!(stderr = stderr.trim_end(), "Refresh hook output");
+            }
+        }
+        Err(e) => warn!(error = %e, "Refresh hook error"),
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_run_command_receives_stdin() {
+        let output = run_command("cat", br#"{"visits":1}"#, Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout, br#"{"visits":1}"#);
+    }
+
+    #[tokio::test]
+    async fn test_run_command_pipes_payload_larger_than_pipe_buffer() {
+        let input = vec![b'x'; 1024 * 1024];
+        let output = run_command("cat", &input, Duration::from_secs(5)).await.unwrap();
+        assert!(output.status.success());
+        assert_eq!(output.stdout.len(), input.len());
+    }
+
+    #[tokio::test]
+    async fn test_run_command_times_out_while_writing_stdin() {
+        let err = run_command("sleep 5", &vec![b'x'; 1024 * 1024], Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(err.contains("timed out"));
+    }
+
+    #[tokio::test]
+    async fn test_run_command_captures_failure() {
+        let output = run_command("echo oops >&2; exit 3", b"", Duration::from_secs(5))
+            .await
+            .unwrap();
+        assert_eq!(output.status.code(), Some(3));
+        assert_eq!(String::from_utf8_lossy(&output.stderr).trim(), "oops");
+    }
+
+    #[tokio::test]
+    async fn test_run_command_times_out() {
+        let err = run_command("sleep 5", b"", Duration::from_millis(100))
+            .await
+            .unwrap_err();
+        assert!(err.contains("timed out"));
+    }
+}
@@ -0,0 +1,141 @@
+//! Persists alert state (current threshold states, last script decisions, open
+//! rule incidents and when each alert last fired) so a restart doesn't re-fire alerts that
+//! already went out. Writes go to a temp file that is fsynced and renamed over
+//! the journal, so a crash never leaves a half-written file behind.
+
+use crate::alerts::{AlertRuleState, RuleState};
+use crate::notifications::{NotificationState, TrafficState};
+use crate::scripts::{AlertDecision, ScriptState};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+/// When each alert (keyed e.g. `threshold:example.com`) last sent a notification.
+pub struct LastFired(pub Mutex<HashMap<String, DateTime<Utc>>>);
+
+#[derive(Serialize, Deserialize, Default, Debug, PartialEq)]
+struct Journal {
+    #[serde(default)]
+    thresholds: HashMap<String, TrafficState>,
+    #[serde(default)]
+    scripts: HashMap<String, Vec<AlertDecision>>,
+    #[serde(default)]
+    last_fired: HashMap<String, DateTime<Utc>>,
+    #[serde(default)]
+    rules: HashMap<String, RuleState>,
+}
+
+fn journal_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join("alert_state.json"))
+}
+
+fn read(path: &Path) -> Journal {
+    let Ok(data) = fs::read_to_string(path) else {
+        return Journal::default();
+    };
+    serde_json::from_str(&data).unwrap_or_else(|e| {
+        warn!(error = %e, "Ignoring unreadable alert journal");
+        Journal::default()
+    })
+}
+
+/// Write `value` as JSON via a temp file that is fsynced and renamed over `path`.
+pub(crate) fn write_atomic(path: &Path, value: &impl Serialize) -> std::io::Result<()> {
+    let data = serde_json::to_vec_pretty(value)?;
+    let tmp = path.with_extension("json.tmp");
+    {
+        let mut file = fs::File::create(&tmp)?;
+        file.write_all(&data)?;
+        file.sync_all()?;
+    }
+    fs::rename(&tmp, path)
+}
+
+/// Load the journal into managed state; call once during setup.
+pub fn restore(app: &AppHandle) {
+    let journal = match journal_path(app) {
+        Ok(path) => read(&path),
+        Err(e) => {
+            warn!(error = %e, "Error locating alert journal");
+            Journal::default()
+        }
+    };
+    *app.state::<NotificationState>().0.lock().unwrap() = journal.thresholds;
+    *app.state::<ScriptState>().0.lock().unwrap() = journal.scripts;
+    *app.state::<LastFired>().0.lock().unwrap() = journal.last_fired;
+    *app.state::<AlertRuleState>().0.lock().unwrap() = journal.rules;
+}
+
+/// Write the current alert state to disk.
+pub fn persist(app: &AppHandle) {
+    let journal = Journal {
+        thresholds: app.state::<NotificationState>().0.lock().unwrap().clone(),
+        scripts: app.state::<ScriptState>().0.lock().unwrap().clone(),
+        last_fired: app.state::<LastFired>().0.lock().unwrap().clone(),
+        rules: app.state::<AlertRuleState>().0.lock().unwrap().clone(),
+    };
+    if let Err(e) = journal_path(app).and_then(|path| write_atomic(&path, &journal).map_err(|e| e.to_string())) {
+        warn!(error = %e, "Error writing alert journal");
+    }
+}
+
+pub fn record_fired(app: &AppHandle, key: &str) {
+    app.state::<LastFired>()
+        .0
+        .lock()
+        .unwrap()
+        .insert(key.to_string(), Utc::now());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn temp_path(name: &str) -> PathBuf {
+        crate::test_support::temp_dir("journal", name).join("alert_state.json")
+    }
+
+    #[test]
+    fn test_write_and_read_round_trip() {
+        let path = temp_path("roundtrip");
+        let journal = Journal {
+            thresholds: HashMap::from([("a.com".to_string(), TrafficState::Spike)]),
+            scripts: HashMap::from([(
+                "quiet".to_string(),
+                vec![AlertDecision {
+                    site: "b.com".to_string(),
+                    message: "no traffic".to_string(),
+                }],
+            )]),
+            last_fired: HashMap::from([(
+                "threshold:a.com".to_string(),
+                DateTime::parse_from_rfc3339("2024-01-15T10:00:00Z").unwrap().to_utc(),
+            )]),
+            rules: HashMap::from([("r1".to_string(), RuleState { firing: true, notified: true })]),
+        };
+        write_atomic(&path, &journal).unwrap();
+        assert_eq!(read(&path), journal);
+        assert!(!path.with_extension("json.tmp").exists());
+    }
+
+    #[test]
+    fn test_read_missing_file_is_empty() {
+        let path = temp_path("missing").with_file_name("does-not-exist.json");
+        assert_eq!(read(&path), Journal::default());
+    }
+
+    #[test]
+    fn test_read_corrupt_file_is_empty() {
+        let path = temp_path("corrupt");
+        fs::write(&path, "{ truncated").unwrap();
+        assert_eq!(read(&path), Journal::default());
+    }
+}
@@ -0,0 +1,85 @@
+//! The last successful fetch, saved to `last_fetch.json` after every refresh so the
+//! panel has sites to show right after a restart instead of a loading state.
+
+use crate::commands::{cache_key, get_settings, AnalyticsSnapshot, Snapshot};
+use crate::errors::FetchError;
+use crate::journal::write_atomic;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tracing::warn;
+
+fn saved_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join("last_fetch.json"))
+}
+
+/// The snapshot at `path`, if there is one and it was fetched with settings matching `key`.
+fn read(path: &Path, key: &str) -> Option<Snapshot> {
+    let data = fs::read_to_string(path).ok()?;
+    let snapshot: Snapshot = serde_json::from_str(&data)
+        .inspect_err(|e| warn!(error = %e, "Ignoring unreadable saved fetch"))
+        .ok()?;
+    (snapshot.key == key).then_some(snapshot)
+}
+
+/// Write `snapshot` to disk, replacing the previous one.
+pub fn save(app: &AppHandle, snapshot: &Snapshot) {
+    if let Err(e) = saved_path(app).and_then(|path| write_atomic(&path, snapshot).map_err(|e| e.to_string())) {
+        warn!(error = %e, "Error saving last fetch");
+    }
+}
+
+/// The last fetch saved to disk, for the panel to show (marked stale) while a fresh
+/// one runs. `None` if nothing was saved or it was fetched with different settings.
+#[tauri::command]
+pub fn get_cached_analytics(app: AppHandle) -> Result<Option<AnalyticsSnapshot>, FetchError> {
+    let settings = get_settings(app.clone())?;
+    let snapshot = read(&saved_path(&app)?, &cache_key(&settings));
+    Ok(snapshot.map(|s| s.into_analytics(&settings)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::SiteData;
+    use chrono::DateTime;
+
+    fn temp_path(name: &str) -> PathBuf {
+        crate::test_support::temp_dir("lastfetch", name).join("last_fetch.json")
+    }
+
+    fn snapshot(key: &str) -> Snapshot {
+        Snapshot {
+            sites: vec![SiteData { name: "a.com".to_string(), visits: 42, ..Default::default() }],
+            fetched_at: DateTime::parse_from_rfc3339("2024-01-15T10:00:00Z").unwrap().to_utc(),
+            site_fetched_at: Default::default(),
+            key: key.to_string(),
+            period: "7d".to_string(),
+            partial: true,
+        }
+    }
+
+    #[test]
+    fn test_read_returns_saved_snapshot_for_same_settings() {
+        let path = temp_path("same");
+        write_atomic(&path, &snapshot("acc|7d|true|false")).unwrap();
+        let saved = read(&path, "acc|7d|true|false").unwrap();
+        assert_eq!(saved.sites[0].name, "a.com");
+        assert_eq!(saved.sites[0].visits, 42);
+        assert!(saved.partial);
+        assert_eq!(saved.fetched_at, DateTime::parse_from_rfc3339("2024-01-15T10:00:00Z").unwrap().to_utc());
+    }
+
+    #[test]
+    fn test_read_ignores_other_settings_and_garbage() {
+        let path = temp_path("other");
+        write_atomic(&path, &snapshot("acc|7d|true|false")).unwrap();
+        assert!(read(&path, "acc|30d|true|false").is_none());
+
+        fs::write(&path, "{not json").unwrap();
+        assert!(read(&path, "acc|7d|true|false").is_none());
+        assert!(read(&temp_path("missing").with_file_name("nothing.json"), "").is_none());
+    }
+}
@@ -1,12 +1,49 @@
+mod alerts;
+mod autostart;
+mod breakdowns;
+mod budget;
+mod cli;
+mod client;
 mod commands;
+mod connectivity;
+mod credentials;
+mod dashboard;
+mod deeplink;
+mod errors;
+mod export;
+mod history;
+mod hooks;
+mod journal;
+mod lastfetch;
+mod live;
+mod logging;
+mod lowdata;
+mod migrations;
+mod notifications;
+mod power;
+mod query;
+mod refresh;
+mod replay;
+mod scripts;
+mod security;
+mod selftest;
+mod shortcut;
+mod sites;
+#[cfg(test)]
+mod test_support;
+mod tray;
+mod updater;
+mod usage;
+mod vitals;
 
+use std::collections::HashMap;
 use std::sync::Mutex;
 use tauri::{
     Emitter, Manager, PhysicalPosition, PhysicalSize,
     image::Image,
-    menu::{Menu, MenuItem, PredefinedMenuItem},
     tray::{MouseButton, MouseButtonState, TrayIconBuilder, TrayIconEvent},
 };
+use tauri_plugin_deep_link::DeepLinkExt;
 
 #[cfg(target_os = "macos")]
 use tauri_nspanel::{tauri_panel, CollectionBehavior, ManagerExt, PanelLevel, StyleMask, WebviewWindowExt};
@@ -49,6 +86,7 @@ fn init_panel(app: &tauri::AppHandle) {
         if let Ok(panel) = handle.get_webview_panel("main") {
             panel.hide();
         }
+        refresh::panel_hidden(&handle);
     });
     panel.set_event_handler(Some(event_handler.as_ref()));
 }
@@ -68,6 +106,20 @@ fn show_panel(app: &tauri::AppHandle) {
     if let Ok(panel) = app.get_webview_panel("main") {
         panel.show_and_make_key();
     }
+    refresh::panel_shown(app);
+}
+
+/// Hide the panel if it's showing, otherwise show it below the tray icon.
+#[cfg(target_os = "macos")]
+fn toggle_panel(app: &tauri::AppHandle) {
+    if let Ok(panel) = app.get_webview_panel("main") {
+        if panel.is_visible() {
+            panel.hide();
+            refresh::panel_hidden(app);
+        } else {
+            show_panel(app);
+        }
+    }
 }
 
 fn store_tray_rect(app: &tauri::AppHandle, event: &TrayIconEvent) {
@@ -83,31 +135,142 @@ fn store_tray_rect(app: &tauri::AppHandle, event: &TrayIconEvent) {
     ));
 }
 
+/// `flarestats --print`: fetch once and exit, without the tray, the panel or any plugin.
+/// Runs as an accessory app like the main one, so frequent runs from a status bar don't
+/// flash a Dock icon.
+fn print_and_exit(mut context: tauri::Context<tauri::Wry>, options: cli::PrintOptions) -> ! {
+    context.config_mut().app.windows.clear();
+    let app = tauri::Builder::default()
+        .manage(budget::ApiBudget(Mutex::new(HashMap::new())))
+        .manage(client::SharedClient(Mutex::new(None)))
+        .setup(|_app| {
+            #[cfg(target_os = "macos")]
+            _app.set_activation_policy(tauri::ActivationPolicy::Accessory);
+            Ok(())
+        })
+        .build(context)
+        .expect("error while building tauri application");
+    std::process::exit(cli::run(app.handle(), options));
+}
+
 #[cfg_attr(mobile, tauri::mobile_entry_point)]
 pub fn run() {
+    let context = tauri::generate_context!();
+    match cli::parse_args(std::env::args().skip(1)) {
+        Ok(Some(options)) => print_and_exit(context, options),
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(2);
+        }
+    }
+
     tauri::Builder::default()
         .plugin(tauri_nspanel::init())
+        .plugin(tauri_plugin_notification::init())
+        .plugin(tauri_plugin_dialog::init())
+        .plugin(tauri_plugin_opener::init())
+        .plugin(tauri_plugin_global_shortcut::Builder::new().build())
+        .plugin(tauri_plugin_deep_link::init())
+        .plugin(tauri_plugin_autostart::init(
+            tauri_plugin_autostart::MacosLauncher::LaunchAgent,
+            None,
+        ))
+        .plugin(tauri_plugin_updater::Builder::new().build())
         .manage(TrayRect(Mutex::new(None)))
         .manage(commands::RefreshTask(Mutex::new(None)))
+        .manage(breakdowns::BreakdownCache(Mutex::new(HashMap::new())))
+        .manage(budget::ApiBudget(Mutex::new(HashMap::new())))
+        .manage(client::SharedClient(Mutex::new(None)))
+        .manage(commands::AnalyticsCache(Mutex::new(Default::default())))
+        .manage(connectivity::Offline(Mutex::new(false)))
+        .manage(history::HistoryDb(Mutex::new(None)))
+        .manage(refresh::RefreshState(Mutex::new(Default::default())))
+        .manage(refresh::PanelHidden(Mutex::new(Some(std::time::Instant::now()))))
+        .manage(refresh::SiteRefreshTimes(Mutex::new(HashMap::new())))
+        .manage(notifications::NotificationState(Mutex::new(HashMap::new())))
+        .manage(scripts::ScriptState(Mutex::new(HashMap::new())))
+        .manage(journal::LastFired(Mutex::new(HashMap::new())))
+        .manage(alerts::AlertRuleState(Mutex::new(HashMap::new())))
+        .manage(sites::SiteCache::default())
+        .manage(tray::TrayState(Mutex::new(None)))
+        .manage(tray::TrayHealth(Mutex::new(Default::default())))
+        .manage(tray::Sparkline(Mutex::new(None)))
+        .manage(updater::PendingUpdate(Mutex::new(None)))
+        .manage(updater::InstallMenuItem(Mutex::new(None)))
         .invoke_handler(tauri::generate_handler![
+            autostart::set_launch_at_login,
+            breakdowns::fetch_site_breakdown,
+            budget::get_api_usage,
             commands::get_settings,
+            commands::get_initial_payload,
             commands::save_settings,
             commands::fetch_analytics,
             commands::start_background_refresh,
+            commands::fetch_workers_analytics,
+            commands::preview_time_range,
+            commands::reveal_app_data,
+            commands::reveal_settings_file,
+            credentials::list_accounts,
+            credentials::verify_credentials,
+            dashboard::open_dashboard,
+            export::export_analytics,
+            history::get_history,
+            lastfetch::get_cached_analytics,
+            live::fetch_live_visitors,
+            logging::get_recent_logs,
+            logging::open_log_folder,
+            notifications::get_alert_thresholds,
+            notifications::set_alert_threshold,
+            alerts::get_alert_rules,
+            alerts::save_alert_rule,
+            alerts::delete_alert_rule,
+            query::query_snapshot,
+            refresh::pause_background_refresh,
+            refresh::refresh_now,
+            refresh::resume_background_refresh,
+            refresh::refresh_status,
+            replay::get_snapshot_at,
+            scripts::get_alert_scripts,
+            scripts::save_alert_script,
+            scripts::delete_alert_script,
+            scripts::test_alert_script,
+            security::fetch_security_events,
+            selftest::run_self_test,
+            shortcut::set_global_shortcut,
+            sites::list_sites,
+            sites::set_site_hidden,
+            sites::set_site_pinned,
+            updater::check_for_updates,
+            updater::install_update,
+            usage::record_usage,
+            usage::get_usage_insights,
+            vitals::fetch_web_vitals,
         ])
         .setup(|app| {
             #[cfg(target_os = "macos")]
             app.set_activation_policy(tauri::ActivationPolicy::Accessory);
 
-            let show = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
-            let settings = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
-            let separator = PredefinedMenuItem::separator(app)?;
-            let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
-            let menu = Menu::with_items(app, &[&show, &settings, &separator, &quit])?;
+            match logging::init(app.handle()) {
+                Ok(guard) => {
+                    app.manage(guard);
+                }
+                Err(e) => eprintln!("Error setting up logging: {}", e),
+            }
+            tracing::info!(version = %app.package_info().version, "FlareStats starting");
+
+            journal::restore(app.handle());
+            autostart::sync(app.handle());
+            shortcut::restore(app.handle());
+            lowdata::start_monitor();
+            updater::start(app.handle());
+            power::start(app.handle());
+
+            let menu = tray::build_menu(app.handle(), &[])?;
 
             let tray_icon = Image::from_bytes(include_bytes!("../icons/tray-icon.png"))?;
 
-            TrayIconBuilder::new()
+            let tray = TrayIconBuilder::new()
                 .icon(tray_icon)
                 .icon_as_template(true)
                 .tooltip("FlareStats")
@@ -116,12 +279,31 @@ pub fn run() {
                 .on_menu_event(|app, event| {
                     match event.id().as_ref() {
                         "show" => show_panel(app),
+                        "refresh_now" => {
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                let _ = refresh::refresh_now(app, Some(true)).await;
+                            });
+                        }
                         "settings" => {
                             show_panel(app);
                             let _ = app.emit("open-settings", ());
                         }
+                        "install_update" => {
+                            let app = app.clone();
+                            tauri::async_runtime::spawn(async move {
+                                if let Err(e) = updater::install(&app).await {
+                                    tracing::error!("{}", e);
+                                    let _ = app.emit("update-error", e);
+                                }
+                            });
+                        }
                         "quit" => app.exit(0),
-                        _ => {}
+                        id => {
+                            if let Some(site) = tray::menu_site(id) {
+                                deeplink::open_site(app, site);
+                            }
+                        }
                     }
                 })
                 .on_tray_icon_event(|tray, event| {
@@ -134,18 +316,29 @@ pub fn run() {
                         ..
                     } = event
                     {
-                        if let Ok(panel) = app.get_webview_panel("main") {
-                            if panel.is_visible() { panel.hide(); } else { show_panel(app); }
-                        }
+                        toggle_panel(app);
                     }
                 })
                 .build(app)?;
+            *app.state::<tray::TrayState>().0.lock().unwrap() = Some(tray);
 
             #[cfg(target_os = "macos")]
             init_panel(app.handle());
 
+            let handle = app.handle().clone();
+            app.deep_link().on_open_url(move |event| {
+                for url in event.urls() {
+                    deeplink::handle(&handle, url.as_str());
+                }
+            });
+            if let Ok(Some(urls)) = app.deep_link().get_current() {
+                for url in urls {
+                    deeplink::handle(app.handle(), url.as_str());
+                }
+            }
+
             Ok(())
         })
-        .run(tauri::generate_context!())
+        .run(context)
         .expect("error while running tauri application");
 }
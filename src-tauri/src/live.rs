@@ -0,0 +1,106 @@
+use crate::budget::metered_client;
+use crate::client::CloudflareClient;
+use crate::commands::{fetch_recent_visits, get_settings, Settings};
+use crate::errors::FetchError;
+use crate::sites::{cached_sites, Site};
+use serde::Serialize;
+use tauri::AppHandle;
+use tracing::warn;
+
+/// Window counted as "online now".
+const LIVE_WINDOW_MINUTES: i64 = 5;
+
+#[derive(Serialize, Clone, Debug)]
+pub struct LiveVisitors {
+    pub name: String,
+    pub visits: u64,
+}
+
+/// Visits per site over the last few minutes. Cheap enough to poll more often than
+/// the main refresh: one small query per site, reusing the cached site list.
+#[tauri::command]
+pub async fn fetch_live_visitors(app: AppHandle) -> Result<Vec<LiveVisitors>, FetchError> {
+    let settings = get_settings(app.clone())?;
+    if !settings.has_credentials() {
+        return Err(FetchError::missing_credentials());
+    }
+    if !crate::lowdata::query_plan(&settings).allows_live() {
+        return Ok(Vec::new());
+    }
+
+    let client = metered_client(&app, &settings)?;
+    let sites = cached_sites(&app, &client, &settings).await?;
+    Ok(live_visitors(&client, &settings, sites).await)
+}
+
+/// Recent visits of each of `sites`, leaving out (and logging) those whose query failed.
+async fn live_visitors(client: &impl CloudflareClient, settings: &Settings, sites: Vec<Site>) -> Vec<LiveVisitors> {
+    let futures = sites.into_iter().map(|site| {
+        async move {
+            let visits = fetch_recent_visits(
+                client,
+                settings,
+                &site.site_tag,
+                chrono::Duration::minutes(LIVE_WINDOW_MINUTES),
+            )
+            .await
+            .map_err(|e| format!("{}: {}", site.name, e))?;
+            Ok::<_, String>(LiveVisitors { name: site.name, visits })
+        }
+    });
+
+    let mut live = Vec::new();
+    for result in futures::future::join_all(futures).await {
+        match result {
+            Ok(v) => live.push(v),
+            Err(e) => warn!(error = %e, "Error fetching live visitors"),
+        }
+    }
+    live
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::client::mock::{json, MockClient};
+
+    fn site(name: &str) -> Site {
+        Site { name: name.to_string(), site_tag: format!("tag-{}", name) }
+    }
+
+    fn settings() -> Settings {
+        Settings { token: "token".to_string(), account_id: "acc".to_string(), ..Default::default() }
+    }
+
+    #[tokio::test]
+    async fn test_live_visitors_per_site() {
+        let client = MockClient::new(|req| {
+            let visits = if req.variables().contains("tag-a.com") { 3 } else { 0 };
+            json(200, serde_json::json!({"data": {"viewer": {"accounts": [{
+                "totals": [{"count": visits * 2, "sum": {"visits": visits}}]
+            }]}}}))
+        });
+
+        let live = live_visitors(&client, &settings(), vec![site("a.com"), site("b.com")]).await;
+
+        let visits: Vec<_> = live.iter().map(|l| (l.name.as_str(), l.visits)).collect();
+        assert_eq!(visits, vec![("a.com", 3), ("b.com", 0)]);
+        assert_eq!(client.requests().len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_live_visitors_leaves_out_failed_sites() {
+        let client = MockClient::new(|req| {
+            if req.variables().contains("tag-broken.com") {
+                return json(500, serde_json::json!({"errors": [{"message": "boom"}]}));
+            }
+            json(200, serde_json::json!({"data": {"viewer": {"accounts": [{
+                "totals": [{"count": 1, "sum": {"visits": 1}}]
+            }]}}}))
+        });
+
+        let live = live_visitors(&client, &settings(), vec![site("broken.com"), site("ok.com")]).await;
+
+        assert_eq!(live.iter().map(|l| l.name.as_str()).collect::<Vec<_>>(), vec!["ok.com"]);
+    }
+}
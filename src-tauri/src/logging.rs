@@ -0,0 +1,145 @@
+//! Structured logs, written to daily-rotated files in `logs/` under the app data folder
+//! so users can attach them to bug reports.
+
+use crate::errors::FetchError;
+use reqwest::header::HeaderMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use tauri::{AppHandle, Manager};
+use tauri_plugin_opener::OpenerExt;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_appender::rolling::{RollingFileAppender, Rotation};
+use tracing_subscriber::prelude::*;
+use tracing_subscriber::{fmt, EnvFilter};
+
+const LOG_PREFIX: &str = "flarestats";
+const LOG_SUFFIX: &str = "log";
+/// Daily files kept before the oldest is deleted.
+const MAX_LOG_FILES: usize = 7;
+/// Used unless `RUST_LOG` is set. API requests and response bodies are logged at debug
+/// level, so they only reach the files with e.g. `RUST_LOG=flarestats_lib=debug`.
+const DEFAULT_FILTER: &str = "info";
+const DEFAULT_RECENT_LINES: usize = 500;
+
+/// Keeps the background log writer alive; dropping it flushes and stops file logging.
+pub struct LogGuard(pub WorkerGuard);
+
+fn log_dir(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app
+        .path()
+        .app_data_dir()
+        .map_err(|e| e.to_string())?
+        .join("logs");
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir)
+}
+
+/// Install the global subscriber, writing to the log folder and to stderr.
+pub fn init(app: &AppHandle) -> Result<LogGuard, String> {
+    let appender = RollingFileAppender::builder()
+        .rotation(Rotation::DAILY)
+        .filename_prefix(LOG_PREFIX)
+        .filename_suffix(LOG_SUFFIX)
+        .max_log_files(MAX_LOG_FILES)
+        .build(log_dir(app)?)
+        .map_err(|e| e.to_string())?;
+    let (writer, guard) = tracing_appender::non_blocking(appender);
+    let filter = EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new(DEFAULT_FILTER));
+
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer().with_writer(writer).with_ansi(false))
+        .with(fmt::layer().with_writer(std::io::stderr))
+        .try_init()
+        .map_err(|e| e.to_string())?;
+    Ok(LogGuard(guard))
+}
+
+/// Format request headers for the log, hiding credentials.
+pub(crate) fn redacted_headers(headers: &HeaderMap) -> String {
+    headers
+        .iter()
+        .map(|(name, value)| {
+            let value = match name.as_str() {
+                "authorization" | "x-auth-key" => "[redacted]",
+                _ => value.to_str().unwrap_or("[binary]"),
+            };
+            format!("{}: {}", name, value)
+        })
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// The last `limit` lines across the log files in `dir`, oldest first.
+fn recent_lines(dir: &Path, limit: usize) -> std::io::Result<Vec<String>> {
+    let mut files: Vec<PathBuf> = fs::read_dir(dir)?
+        .filter_map(|entry| entry.ok().map(|e| e.path()))
+        .filter(|path| {
+            path.file_name()
+                .and_then(|n| n.to_str())
+                .is_some_and(|n| n.starts_with(LOG_PREFIX) && n.ends_with(LOG_SUFFIX))
+        })
+        .collect();
+    // File names end in the date, so newest sorts last
+    files.sort();
+
+    let mut lines = Vec::new();
+    for path in files.iter().rev() {
+        let content = fs::read_to_string(path)?;
+        let mut file_lines: Vec<String> = content.lines().map(String::from).collect();
+        file_lines.append(&mut lines);
+        lines = file_lines;
+        if lines.len() >= limit {
+            break;
+        }
+    }
+    let skip = lines.len().saturating_sub(limit);
+    Ok(lines.split_off(skip))
+}
+
+#[tauri::command]
+pub fn get_recent_logs(app: AppHandle, lines: Option<usize>) -> Result<Vec<String>, FetchError> {
+    recent_lines(&log_dir(&app)?, lines.unwrap_or(DEFAULT_RECENT_LINES)).map_err(|e| e.to_string().into())
+}
+
+#[tauri::command]
+pub fn open_log_folder(app: AppHandle) -> Result<(), FetchError> {
+    let dir = log_dir(&app)?;
+    app.opener()
+        .open_path(dir.to_string_lossy(), None::<&str>)
+        .map_err(|e| e.to_string().into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::temp_dir;
+    use reqwest::header::{HeaderValue, AUTHORIZATION, CONTENT_TYPE};
+
+    #[test]
+    fn test_redacted_headers_hide_token() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Bearer secret-token"));
+        headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+        let logged = redacted_headers(&headers);
+        assert!(!logged.contains("secret-token"));
+        assert!(logged.contains("authorization: [redacted]"));
+        assert!(logged.contains("content-type: application/json"));
+    }
+
+    #[test]
+    fn test_recent_lines_span_files_newest_last() {
+        let dir = temp_dir("logs", "span");
+        fs::write(dir.join("flarestats.2024-01-14.log"), "a\nb\nc\n").unwrap();
+        fs::write(dir.join("flarestats.2024-01-15.log"), "d\ne\n").unwrap();
+        fs::write(dir.join("other.txt"), "ignored\n").unwrap();
+        assert_eq!(recent_lines(&dir, 3).unwrap(), vec!["c", "d", "e"]);
+        assert_eq!(recent_lines(&dir, 10).unwrap(), vec!["a", "b", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn test_recent_lines_empty_dir() {
+        let dir = temp_dir("logs", "empty");
+        assert!(recent_lines(&dir, 10).unwrap().is_empty());
+    }
+}
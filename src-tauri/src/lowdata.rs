@@ -0,0 +1,123 @@
+//! Low-data mode: fetch totals only and refresh less often, when turned on or, with "auto",
+//! while macOS reports the network as constrained (Low Data Mode).
+
+use crate::commands::Settings;
+use std::time::Duration;
+
+/// Background refreshes in low-data mode run at most this often.
+const LOW_DATA_MIN_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// What a fetch asks Cloudflare for.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum QueryPlan {
+    /// Totals, previous-period totals and the chart series.
+    Full,
+    /// Totals only: no series, no live polling, and a longer background interval.
+    TotalsOnly,
+}
+
+impl QueryPlan {
+    pub fn includes_series(self) -> bool {
+        self == QueryPlan::Full
+    }
+
+    pub fn allows_live(self) -> bool {
+        self == QueryPlan::Full
+    }
+
+    pub fn refresh_interval(self, configured: Duration) -> Duration {
+        match self {
+            QueryPlan::Full => configured,
+            QueryPlan::TotalsOnly => configured.max(LOW_DATA_MIN_INTERVAL),
+        }
+    }
+}
+
+/// `mode` is the `low_data_mode` setting: "on", "off", or "auto" to follow the system.
+fn plan_for(mode: &str, constrained: bool) -> QueryPlan {
+    match mode {
+        "on" => QueryPlan::TotalsOnly,
+        "auto" if constrained => QueryPlan::TotalsOnly,
+        _ => QueryPlan::Full,
+    }
+}
+
+pub fn query_plan(settings: &Settings) -> QueryPlan {
+    plan_for(&settings.low_data_mode, network_constrained())
+}
+
+#[cfg(target_os = "macos")]
+mod monitor {
+    use block2::RcBlock;
+    use std::ffi::c_void;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    pub static CONSTRAINED: AtomicBool = AtomicBool::new(false);
+
+    #[link(name = "Network", kind = "framework")]
+    unsafe extern "C" {
+        fn nw_path_monitor_create() -> *mut c_void;
+        fn nw_path_monitor_set_queue(monitor: *mut c_void, queue: *mut c_void);
+        fn nw_path_monitor_set_update_handler(monitor: *mut c_void, handler: &block2::Block<dyn Fn(*mut c_void)>);
+        fn nw_path_monitor_start(monitor: *mut c_void);
+        fn nw_path_is_constrained(path: *mut c_void) -> bool;
+    }
+
+    unsafe extern "C" {
+        fn dispatch_get_global_queue(identifier: isize, flags: usize) -> *mut c_void;
+    }
+
+    /// Watch the system's Low Data Mode flag for the current network path.
+    /// The monitor is never cancelled; it lives as long as the app.
+    pub fn start() {
+        let handler = RcBlock::new(|path: *mut c_void| {
+            let constrained = unsafe { nw_path_is_constrained(path) };
+            CONSTRAINED.store(constrained, Ordering::Relaxed);
+        });
+        unsafe {
+            let monitor = nw_path_monitor_create();
+            nw_path_monitor_set_queue(monitor, dispatch_get_global_queue(0, 0));
+            nw_path_monitor_set_update_handler(monitor, &handler);
+            nw_path_monitor_start(monitor);
+        }
+    }
+}
+
+/// Start following the system's constrained-network (Low Data Mode) flag.
+pub fn start_monitor() {
+    #[cfg(target_os = "macos")]
+    monitor::start();
+}
+
+#[cfg(target_os = "macos")]
+fn network_constrained() -> bool {
+    monitor::CONSTRAINED.load(std::sync::atomic::Ordering::Relaxed)
+}
+
+#[cfg(not(target_os = "macos"))]
+fn network_constrained() -> bool {
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_for_modes() {
+        assert_eq!(plan_for("on", false), QueryPlan::TotalsOnly);
+        assert_eq!(plan_for("off", true), QueryPlan::Full);
+        assert_eq!(plan_for("auto", true), QueryPlan::TotalsOnly);
+        assert_eq!(plan_for("auto", false), QueryPlan::Full);
+        assert_eq!(plan_for("", true), QueryPlan::Full);
+    }
+
+    #[test]
+    fn test_refresh_interval_stretched_in_low_data() {
+        let five_min = Duration::from_secs(300);
+        assert_eq!(QueryPlan::Full.refresh_interval(five_min), five_min);
+        assert_eq!(QueryPlan::TotalsOnly.refresh_interval(five_min), LOW_DATA_MIN_INTERVAL);
+        let two_hours = Duration::from_secs(7200);
+        assert_eq!(QueryPlan::TotalsOnly.refresh_interval(two_hours), two_hours);
+    }
+}
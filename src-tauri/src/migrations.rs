@@ -0,0 +1,139 @@
+use serde_json::{Map, Value};
+
+/// Version written by this build. Bump it and append to `MIGRATIONS` whenever the
+/// shape of `settings.json` changes in a way `#[serde(default)]` can't absorb.
+pub const CURRENT_VERSION: u32 = 1;
+
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` upgrades a file from version `n` to `n + 1`.
+const MIGRATIONS: &[Migration] = &[v0_to_v1];
+
+/// Files written before versioning. The first releases only knew the 5m/15m/60m
+/// intervals and silently fell back to 15m for anything else, and very old files
+/// may lack `period`, which is now required.
+fn v0_to_v1(settings: &mut Map<String, Value>) {
+    for key in ["token", "account_id"] {
+        if !settings.get(key).is_some_and(Value::is_string) {
+            settings.insert(key.to_string(), Value::from(""));
+        }
+    }
+    if settings.get("period").and_then(Value::as_str).is_none_or(str::is_empty) {
+        settings.insert("period".to_string(), Value::from("24h"));
+    }
+    let interval_ok = settings
+        .get("refresh_interval")
+        .and_then(Value::as_str)
+        .is_some_and(|i| crate::commands::parse_interval(i).is_ok());
+    if !interval_ok {
+        settings.insert("refresh_interval".to_string(), Value::from("15m"));
+    }
+}
+
+/// Upgrade a parsed `settings.json` to `CURRENT_VERSION`, returning the version it
+/// was at. Files from a newer build are left as they are.
+pub fn migrate(settings: &mut Value) -> Result<u32, String> {
+    let map = settings
+        .as_object_mut()
+        .ok_or("settings.json is not a JSON object")?;
+    let from = map.get("schema_version").and_then(Value::as_u64).unwrap_or(0) as u32;
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(from as usize) {
+        migration(map);
+        map.insert("schema_version".to_string(), Value::from(version + 1));
+    }
+    Ok(from)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::Settings;
+    use serde_json::json;
+
+    fn migrate_to_settings(mut value: Value) -> (u32, Settings) {
+        let from = migrate(&mut value).unwrap();
+        (from, serde_json::from_value(value).unwrap())
+    }
+
+    #[test]
+    fn test_migrate_first_release_file() {
+        let (from, settings) = migrate_to_settings(json!({
+            "token": "t",
+            "account_id": "a",
+            "period": "7d",
+            "exclude_bots": false,
+            "theme": "dark",
+            "refresh_interval": "60m"
+        }));
+        assert_eq!(from, 0);
+        assert_eq!(settings.schema_version, CURRENT_VERSION);
+        assert_eq!(settings.token, "t");
+        assert_eq!(settings.period, "7d");
+        assert!(!settings.exclude_bots);
+        assert_eq!(settings.refresh_interval, "60m");
+        assert_eq!(settings.tray_title, "off");
+    }
+
+    #[test]
+    fn test_migrate_fills_missing_required_fields() {
+        let (_, settings) = migrate_to_settings(json!({"token": "t"}));
+        assert_eq!(settings.account_id, "");
+        assert_eq!(settings.period, "24h");
+        assert_eq!(settings.refresh_interval, "15m");
+    }
+
+    #[test]
+    fn test_migrate_replaces_interval_old_builds_ignored() {
+        let (_, settings) = migrate_to_settings(json!({
+            "token": "t", "account_id": "a", "period": "24h", "refresh_interval": "10 minutes"
+        }));
+        assert_eq!(settings.refresh_interval, "15m");
+    }
+
+    #[test]
+    fn test_migrate_unversioned_file_with_later_fields() {
+        let (from, settings) = migrate_to_settings(json!({
+            "token": "t",
+            "account_id": "a",
+            "period": "24h",
+            "refresh_interval": "1h30m",
+            "alert_thresholds": {"a.com": {"baseline": 100, "spike_percent": 50}},
+            "tray_title": "site",
+            "tray_site": "a.com",
+            "alert_scripts": [{"name": "s", "source": "true"}],
+            "cache_ttl_secs": 0
+        }));
+        assert_eq!(from, 0);
+        assert_eq!(settings.refresh_interval, "1h30m");
+        assert_eq!(settings.alert_thresholds["a.com"].baseline, 100);
+        assert_eq!(settings.tray_site, "a.com");
+        assert!(settings.alert_scripts[0].enabled);
+        assert_eq!(settings.cache_ttl_secs, 0);
+    }
+
+    #[test]
+    fn test_migrate_current_file_unchanged() {
+        let mut value = serde_json::to_value(Settings {
+            period: "30d".to_string(),
+            ..Default::default()
+        })
+        .unwrap();
+        let before = value.clone();
+        assert_eq!(migrate(&mut value).unwrap(), CURRENT_VERSION);
+        assert_eq!(value, before);
+    }
+
+    #[test]
+    fn test_migrate_leaves_newer_file_alone() {
+        let mut value = json!({"schema_version": CURRENT_VERSION + 1, "token": "t", "future": true});
+        let before = value.clone();
+        assert_eq!(migrate(&mut value).unwrap(), CURRENT_VERSION + 1);
+        assert_eq!(value, before);
+    }
+
+    #[test]
+    fn test_migrate_rejects_non_object() {
+        assert!(migrate(&mut json!([1, 2])).is_err());
+    }
+}
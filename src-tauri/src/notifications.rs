@@ -0,0 +1,214 @@
+use crate::budget::metered_client;
+use crate::commands::{fetch_recent_visits, fetch_sites, get_settings, save_settings, Settings};
+use crate::errors::FetchError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+use tauri_plugin_notification::NotificationExt;
+use tracing::warn;
+
+/// Traffic limits for a single site, compared against visits in the last hour.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AlertThreshold {
+    /// Expected number of visits per hour.
+    pub baseline: u64,
+    /// Notify when visits exceed the baseline by more than this percentage.
+    #[serde(default)]
+    pub spike_percent: Option<u32>,
+    /// Notify when visits fall below the baseline by more than this percentage.
+    #[serde(default)]
+    pub drop_percent: Option<u32>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum TrafficState {
+    Normal,
+    Spike,
+    Drop,
+}
+
+/// Last observed traffic state per site, so a notification only fires when
+/// a site crosses a threshold rather than on every refresh while it stays there.
+pub struct NotificationState(pub Mutex<HashMap<String, TrafficState>>);
+
+fn classify(visits: u64, threshold: &AlertThreshold) -> TrafficState {
+    let baseline = threshold.baseline as f64;
+    let visits = visits as f64;
+    if let Some(pct) = threshold.spike_percent
+        && visits > baseline * (1.0 + pct as f64 / 100.0)
+    {
+        return TrafficState::Spike;
+    }
+    if let Some(pct) = threshold.drop_percent
+        && visits < baseline * (1.0 - pct as f64 / 100.0)
+    {
+        return TrafficState::Drop;
+    }
+    TrafficState::Normal
+}
+
+fn notification_text(site: &str, state: TrafficState, visits: u64, baseline: u64) -> Option<(String, String)> {
+    match state {
+        TrafficState::Normal => None,
+        TrafficState::Spike => Some((
+            format!("Traffic spike on {}", site),
+            format!("{} visits in the last hour (baseline {})", visits, baseline),
+        )),
+        TrafficState::Drop => Some((
+            format!("Traffic drop on {}", site),
+            format!("{} visits in the last hour (baseline {})", visits, baseline),
+        )),
+    }
+}
+
+/// Show a native notification and record it in the alert journal under `key`.
+pub(crate) fn notify(app: &AppHandle, key: &str, title: &str, body: &str) {
+    if let Err(e) = app.notification().builder().title(title).body(body).show() {
+        warn!(error = %e, "Error showing notification");
+    }
+    crate::journal::record_fired(app, key);
+}
+
+/// Compare last-hour visits of every site with a configured threshold and
+/// send a native notification for sites that just started spiking or dropping.
+pub async fn check_thresholds(app: &AppHandle) {
+    let settings = match get_settings(app.clone()) {
+        Ok(s) => s,
+        Err(_) => return,
+    };
+    if settings.alert_thresholds.is_empty() {
+        return;
+    }
+
+    let client = match metered_client(app, &settings) {
+        Ok(client) => client,
+        Err(e) => {
+            warn!(error = %e, "Error building HTTP client for alerts");
+            return;
+        }
+    };
+    let sites = match fetch_sites(&client, &settings.credentials(), &settings.account_id).await {
+        Ok(sites) => sites,
+        Err(e) => {
+            warn!(error = %e, "Error fetching sites for alerts");
+            return;
+        }
+    };
+
+    let mut changed = false;
+    for (name, site_tag) in sites {
+        let Some(threshold) = settings.alert_thresholds.get(&name) else {
+            continue;
+        };
+        let visits = match fetch_recent_visits(
+            &client,
+            &settings,
+            &site_tag,
+            chrono::Duration::hours(1),
+        )
+        .await
+        {
+            Ok(v) => v,
+            Err(e) => {
+                warn!(site = %name, error = %e, "Error fetching recent visits");
+                continue;
+            }
+        };
+
+        let state = classify(visits, threshold);
+        let previous = app
+            .state::<NotificationState>()
+            .0
+            .lock()
+            .unwrap()
+            .insert(name.clone(), state);
+        if previous == Some(state) {
+            continue;
+        }
+        changed = true;
+
+        if let Some((title, body)) = notification_text(&name, state, visits, threshold.baseline) {
+            notify(app, &format!("threshold:{}", name), &title, &body);
+        }
+    }
+
+    if changed {
+        crate::journal::persist(app);
+    }
+}
+
+#[tauri::command]
+pub fn get_alert_thresholds(app: AppHandle) -> Result<HashMap<String, AlertThreshold>, FetchError> {
+    Ok(get_settings(app)?.alert_thresholds)
+}
+
+/// Set or clear (`threshold: null`) the alert threshold for a site.
+#[tauri::command]
+pub fn set_alert_threshold(
+    app: AppHandle,
+    site: String,
+    threshold: Option<AlertThreshold>,
+) -> Result<(), FetchError> {
+    let mut settings: Settings = get_settings(app.clone())?;
+    match threshold {
+        Some(t) => {
+            settings.alert_thresholds.insert(site.clone(), t);
+        }
+        None => {
+            settings.alert_thresholds.remove(&site);
+        }
+    }
+    save_settings(app.clone(), settings)?;
+    app.state::<NotificationState>().0.lock().unwrap().remove(&site);
+    crate::journal::persist(&app);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn threshold(baseline: u64, spike: Option<u32>, drop: Option<u32>) -> AlertThreshold {
+        AlertThreshold {
+            baseline,
+            spike_percent: spike,
+            drop_percent: drop,
+        }
+    }
+
+    #[test]
+    fn test_classify_spike_above_threshold() {
+        let t = threshold(100, Some(50), Some(50));
+        assert_eq!(classify(151, &t), TrafficState::Spike);
+        assert_eq!(classify(150, &t), TrafficState::Normal);
+    }
+
+    #[test]
+    fn test_classify_drop_below_threshold() {
+        let t = threshold(100, Some(50), Some(50));
+        assert_eq!(classify(49, &t), TrafficState::Drop);
+        assert_eq!(classify(50, &t), TrafficState::Normal);
+    }
+
+    #[test]
+    fn test_classify_ignores_unset_directions() {
+        let t = threshold(100, None, None);
+        assert_eq!(classify(0, &t), TrafficState::Normal);
+        assert_eq!(classify(10_000, &t), TrafficState::Normal);
+    }
+
+    #[test]
+    fn test_notification_text_only_for_alert_states() {
+        assert!(notification_text("a.com", TrafficState::Normal, 10, 10).is_none());
+        let (title, body) = notification_text("a.com", TrafficState::Drop, 3, 40).unwrap();
+        assert_eq!(title, "Traffic drop on a.com");
+        assert_eq!(body, "3 visits in the last hour (baseline 40)");
+    }
+
+    #[test]
+    fn test_alert_threshold_deserialize_optional_percentages() {
+        let t: AlertThreshold = serde_json::from_str(r#"{"baseline":120,"drop_percent":80}"#).unwrap();
+        assert_eq!(t, threshold(120, None, Some(80)));
+    }
+}
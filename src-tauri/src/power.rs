@@ -0,0 +1,119 @@
+//! System sleep/wake: the background loop is stopped when the machine sleeps and
+//! restarted with an immediate refresh when it wakes, so the panel isn't stale after a night.
+
+use crate::commands::start_background_refresh;
+use std::time::Duration;
+use tauri::AppHandle;
+use tracing::{info, warn};
+
+/// Give Wi-Fi a moment to reconnect after waking before fetching.
+const WAKE_SETTLE: Duration = Duration::from_secs(5);
+
+/// Stop the background loop so its timer doesn't fire while the network is still down.
+#[cfg(target_os = "macos")]
+fn on_sleep(app: &AppHandle) {
+    use tauri::Manager;
+
+    info!("System going to sleep");
+    if let Some(task) = app.state::<crate::commands::RefreshTask>().0.lock().unwrap().take() {
+        task.abort();
+    }
+}
+
+/// Restart the background loop and refresh right away, unless the user paused refreshes.
+fn on_wake(app: &AppHandle) {
+    info!("System woke from sleep");
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(WAKE_SETTLE).await;
+        if crate::refresh::is_paused(&app) {
+            return;
+        }
+        if let Err(e) = start_background_refresh(app.clone()).await {
+            warn!(error = %e, "Error restarting background refresh after wake");
+        }
+        if !crate::refresh::check_auto_pause(&app).await {
+            crate::refresh::run_now(&app).await;
+        }
+    });
+}
+
+#[cfg(target_os = "macos")]
+mod monitor {
+    use block2::RcBlock;
+    use objc2_app_kit::{NSWorkspace, NSWorkspaceDidWakeNotification, NSWorkspaceWillSleepNotification};
+    use objc2_foundation::NSNotification;
+    use std::ptr::NonNull;
+    use tauri::AppHandle;
+
+    /// Observe `NSWorkspace` sleep and wake notifications. The observers are never
+    /// removed; they live as long as the app.
+    pub fn start(app: &AppHandle) {
+        let center = NSWorkspace::sharedWorkspace().notificationCenter();
+        let names = unsafe { [(NSWorkspaceWillSleepNotification, false), (NSWorkspaceDidWakeNotification, true)] };
+        for (name, wake) in names {
+            let app = app.clone();
+            let block = RcBlock::new(move |_: NonNull<NSNotification>| {
+                if wake {
+                    super::on_wake(&app);
+                } else {
+                    super::on_sleep(&app);
+                }
+            });
+            let observer = unsafe { center.addObserverForName_object_queue_usingBlock(Some(name), None, None, &block) };
+            std::mem::forget(observer);
+        }
+    }
+}
+
+/// How often the wall clock is sampled on platforms without a wake notification.
+#[cfg(not(target_os = "macos"))]
+const CLOCK_TICK: Duration = Duration::from_secs(30);
+/// A tick that took this much longer than scheduled means the machine was asleep.
+#[cfg(not(target_os = "macos"))]
+const SLEEP_GAP: Duration = Duration::from_secs(60);
+
+/// Whether a `CLOCK_TICK` sleep that took `wall_elapsed` on the wall clock spanned a suspend.
+#[cfg(not(target_os = "macos"))]
+fn slept_through(wall_elapsed: Duration) -> bool {
+    wall_elapsed > CLOCK_TICK + SLEEP_GAP
+}
+
+/// Timers don't run while suspended, so a jump in the wall clock between two ticks
+/// marks a wake. There is no sleep notification; the loop is simply restarted on wake.
+#[cfg(not(target_os = "macos"))]
+mod monitor {
+    use std::time::SystemTime;
+    use tauri::AppHandle;
+
+    pub fn start(app: &AppHandle) {
+        let app = app.clone();
+        tauri::async_runtime::spawn(async move {
+            loop {
+                let before = SystemTime::now();
+                tokio::time::sleep(super::CLOCK_TICK).await;
+                let elapsed = SystemTime::now().duration_since(before).unwrap_or_default();
+                if super::slept_through(elapsed) {
+                    super::on_wake(&app);
+                }
+            }
+        });
+    }
+}
+
+/// Start following system sleep and wake.
+pub fn start(app: &AppHandle) {
+    monitor::start(app);
+}
+
+#[cfg(all(test, not(target_os = "macos")))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_slept_through_needs_a_clear_gap() {
+        assert!(!slept_through(CLOCK_TICK));
+        assert!(!slept_through(CLOCK_TICK + Duration::from_secs(5)));
+        assert!(slept_through(Duration::from_secs(8 * 3600)));
+    }
+}
@@ -0,0 +1,257 @@
+use crate::commands::{AnalyticsCache, SiteData};
+use crate::errors::FetchError;
+use rusqlite::hooks::{AuthAction, AuthContext, Authorization};
+use rusqlite::types::ValueRef;
+use rusqlite::{params, Connection, ErrorCode, OpenFlags};
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+
+const MAX_ROWS: usize = 1000;
+/// Queries still running after this are interrupted, so a runaway recursive CTE or
+/// cross join can't keep a blocking thread busy.
+const QUERY_TIME_LIMIT: Duration = Duration::from_secs(2);
+/// SQLite VM instructions between checks of `QUERY_TIME_LIMIT`.
+const PROGRESS_STEPS: i32 = 10_000;
+
+/// Numbers the in-memory databases, so concurrent queries each get their own.
+static DATABASE_ID: AtomicU64 = AtomicU64::new(0);
+
+#[derive(Serialize, Debug)]
+pub struct QueryResult {
+    pub columns: Vec<String>,
+    pub rows: Vec<Vec<serde_json::Value>>,
+    /// True when the result was cut off at `MAX_ROWS`.
+    pub truncated: bool,
+}
+
+/// Load sites into a fresh in-memory database with `sites` and `series` tables. Returns
+/// the connection that wrote them, which keeps the database alive, and a read-only one
+/// to the same database for running queries on.
+fn load(sites: &[SiteData]) -> rusqlite::Result<(Connection, Connection)> {
+    let uri = format!("file:snapshot-{}?mode=memory&cache=shared", DATABASE_ID.fetch_add(1, Ordering::Relaxed));
+    let conn = Connection::open_with_flags(
+        &uri,
+        OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_URI,
+    )?;
+    conn.execute_batch(
+        "CREATE TABLE sites (
+             name TEXT PRIMARY KEY,
+             visits INTEGER NOT NULL,
+             page_views INTEGER NOT NULL,
+             prev_visits INTEGER NOT NULL,
+             prev_page_views INTEGER NOT NULL
+         );
+         CREATE TABLE series (
+             site TEXT NOT NULL,
+             timestamp TEXT NOT NULL,
+             visits INTEGER NOT NULL,
+             page_views INTEGER NOT NULL
+         );",
+    )?;
+    {
+        let mut site_stmt = conn.prepare("INSERT INTO sites VALUES (?1, ?2, ?3, ?4, ?5)")?;
+        let mut series_stmt = conn.prepare("INSERT INTO series VALUES (?1, ?2, ?3, ?4)")?;
+        for site in sites {
+            site_stmt.execute(params![
+                site.name,
+                site.visits,
+                site.page_views,
+                site.prev_visits,
+                site.prev_page_views
+            ])?;
+            for point in &site.series {
+                series_stmt.execute(params![site.name, point.timestamp, point.visits, point.page_views])?;
+            }
+        }
+    }
+    let reader = Connection::open_with_flags(&uri, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_URI)?;
+    reader.authorizer(Some(authorize));
+    Ok((conn, reader))
+}
+
+/// Allow reading the tables and calling functions; everything else, including `ATTACH`
+/// and `PRAGMA`, is refused when the statement is prepared.
+fn authorize(context: AuthContext<'_>) -> Authorization {
+    match context.action {
+        AuthAction::Select | AuthAction::Read { .. } | AuthAction::Function { .. } | AuthAction::Recursive => {
+            Authorization::Allow
+        }
+        _ => Authorization::Deny,
+    }
+}
+
+fn query_error(error: rusqlite::Error) -> String {
+    match error.sqlite_error_code() {
+        Some(ErrorCode::OperationInterrupted) => {
+            format!("Query stopped after {}s, try narrowing it down", QUERY_TIME_LIMIT.as_secs())
+        }
+        Some(ErrorCode::AuthorizationForStatementDenied) => "Only read-only queries are allowed".to_string(),
+        _ => error.to_string(),
+    }
+}
+
+fn to_json(value: ValueRef<'_>) -> serde_json::Value {
+    match value {
+        ValueRef::Null => serde_json::Value::Null,
+        ValueRef::Integer(i) => i.into(),
+        ValueRef::Real(f) => f.into(),
+        ValueRef::Text(t) => String::from_utf8_lossy(t).into(),
+        ValueRef::Blob(b) => format!("<{} bytes>", b.len()).into(),
+    }
+}
+
+fn run(conn: &Connection, sql: &str, time_limit: Duration) -> Result<QueryResult, String> {
+    let started = Instant::now();
+    conn.progress_handler(PROGRESS_STEPS, Some(move || started.elapsed() > time_limit));
+    let mut stmt = conn.prepare(sql).map_err(query_error)?;
+    if !stmt.readonly() {
+        return Err("Only read-only queries are allowed".to_string());
+    }
+    let columns: Vec<String> = stmt.column_names().iter().map(|c| c.to_string()).collect();
+    let mut rows = stmt.query([]).map_err(query_error)?;
+
+    let mut out = Vec::new();
+    let mut truncated = false;
+    while let Some(row) = rows.next().map_err(query_error)? {
+        if out.len() == MAX_ROWS {
+            truncated = true;
+            break;
+        }
+        let values = (0..columns.len())
+            .map(|i| row.get_ref(i).map(to_json))
+            .collect::<rusqlite::Result<Vec<_>>>()
+            .map_err(|e| e.to_string())?;
+        out.push(values);
+    }
+
+    Ok(QueryResult {
+        columns,
+        rows: out,
+        truncated,
+    })
+}
+
+/// Run an ad-hoc read-only SQL query against the latest fetched analytics,
+/// exposed as tables `sites(name, visits, page_views, prev_visits, prev_page_views)`
+/// and `series(site, timestamp, visits, page_views)`.
+#[tauri::command]
+pub async fn query_snapshot(app: AppHandle, sql: String) -> Result<QueryResult, FetchError> {
+    let sites = app
+        .state::<AnalyticsCache>()
+        .latest()
+        .map(|s| s.sites)
+        .unwrap_or_default();
+    let result = tokio::task::spawn_blocking(move || {
+        let (_writer, conn) = load(&sites).map_err(|e| e.to_string())?;
+        run(&conn, &sql, QUERY_TIME_LIMIT)
+    })
+    .await
+    .map_err(|e| e.to_string())?;
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::commands::SeriesPoint;
+
+    fn sites() -> Vec<SiteData> {
+        let point = |ts: &str, visits| SeriesPoint {
+            timestamp: ts.to_string(),
+            visits,
+            page_views: visits * 2,
+            ..Default::default()
+        };
+        vec![
+            SiteData {
+                name: "a.com".to_string(),
+                visits: 30,
+                page_views: 60,
+                series: vec![point("2024-01-15", 10), point("2024-01-16", 20)],
+                ..Default::default()
+            },
+            SiteData {
+                name: "b.com".to_string(),
+                visits: 5,
+                page_views: 10,
+                series: vec![point("2024-01-15", 5)],
+                ..Default::default()
+            },
+        ]
+    }
+
+    #[test]
+    fn test_run_aggregates_series() {
+        let (_writer, conn) = load(&sites()).unwrap();
+        let result = run(
+            &conn,
+            "SELECT timestamp, SUM(visits) AS v FROM series GROUP BY timestamp ORDER BY timestamp",
+            QUERY_TIME_LIMIT,
+        )
+        .unwrap();
+        assert_eq!(result.columns, vec!["timestamp", "v"]);
+        assert_eq!(result.rows.len(), 2);
+        assert_eq!(result.rows[0], vec![serde_json::json!("2024-01-15"), serde_json::json!(15)]);
+        assert!(!result.truncated);
+    }
+
+    #[test]
+    fn test_run_rejects_writes() {
+        let (_writer, conn) = load(&sites()).unwrap();
+        let err = run(&conn, "DELETE FROM sites", QUERY_TIME_LIMIT).unwrap_err();
+        assert!(err.contains("read-only"));
+    }
+
+    #[test]
+    fn test_run_reports_sql_errors() {
+        let (_writer, conn) = load(&sites()).unwrap();
+        assert!(run(&conn, "SELECT nope FROM sites", QUERY_TIME_LIMIT).is_err());
+    }
+
+    #[test]
+    fn test_run_truncates_large_results() {
+        let (_writer, conn) = load(&[]).unwrap();
+        let result = run(
+            &conn,
+            "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n LIMIT 1500) SELECT x FROM n",
+            QUERY_TIME_LIMIT,
+        )
+        .unwrap();
+        assert_eq!(result.rows.len(), MAX_ROWS);
+        assert!(result.truncated);
+    }
+
+    #[test]
+    fn test_run_rejects_attach_and_pragma() {
+        let (_writer, conn) = load(&sites()).unwrap();
+        let path = crate::test_support::temp_dir("query", "attach").join("other.db");
+        let attach = format!("ATTACH DATABASE '{}' AS other", path.display());
+        assert!(run(&conn, &attach, QUERY_TIME_LIMIT).unwrap_err().contains("read-only"));
+        assert!(!path.exists());
+        assert!(run(&conn, "PRAGMA table_info(sites)", QUERY_TIME_LIMIT).unwrap_err().contains("read-only"));
+        assert!(run(&conn, "SELECT * FROM pragma_table_info('sites')", QUERY_TIME_LIMIT).is_err());
+    }
+
+    #[test]
+    fn test_run_stops_runaway_queries() {
+        let (_writer, conn) = load(&[]).unwrap();
+        let err = run(
+            &conn,
+            "WITH RECURSIVE n(x) AS (SELECT 1 UNION ALL SELECT x + 1 FROM n) SELECT count(*) FROM n",
+            Duration::from_millis(50),
+        )
+        .unwrap_err();
+        assert!(err.contains("Query stopped"));
+    }
+
+    #[test]
+    fn test_loaded_databases_are_separate() {
+        let (_a, first) = load(&sites()).unwrap();
+        let (_b, second) = load(&[]).unwrap();
+        let count = |conn: &Connection| run(conn, "SELECT count(*) FROM sites", QUERY_TIME_LIMIT).unwrap().rows[0][0].clone();
+        assert_eq!(count(&first), serde_json::json!(2));
+        assert_eq!(count(&second), serde_json::json!(0));
+    }
+}
@@ -0,0 +1,391 @@
+//! Scheduling, pause/resume and status for the background refresh loop, plus automatic
+//! pausing while nobody is looking at the panel and nothing else needs fresh data.
+
+use crate::commands::{
+    fetch_analytics, get_settings, parse_interval, start_background_refresh, AnalyticsCache, AnalyticsSnapshot,
+    RefreshTask, Settings, SiteData,
+};
+use crate::errors::FetchError;
+use chrono::Utc;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Emitter, Manager};
+
+/// Skip background fetches once the panel has been hidden this long.
+const AUTO_PAUSE_HIDDEN: Duration = Duration::from_secs(60 * 60);
+/// Shorter threshold when running on battery.
+const AUTO_PAUSE_HIDDEN_ON_BATTERY: Duration = Duration::from_secs(10 * 60);
+/// Each tick is delayed by up to this fraction of the interval, so instances started
+/// together (a team's laptops after a meeting) don't query Cloudflare in lockstep.
+const JITTER: f64 = 0.1;
+/// Longest wait between attempts while refreshes keep failing, unless the interval is longer.
+const MAX_BACKOFF: Duration = Duration::from_secs(60 * 60);
+
+/// When the background loop fetches. Ticks fall on `baseline + n * interval`, however
+/// long each fetch took, so the cadence doesn't drift. After a failure the next attempt
+/// backs off exponentially instead; the first success starts a new cadence from there.
+pub struct Schedule {
+    baseline: Instant,
+    failures: u32,
+}
+
+impl Schedule {
+    pub fn new(now: Instant) -> Self {
+        Self { baseline: now, failures: 0 }
+    }
+
+    /// The next tick after `now`, with `jitter` (0..1) of up to `JITTER` of the interval added.
+    pub fn next_tick(&self, now: Instant, interval: Duration, jitter: f64) -> Instant {
+        let interval = interval.max(Duration::from_secs(1));
+        let jitter = interval.mul_f64(JITTER * jitter.clamp(0.0, 1.0));
+        if self.failures > 0 {
+            let backoff = interval.saturating_mul(1 << self.failures.min(16));
+            return now + backoff.min(MAX_BACKOFF.max(interval)) + jitter;
+        }
+        let elapsed = now.saturating_duration_since(self.baseline);
+        let ticks = (elapsed.as_millis() / interval.as_millis()) as u32 + 1;
+        self.baseline + interval * ticks + jitter
+    }
+
+    pub fn record(&mut self, ok: bool, now: Instant) {
+        if !ok {
+            self.failures += 1;
+        } else if std::mem::take(&mut self.failures) > 0 {
+            self.baseline = now;
+        }
+    }
+}
+
+/// When each site was last fetched, so sites with a longer
+/// `Settings::site_refresh_intervals` entry can sit out ticks.
+pub struct SiteRefreshTimes(pub Mutex<HashMap<String, Instant>>);
+
+pub fn record_site_fetches(app: &AppHandle, sites: &[SiteData]) {
+    let now = Instant::now();
+    let times = app.state::<SiteRefreshTimes>();
+    let mut times = times.0.lock().unwrap();
+    for site in sites {
+        times.insert(site.name.clone(), now);
+    }
+}
+
+/// `Settings::site_refresh_intervals` parsed; entries that don't parse are left out,
+/// `save_settings` rejects them anyway.
+pub fn site_intervals(settings: &Settings) -> HashMap<String, Duration> {
+    settings
+        .site_refresh_intervals
+        .iter()
+        .filter_map(|(site, interval)| Some((site.clone(), parse_interval(interval).ok()?)))
+        .collect()
+}
+
+/// Sites fetched less than their interval ago, which sit out this tick. Half a `tick` of
+/// slack keeps jitter from pushing a site a whole tick past its interval.
+fn not_due(
+    last: &HashMap<String, Instant>,
+    intervals: &HashMap<String, Duration>,
+    default: Duration,
+    tick: Duration,
+    now: Instant,
+) -> HashSet<String> {
+    last.iter()
+        .filter(|(site, at)| {
+            let interval = intervals.get(*site).copied().unwrap_or(default);
+            now.saturating_duration_since(**at) + tick / 2 < interval
+        })
+        .map(|(site, _)| site.clone())
+        .collect()
+}
+
+/// The sites the upcoming tick can skip, or `None` if none of the last fetched ones are due.
+pub fn sites_to_skip(
+    app: &AppHandle,
+    intervals: &HashMap<String, Duration>,
+    default: Duration,
+    tick: Duration,
+) -> Option<HashSet<String>> {
+    let skip = not_due(&app.state::<SiteRefreshTimes>().0.lock().unwrap(), intervals, default, tick, Instant::now());
+    let latest = app.state::<AnalyticsCache>().latest();
+    let all_skipped = latest.is_some_and(|s| !s.sites.is_empty() && s.sites.iter().all(|site| skip.contains(&site.name)));
+    (!all_skipped).then_some(skip)
+}
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct LastRun {
+    /// Unix milliseconds.
+    pub at: i64,
+    pub ok: bool,
+    pub error: Option<FetchError>,
+}
+
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct RefreshStatus {
+    pub running: bool,
+    /// Paused by the user; the loop isn't running.
+    pub paused: bool,
+    /// The loop is running but skipping fetches because the panel is hidden.
+    pub auto_paused: bool,
+    /// Unix milliseconds of the next scheduled tick.
+    pub next_run_at: Option<i64>,
+    pub last_run: Option<LastRun>,
+}
+
+pub struct RefreshState(pub Mutex<RefreshStatus>);
+
+/// When the panel was last hidden; `None` while it is visible.
+pub struct PanelHidden(pub Mutex<Option<Instant>>);
+
+/// Whether something other than the panel consumes background refreshes.
+fn needs_background(settings: &Settings) -> bool {
+    settings.tray_title != "off"
+        || settings.tray_sparkline
+        || settings.tray_menu_sites > 0
+        || !settings.alert_thresholds.is_empty()
+        || settings.alert_rules.iter().any(|r| r.enabled)
+        || settings.alert_scripts.iter().any(|s| s.enabled)
+        || !settings.refresh_hook.trim().is_empty()
+}
+
+fn should_auto_pause(needs_background: bool, hidden_for: Option<Duration>, on_battery: bool) -> bool {
+    if needs_background {
+        return false;
+    }
+    hidden_for.is_some_and(|d| d >= AUTO_PAUSE_HIDDEN || (on_battery && d >= AUTO_PAUSE_HIDDEN_ON_BATTERY))
+}
+
+#[cfg(target_os = "macos")]
+async fn on_battery() -> bool {
+    tokio::process::Command::new("pmset")
+        .args(["-g", "batt"])
+        .output()
+        .await
+        .map(|out| String::from_utf8_lossy(&out.stdout).contains("'Battery Power'"))
+        .unwrap_or(false)
+}
+
+#[cfg(not(target_os = "macos"))]
+async fn on_battery() -> bool {
+    false
+}
+
+pub fn is_paused(app: &AppHandle) -> bool {
+    app.state::<RefreshState>().0.lock().unwrap().paused
+}
+
+pub fn schedule_next(app: &AppHandle, interval: Duration) {
+    let next = Utc::now() + chrono::Duration::from_std(interval).unwrap_or_default();
+    app.state::<RefreshState>().0.lock().unwrap().next_run_at = Some(next.timestamp_millis());
+}
+
+pub fn record_run(app: &AppHandle, result: Result<(), &FetchError>) {
+    if let Err(e) = result {
+        crate::tray::report_failure(app, e);
+    }
+    app.state::<RefreshState>().0.lock().unwrap().last_run = Some(LastRun {
+        at: Utc::now().timestamp_millis(),
+        ok: result.is_ok(),
+        error: result.err().cloned(),
+    });
+}
+
+/// Decide whether the upcoming background tick should be skipped, updating `auto_paused`.
+pub async fn check_auto_pause(app: &AppHandle) -> bool {
+    let hidden_for = app.state::<PanelHidden>().0.lock().unwrap().map(|since| since.elapsed());
+    let needs_background = get_settings(app.clone()).map(|s| needs_background(&s)).unwrap_or(true);
+    let battery = hidden_for.is_some() && !needs_background && on_battery().await;
+    let pause = should_auto_pause(needs_background, hidden_for, battery);
+    app.state::<RefreshState>().0.lock().unwrap().auto_paused = pause;
+    pause
+}
+
+pub fn panel_hidden(app: &AppHandle) {
+    // The dashboard window keeps showing refreshes while it's open
+    if crate::dashboard::is_open(app) {
+        return;
+    }
+    app.state::<PanelHidden>().0.lock().unwrap().get_or_insert_with(Instant::now);
+}
+
+/// Fetch (from the cache unless `force`), recording and announcing the result like a
+/// scheduled run.
+async fn fetch_and_announce(app: &AppHandle, force: bool) -> Result<AnalyticsSnapshot, FetchError> {
+    let result = fetch_analytics(app.clone(), Some(force)).await;
+    match &result {
+        Ok(data) => {
+            record_run(app, Ok(()));
+            let _ = app.emit("analytics-refreshed", data);
+        }
+        Err(e) => {
+            record_run(app, Err(e));
+            crate::errors::emit_error(app, None, e);
+        }
+    }
+    result
+}
+
+/// Fetch outside the background loop's schedule, recording and announcing the result
+/// like a scheduled run.
+pub async fn run_now(app: &AppHandle) {
+    let _ = fetch_and_announce(app, true).await;
+}
+
+/// Fetch right away and restart the background timer, so the next scheduled run is
+/// a full interval after this one rather than whatever was left of the old one.
+#[tauri::command]
+pub async fn refresh_now(app: AppHandle, force: Option<bool>) -> Result<AnalyticsSnapshot, FetchError> {
+    start_background_refresh(app.clone()).await?;
+    fetch_and_announce(&app, force.unwrap_or(false)).await
+}
+
+/// Mark the panel visible and, if refreshes were auto-paused, catch up immediately.
+pub fn panel_shown(app: &AppHandle) {
+    *app.state::<PanelHidden>().0.lock().unwrap() = None;
+    let was_paused = std::mem::take(&mut app.state::<RefreshState>().0.lock().unwrap().auto_paused);
+    if !was_paused {
+        return;
+    }
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move { run_now(&app).await });
+}
+
+#[tauri::command]
+pub fn pause_background_refresh(app: AppHandle) -> Result<(), FetchError> {
+    {
+        let state = app.state::<RefreshState>();
+        let mut status = state.0.lock().map_err(|e| e.to_string())?;
+        status.paused = true;
+        status.next_run_at = None;
+    }
+    if let Some(task) = app.state::<RefreshTask>().0.lock().map_err(|e| e.to_string())?.take() {
+        task.abort();
+    }
+    Ok(())
+}
+
+#[tauri::command]
+pub async fn resume_background_refresh(app: AppHandle) -> Result<(), FetchError> {
+    app.state::<RefreshState>().0.lock().map_err(|e| e.to_string())?.paused = false;
+    start_background_refresh(app).await
+}
+
+#[tauri::command]
+pub fn refresh_status(app: AppHandle) -> Result<RefreshStatus, FetchError> {
+    let running = app
+        .state::<RefreshTask>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .as_ref()
+        .is_some_and(|task| !task.is_finished());
+    let mut status = app.state::<RefreshState>().0.lock().map_err(|e| e.to_string())?.clone();
+    status.running = running;
+    Ok(status)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIN: Duration = Duration::from_secs(60);
+
+    #[test]
+    fn test_not_due_follows_per_site_intervals() {
+        let now = Instant::now() + 120 * MIN;
+        let last = HashMap::from([
+            ("main.com".to_string(), now - 5 * MIN),
+            ("dormant.com".to_string(), now - 55 * MIN),
+            ("blog.com".to_string(), now - 10 * MIN),
+        ]);
+        let intervals = HashMap::from([("main.com".to_string(), 5 * MIN), ("dormant.com".to_string(), 60 * MIN)]);
+        // Ticks every 5 minutes for main.com; blog.com follows the 15-minute default
+        let skip = not_due(&last, &intervals, 15 * MIN, 5 * MIN, now);
+        assert_eq!(skip, HashSet::from(["blog.com".to_string(), "dormant.com".to_string()]));
+        assert!(not_due(&last, &intervals, 15 * MIN, 5 * MIN, now + 5 * MIN).is_empty());
+    }
+
+    #[test]
+    fn test_not_due_fetches_everything_without_overrides() {
+        let now = Instant::now() + 120 * MIN;
+        // A tick that came a little early, as jitter can make it
+        let last = HashMap::from([("a.com".to_string(), now - 14 * MIN), ("b.com".to_string(), now - 16 * MIN)]);
+        assert!(not_due(&last, &HashMap::new(), 15 * MIN, 15 * MIN, now).is_empty());
+    }
+
+    #[test]
+    fn test_should_auto_pause_after_long_hide() {
+        assert!(!should_auto_pause(false, None, false));
+        assert!(!should_auto_pause(false, Some(30 * MIN), false));
+        assert!(should_auto_pause(false, Some(60 * MIN), false));
+    }
+
+    #[test]
+    fn test_should_auto_pause_sooner_on_battery() {
+        assert!(!should_auto_pause(false, Some(5 * MIN), true));
+        assert!(should_auto_pause(false, Some(10 * MIN), true));
+    }
+
+    #[test]
+    fn test_should_not_auto_pause_when_background_data_is_needed() {
+        assert!(!should_auto_pause(true, Some(600 * MIN), true));
+    }
+
+    #[test]
+    fn test_schedule_keeps_cadence_however_long_fetches_take() {
+        let start = Instant::now();
+        let mut schedule = Schedule::new(start);
+        assert_eq!(schedule.next_tick(start, MIN, 0.0), start + MIN);
+        // A fetch that finished 20s into the next interval doesn't push later ticks back
+        schedule.record(true, start + MIN + Duration::from_secs(20));
+        assert_eq!(schedule.next_tick(start + MIN + Duration::from_secs(20), MIN, 0.0), start + 2 * MIN);
+        // Nor does a tick skipped entirely
+        assert_eq!(schedule.next_tick(start + 3 * MIN + Duration::from_secs(5), MIN, 0.0), start + 4 * MIN);
+    }
+
+    #[test]
+    fn test_schedule_jitter_stays_within_a_tenth_of_the_interval() {
+        let start = Instant::now();
+        let schedule = Schedule::new(start);
+        assert_eq!(schedule.next_tick(start, 10 * MIN, 1.0), start + 11 * MIN);
+        assert_eq!(schedule.next_tick(start, 10 * MIN, 5.0), start + 11 * MIN);
+        assert_eq!(schedule.next_tick(start, 10 * MIN, 0.5), start + 10 * MIN + Duration::from_secs(30));
+    }
+
+    #[test]
+    fn test_schedule_backs_off_after_failures_and_recovers() {
+        let start = Instant::now();
+        let mut schedule = Schedule::new(start);
+        let now = start + 5 * MIN;
+        schedule.record(false, now);
+        assert_eq!(schedule.next_tick(now, 5 * MIN, 0.0), now + 10 * MIN);
+        schedule.record(false, now);
+        assert_eq!(schedule.next_tick(now, 5 * MIN, 0.0), now + 20 * MIN);
+        for _ in 0..10 {
+            schedule.record(false, now);
+        }
+        assert_eq!(schedule.next_tick(now, 5 * MIN, 0.0), now + MAX_BACKOFF);
+        assert_eq!(schedule.next_tick(now, 120 * MIN, 0.0), now + 120 * MIN);
+
+        // The first success starts a fresh cadence from the moment it finished
+        let recovered = now + 40 * MIN + Duration::from_secs(7);
+        schedule.record(true, recovered);
+        assert_eq!(schedule.next_tick(recovered, 5 * MIN, 0.0), recovered + 5 * MIN);
+    }
+
+    #[test]
+    fn test_needs_background() {
+        // The Top Sites submenu is on by default
+        assert!(needs_background(&Settings::default()));
+        let mut settings = Settings { tray_menu_sites: 0, ..Default::default() };
+        assert!(!needs_background(&settings));
+        settings.tray_title = "total".to_string();
+        assert!(needs_background(&settings));
+        settings.tray_title = "off".to_string();
+        settings.tray_sparkline = true;
+        assert!(needs_background(&settings));
+        settings.tray_sparkline = false;
+        settings.refresh_hook = "cat > /tmp/stats.json".to_string();
+        assert!(needs_background(&settings));
+    }
+}
@@ -0,0 +1,40 @@
+use crate::commands::{get_settings, AnalyticsCache, AnalyticsSnapshot};
+use crate::errors::FetchError;
+use serde::Serialize;
+use tauri::{AppHandle, Emitter, Manager};
+
+/// One kept refresh, as shown when flipping back through recent snapshots.
+#[derive(Serialize)]
+pub struct SnapshotView {
+    /// 0 is the latest refresh, higher is older.
+    pub index: usize,
+    /// Number of snapshots currently kept.
+    pub total: usize,
+    #[serde(flatten)]
+    pub snapshot: AnalyticsSnapshot,
+}
+
+#[derive(Serialize, Clone)]
+struct SnapshotsChanged {
+    total: usize,
+}
+
+/// Tell the panel a new snapshot was added, so an older one being viewed can keep its place.
+pub fn emit_changed(app: &AppHandle, total: usize) {
+    let _ = app.emit("snapshots-changed", SnapshotsChanged { total });
+}
+
+#[tauri::command]
+pub fn get_snapshot_at(app: AppHandle, index: usize) -> Result<SnapshotView, FetchError> {
+    let settings = get_settings(app.clone())?;
+    let cache = app.state::<AnalyticsCache>();
+    let history = cache.0.lock().unwrap();
+    let snapshot = history
+        .get(index)
+        .ok_or_else(|| format!("No snapshot at index {} ({} kept)", index, history.len()))?;
+    Ok(SnapshotView {
+        index,
+        total: history.len(),
+        snapshot: snapshot.clone().into_analytics(&settings),
+    })
+}
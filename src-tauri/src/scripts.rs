@@ -0,0 +1,255 @@
+//! User-written alert rules in Rhai, for conditions threshold alerts can't express.
+//!
+//! A script sees two constants — `sites` (the latest refresh, same shape as the
+//! `analytics-refreshed` payload's `sites`) and `history` (site name → daily points
+//! from the local history database) — and returns an array of `#{ site, message }`
+//! maps, one per alert to raise. Scripts run sandboxed with operation, size and
+//! wall-clock limits.
+
+use crate::commands::{default_true, fetch_analytics_inner, get_settings, save_settings, SeriesPoint, Settings, SiteData};
+use crate::errors::FetchError;
+use rhai::serde::{from_dynamic, to_dynamic};
+use rhai::{Dynamic, Engine, Scope};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use tauri::{AppHandle, Manager};
+use tracing::{info, warn};
+
+const MAX_OPERATIONS: u64 = 1_000_000;
+const TIME_LIMIT: Duration = Duration::from_millis(250);
+const HISTORY_DAYS: i64 = 30;
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AlertScript {
+    pub name: String,
+    pub source: String,
+    #[serde(default = "default_true")]
+    pub enabled: bool,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct AlertDecision {
+    pub site: String,
+    pub message: String,
+}
+
+/// Decisions returned by each script on its previous run, so an alert is only
+/// raised when it first appears rather than on every refresh.
+pub struct ScriptState(pub Mutex<HashMap<String, Vec<AlertDecision>>>);
+
+fn build_engine() -> Engine {
+    let mut engine = Engine::new();
+    engine
+        .set_max_operations(MAX_OPERATIONS)
+        .set_max_call_levels(32)
+        .set_max_expr_depths(64, 32)
+        .set_max_string_size(4_096)
+        .set_max_array_size(10_000)
+        .set_max_map_size(1_000)
+        .on_print(|s| info!(output = s, "Alert script"));
+    let started = Instant::now();
+    engine.on_progress(move |_| (started.elapsed() > TIME_LIMIT).then_some(Dynamic::UNIT));
+    engine
+}
+
+fn evaluate(
+    source: &str,
+    sites: &[SiteData],
+    history: &HashMap<String, Vec<SeriesPoint>>,
+) -> Result<Vec<AlertDecision>, String> {
+    let engine = build_engine();
+    let mut scope = Scope::new();
+    scope.push_constant("sites", to_dynamic(sites).map_err(|e| e.to_string())?);
+    scope.push_constant("history", to_dynamic(history).map_err(|e| e.to_string())?);
+
+    let result: Dynamic = engine
+        .eval_with_scope(&mut scope, source)
+        .map_err(|e| e.to_string())?;
+    if result.is_unit() {
+        return Ok(Vec::new());
+    }
+    from_dynamic(&result)
+        .map_err(|e| format!("Script must return an array of #{{ site, message }}: {}", e))
+}
+
+async fn evaluate_blocking(
+    source: String,
+    sites: Vec<SiteData>,
+    history: HashMap<String, Vec<SeriesPoint>>,
+) -> Result<Vec<AlertDecision>, String> {
+    tokio::task::spawn_blocking(move || evaluate(&source, &sites, &history))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn load_history(app: &AppHandle, settings: &Settings, sites: &[SiteData]) -> HashMap<String, Vec<SeriesPoint>> {
+    let names: Vec<String> = sites.iter().map(|s| s.name.clone()).collect();
+    crate::history::recent(app, settings, &names, HISTORY_DAYS).unwrap_or_else(|e| {
+        warn!(error = %e, "Error loading history for alert scripts");
+        HashMap::new()
+    })
+}
+
+/// Run every enabled script against a finished refresh and notify on new alerts.
+pub async fn run_alert_scripts(app: &AppHandle, sites: &[SiteData]) {
+    let Ok(settings) = get_settings(app.clone()) else {
+        return;
+    };
+    let scripts: Vec<AlertScript> = settings.alert_scripts.iter().filter(|s| s.enabled).cloned().collect();
+    if scripts.is_empty() {
+        return;
+    }
+
+    let history = load_history(app, &settings, sites);
+    let mut changed = false;
+    for script in scripts {
+        let decisions =
+            match evaluate_blocking(script.source.clone(), sites.to_vec(), history.clone()).await {
+                Ok(d) => d,
+                Err(e) => {
+                    warn!(script = %script.name, error = %e, "Alert script failed");
+                    continue;
+                }
+            };
+
+        let previous = app
+            .state::<ScriptState>()
+            .0
+            .lock()
+            .unwrap()
+            .insert(script.name.clone(), decisions.clone())
+            .unwrap_or_default();
+        if previous == decisions {
+            continue;
+        }
+        changed = true;
+        for decision in decisions.iter().filter(|d| !previous.contains(d)) {
+            let key = format!("script:{}:{}", script.name, decision.site);
+            crate::notifications::notify(app, &key, &decision.site, &decision.message);
+        }
+    }
+
+    if changed {
+        crate::journal::persist(app);
+    }
+}
+
+#[tauri::command]
+pub fn get_alert_scripts(app: AppHandle) -> Result<Vec<AlertScript>, FetchError> {
+    Ok(get_settings(app)?.alert_scripts)
+}
+
+/// Add or replace (by name) an alert script.
+#[tauri::command]
+pub fn save_alert_script(app: AppHandle, script: AlertScript) -> Result<(), FetchError> {
+    build_engine().compile(&script.source).map_err(|e| e.to_string())?;
+    let mut settings = get_settings(app.clone())?;
+    match settings.alert_scripts.iter_mut().find(|s| s.name == script.name) {
+        Some(existing) => *existing = script.clone(),
+        None => settings.alert_scripts.push(script.clone()),
+    }
+    save_settings(app.clone(), settings)?;
+    app.state::<ScriptState>().0.lock().unwrap().remove(&script.name);
+    crate::journal::persist(&app);
+    Ok(())
+}
+
+#[tauri::command]
+pub fn delete_alert_script(app: AppHandle, name: String) -> Result<(), FetchError> {
+    let mut settings = get_settings(app.clone())?;
+    settings.alert_scripts.retain(|s| s.name != name);
+    save_settings(app.clone(), settings)?;
+    app.state::<ScriptState>().0.lock().unwrap().remove(&name);
+    crate::journal::persist(&app);
+    Ok(())
+}
+
+/// Dry-run a script against freshly fetched data without sending notifications.
+#[tauri::command]
+pub async fn test_alert_script(app: AppHandle, source: String) -> Result<Vec<AlertDecision>, FetchError> {
+    let sites = fetch_analytics_inner(&app).await?.sites;
+    let history = load_history(&app, &get_settings(app.clone())?, &sites);
+    Ok(evaluate_blocking(source, sites, history).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(name: &str, visits: u64) -> SiteData {
+        SiteData {
+            name: name.to_string(),
+            visits,
+            page_views: visits * 2,
+            series: vec![SeriesPoint {
+                timestamp: "2024-01-15".to_string(),
+                visits,
+                page_views: visits * 2,
+                ..Default::default()
+            }],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_evaluate_returns_decisions() {
+        let source = r#"
+            let out = [];
+            for s in sites {
+                if s.visits < 10 { out.push(#{ site: s.name, message: "quiet" }); }
+            }
+            out
+        "#;
+        let decisions = evaluate(source, &[site("a.com", 5), site("b.com", 50)], &HashMap::new()).unwrap();
+        assert_eq!(
+            decisions,
+            vec![AlertDecision {
+                site: "a.com".to_string(),
+                message: "quiet".to_string()
+            }]
+        );
+    }
+
+    #[test]
+    fn test_evaluate_reads_history() {
+        let history = HashMap::from([(
+            "a.com".to_string(),
+            vec![SeriesPoint {
+                timestamp: "2024-01-14".to_string(),
+                visits: 100,
+                page_views: 100,
+                ..Default::default()
+            }],
+        )]);
+        let source = r#"
+            let prev = history["a.com"][0].visits;
+            if sites[0].visits * 2 < prev { [#{ site: "a.com", message: `down from ${prev}` }] } else { [] }
+        "#;
+        let decisions = evaluate(source, &[site("a.com", 10)], &history).unwrap();
+        assert_eq!(decisions[0].message, "down from 100");
+    }
+
+    #[test]
+    fn test_evaluate_unit_result_means_no_alerts() {
+        assert!(evaluate("let x = 1;", &[], &HashMap::new()).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_evaluate_rejects_wrong_shape() {
+        let err = evaluate("42", &[], &HashMap::new()).unwrap_err();
+        assert!(err.contains("array"));
+    }
+
+    #[test]
+    fn test_evaluate_stops_runaway_scripts() {
+        assert!(evaluate("loop { }", &[], &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_evaluate_limits_string_growth() {
+        let source = r#"let s = "x"; loop { s += s; }"#;
+        assert!(evaluate(source, &[], &HashMap::new()).is_err());
+    }
+}
@@ -0,0 +1,259 @@
+use crate::budget::metered_client;
+use crate::client::{CloudflareClient, Credentials, API_BASE};
+use crate::commands::{
+    fill_series_gaps, get_settings, graphql_request, query_dimension, time_range, Settings,
+    timezone,
+};
+use crate::errors::FetchError;
+use chrono_tz::Tz;
+use serde::Serialize;
+use std::collections::HashMap;
+use tauri::AppHandle;
+use tracing::warn;
+
+/// Firewall actions counted as blocked.
+const BLOCK_ACTIONS: &[&str] = &["block", "drop"];
+/// Firewall actions counted as challenged.
+const CHALLENGE_ACTIONS: &[&str] = &["challenge", "jschallenge", "managed_challenge"];
+
+/// The latest bucket is a spike when it has at least this many events...
+const MIN_SPIKE_EVENTS: u64 = 50;
+/// ...and this many times the average of the earlier buckets.
+const SPIKE_FACTOR: f64 = 3.0;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct SecurityPoint {
+    pub timestamp: String,
+    pub blocked: u64,
+    pub challenged: u64,
+}
+
+/// Blocked and challenged requests for one zone over the selected period.
+#[derive(Serialize, Clone, Debug)]
+pub struct ZoneSecurity {
+    pub name: String,
+    pub blocked: u64,
+    pub challenged: u64,
+    pub series: Vec<SecurityPoint>,
+    /// The latest bucket has far more events than the ones before it.
+    pub spike: bool,
+}
+
+/// Parse one page of `GET /zones`, returning `(name, id)` pairs and the total page count.
+fn parse_zones(body: &serde_json::Value) -> Result<(Vec<(String, String)>, u64), String> {
+    let zones = body["result"]
+        .as_array()
+        .ok_or("Invalid response: missing result array")?
+        .iter()
+        .filter_map(|zone| Some((zone["name"].as_str()?.to_string(), zone["id"].as_str()?.to_string())))
+        .collect();
+    let total_pages = body["result_info"]["total_pages"].as_u64().unwrap_or(1);
+    Ok((zones, total_pages))
+}
+
+async fn fetch_zones(
+    client: &impl CloudflareClient,
+    auth: &Credentials,
+    account_id: &str,
+) -> Result<Vec<(String, String)>, FetchError> {
+    let url = format!("{}/zones", API_BASE);
+    let mut zones = Vec::new();
+    let mut page: u64 = 1;
+    loop {
+        let query = [
+            ("account.id", account_id.to_string()),
+            ("page", page.to_string()),
+            ("per_page", "50".to_string()),
+        ];
+        let body = client.get(auth, &url, &query).await?.success_json()?;
+        let (batch, total_pages) = parse_zones(&body).map_err(FetchError::parse)?;
+        zones.extend(batch);
+        if page >= total_pages {
+            break;
+        }
+        page += 1;
+    }
+    Ok(zones)
+}
+
+/// Sum `(blocked, challenged)` per timestamp from firewall event groups.
+fn bucket_events(groups: &[serde_json::Value]) -> HashMap<String, (u64, u64)> {
+    let mut buckets: HashMap<String, (u64, u64)> = HashMap::new();
+    for group in groups {
+        let (Some(ts), Some(action)) = (
+            group["dimensions"]["ts"].as_str(),
+            group["dimensions"]["action"].as_str(),
+        ) else {
+            continue;
+        };
+        let count = group["count"].as_u64().unwrap_or(0);
+        let entry = buckets.entry(ts.to_string()).or_default();
+        if BLOCK_ACTIONS.contains(&action) {
+            entry.0 += count;
+        } else if CHALLENGE_ACTIONS.contains(&action) {
+            entry.1 += count;
+        }
+    }
+    buckets
+}
+
+fn is_spike(series: &[SecurityPoint]) -> bool {
+    let Some((last, earlier)) = series.split_last() else {
+        return false;
+    };
+    let latest = last.blocked + last.challenged;
+    if latest < MIN_SPIKE_EVENTS || earlier.is_empty() {
+        return false;
+    }
+    let total: u64 = earlier.iter().map(|p| p.blocked + p.challenged).sum();
+    let baseline = total as f64 / earlier.len() as f64;
+    latest as f64 > baseline * SPIKE_FACTOR
+}
+
+async fn fetch_zone_security(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+    name: &str,
+    zone_tag: &str,
+    tz: Tz,
+) -> Result<ZoneSecurity, FetchError> {
+    let (start, end, ts_field) = time_range(settings);
+    let dimension = query_dimension(ts_field);
+
+    let query = format!(
+        r#"{{
+  viewer {{
+    zones(filter: {{ zoneTag: $zoneTag }}) {{
+      events: firewallEventsAdaptiveGroups(limit: 5000, filter: $filter) {{
+        count
+        dimensions {{ action ts: {dimension} }}
+      }}
+    }}
+  }}
+}}"#
+    );
+
+    let actions: Vec<&str> = BLOCK_ACTIONS.iter().chain(CHALLENGE_ACTIONS).copied().collect();
+    let variables = serde_json::json!({
+        "zoneTag": zone_tag,
+        "filter": {
+            "datetime_geq": start,
+            "datetime_leq": end,
+            "action_in": actions,
+        },
+    });
+
+    let data = graphql_request(client, &settings.credentials(), &query, variables).await?;
+    let groups = data["data"]["viewer"]["zones"][0]["events"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+
+    let series: Vec<SecurityPoint> = fill_series_gaps(&start, &end, ts_field, tz, &bucket_events(&groups))
+        .into_iter()
+        .map(|point| SecurityPoint {
+            timestamp: point.timestamp,
+            blocked: point.visits,
+            challenged: point.page_views,
+        })
+        .collect();
+
+    Ok(ZoneSecurity {
+        name: name.to_string(),
+        blocked: series.iter().map(|p| p.blocked).sum(),
+        challenged: series.iter().map(|p| p.challenged).sum(),
+        spike: is_spike(&series),
+        series,
+    })
+}
+
+/// Blocked and challenged request counts per zone for the selected period.
+/// Needs the token to have Zone Analytics read permission.
+#[tauri::command]
+pub async fn fetch_security_events(app: AppHandle) -> Result<Vec<ZoneSecurity>, FetchError> {
+    let settings = get_settings(app.clone())?;
+    if !settings.has_credentials() {
+        return Err(FetchError::missing_credentials());
+    }
+
+    let client = metered_client(&app, &settings)?;
+    let zones = fetch_zones(&client, &settings.credentials(), &settings.account_id).await?;
+    let tz = timezone(&settings);
+    let futures = zones
+        .iter()
+        .map(|(name, zone_tag)| fetch_zone_security(&client, &settings, name, zone_tag, tz));
+
+    let mut results = Vec::new();
+    for (result, (name, _)) in futures::future::join_all(futures).await.into_iter().zip(&zones) {
+        match result {
+            Ok(zone) => results.push(zone),
+            Err(e) => warn!(zone = %name, error = %e, "Error fetching security events"),
+        }
+    }
+    results.sort_by_key(|z| std::cmp::Reverse(z.blocked + z.challenged));
+    Ok(results)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn point(blocked: u64, challenged: u64) -> SecurityPoint {
+        SecurityPoint {
+            timestamp: String::new(),
+            blocked,
+            challenged,
+        }
+    }
+
+    #[test]
+    fn test_parse_zones() {
+        let body = json!({
+            "result": [
+                {"id": "z1", "name": "a.com"},
+                {"id": "z2", "name": "b.com"},
+                {"name": "missing-id.com"}
+            ],
+            "result_info": {"total_pages": 2}
+        });
+        let (zones, total_pages) = parse_zones(&body).unwrap();
+        assert_eq!(
+            zones,
+            vec![
+                ("a.com".to_string(), "z1".to_string()),
+                ("b.com".to_string(), "z2".to_string()),
+            ]
+        );
+        assert_eq!(total_pages, 2);
+        assert!(parse_zones(&json!({"success": false})).is_err());
+    }
+
+    #[test]
+    fn test_bucket_events_splits_actions() {
+        let groups = vec![
+            json!({"count": 5, "dimensions": {"action": "block", "ts": "2024-01-15T10:00:00Z"}}),
+            json!({"count": 2, "dimensions": {"action": "drop", "ts": "2024-01-15T10:00:00Z"}}),
+            json!({"count": 3, "dimensions": {"action": "managed_challenge", "ts": "2024-01-15T10:00:00Z"}}),
+            json!({"count": 9, "dimensions": {"action": "log", "ts": "2024-01-15T11:00:00Z"}}),
+            json!({"count": 1, "dimensions": {"ts": "2024-01-15T11:00:00Z"}}),
+        ];
+        let buckets = bucket_events(&groups);
+        assert_eq!(buckets["2024-01-15T10:00:00Z"], (7, 3));
+        assert_eq!(buckets["2024-01-15T11:00:00Z"], (0, 0));
+    }
+
+    #[test]
+    fn test_is_spike() {
+        assert!(is_spike(&[point(10, 0), point(5, 5), point(60, 20)]));
+        assert!(!is_spike(&[point(40, 0), point(30, 10), point(60, 20)]));
+    }
+
+    #[test]
+    fn test_is_spike_needs_enough_events() {
+        assert!(!is_spike(&[point(0, 0), point(0, 0), point(20, 10)]));
+        assert!(is_spike(&[point(0, 0), point(0, 0), point(40, 10)]));
+        assert!(!is_spike(&[point(100, 0)]));
+        assert!(!is_spike(&[]));
+    }
+}
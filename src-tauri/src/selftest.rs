@@ -0,0 +1,199 @@
+//! "Test connection": runs the fetch pipeline step by step against the configured
+//! account and reports where it breaks.
+
+use crate::budget::MeteredClient;
+use crate::commands::{api_client, fetch_recent_visits, fetch_sites, get_settings};
+use crate::credentials::{check_credentials, CredentialStatus};
+use crate::errors::FetchError;
+use crate::sites::{Site, SiteCache};
+use serde::Serialize;
+use std::future::Future;
+use std::time::Instant;
+use tauri::{AppHandle, Emitter, Manager};
+
+#[derive(Serialize, Clone, Copy, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum StepStatus {
+    Pass,
+    Fail,
+    Skip,
+}
+
+#[derive(Serialize, Clone, Debug)]
+pub struct SelfTestStep {
+    pub name: String,
+    pub status: StepStatus,
+    pub detail: String,
+    pub duration_ms: u64,
+}
+
+#[derive(Serialize, Debug)]
+pub struct SelfTestReport {
+    pub ok: bool,
+    pub steps: Vec<SelfTestStep>,
+}
+
+/// Runs steps in order; once one fails the rest are recorded as skipped.
+#[derive(Default)]
+struct Runner {
+    steps: Vec<SelfTestStep>,
+    failed: bool,
+}
+
+impl Runner {
+    async fn step<T, Fut>(&mut self, name: &str, run: impl FnOnce() -> Fut) -> Option<T>
+    where
+        Fut: Future<Output = Result<(T, String), String>>,
+    {
+        if self.failed {
+            self.push(name, StepStatus::Skip, "Skipped after an earlier failure".to_string(), 0);
+            return None;
+        }
+        let started = Instant::now();
+        let result = run().await;
+        let elapsed = started.elapsed().as_millis() as u64;
+        match result {
+            Ok((value, detail)) => {
+                self.push(name, StepStatus::Pass, detail, elapsed);
+                Some(value)
+            }
+            Err(e) => {
+                self.failed = true;
+                self.push(name, StepStatus::Fail, e, elapsed);
+                None
+            }
+        }
+    }
+
+    fn push(&mut self, name: &str, status: StepStatus, detail: String, duration_ms: u64) {
+        self.steps.push(SelfTestStep {
+            name: name.to_string(),
+            status,
+            detail,
+            duration_ms,
+        });
+    }
+
+    fn finish(self) -> SelfTestReport {
+        SelfTestReport {
+            ok: !self.failed,
+            steps: self.steps,
+        }
+    }
+}
+
+#[tauri::command]
+pub async fn run_self_test(app: AppHandle) -> Result<SelfTestReport, FetchError> {
+    let settings = get_settings(app.clone())?;
+    let mut runner = Runner::default();
+
+    // Never used if this fails: every later step is skipped
+    let client = runner
+        .step("Build HTTP client", || async {
+            let client = api_client(&settings)?;
+            let detail = if settings.proxy_url.trim().is_empty() {
+                "Direct connection".to_string()
+            } else {
+                format!("Via proxy {}", settings.proxy_url.trim())
+            };
+            Ok((client, detail))
+        })
+        .await
+        .unwrap_or_default();
+    let client = MeteredClient::new(&app, &settings, client);
+
+    runner
+        .step("Verify credentials", || async {
+            let check = check_credentials(&client, &settings.credentials(), &settings.account_id).await?;
+            match check.status {
+                CredentialStatus::Valid => Ok(((), check.message)),
+                _ => Err(check.message),
+            }
+        })
+        .await;
+
+    let sites = runner
+        .step("List sites", || async {
+            let sites = fetch_sites(&client, &settings.credentials(), &settings.account_id).await?;
+            let detail = format!("{} site(s) found", sites.len());
+            Ok((sites, detail))
+        })
+        .await;
+
+    runner
+        .step("Query analytics", || async {
+            let Some((name, site_tag)) = sites.as_ref().and_then(|s| s.first()) else {
+                return Ok(((), "No sites to query".to_string()));
+            };
+            let visits = fetch_recent_visits(
+                &client,
+                &settings,
+                site_tag,
+                chrono::Duration::hours(1),
+            )
+            .await?;
+            Ok(((), format!("{}: {} visit(s) in the last hour", name, visits)))
+        })
+        .await;
+
+    runner
+        .step("Update site cache", || async {
+            let cached: Vec<Site> = sites
+                .unwrap_or_default()
+                .into_iter()
+                .map(|(name, site_tag)| Site { name, site_tag })
+                .collect();
+            let count = cached.len();
+            app.state::<SiteCache>().replace(&settings, cached);
+            Ok(((), format!("{} site(s) cached", count)))
+        })
+        .await;
+
+    runner
+        .step("Emit event", || async {
+            app.emit("self-test", ()).map_err(|e| e.to_string())?;
+            Ok(((), "Event delivered".to_string()))
+        })
+        .await;
+
+    Ok(runner.finish())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_runner_records_passing_steps() {
+        let mut runner = Runner::default();
+        let value = runner.step("one", || async { Ok((42, "fine".to_string())) }).await;
+        assert_eq!(value, Some(42));
+        let report = runner.finish();
+        assert!(report.ok);
+        assert_eq!(report.steps[0].status, StepStatus::Pass);
+        assert_eq!(report.steps[0].detail, "fine");
+    }
+
+    #[tokio::test]
+    async fn test_runner_skips_steps_after_failure() {
+        let mut runner = Runner::default();
+        runner
+            .step("one", || async { Err::<((), String), _>("boom".to_string()) })
+            .await;
+        let mut ran = false;
+        let value = runner
+            .step("two", || {
+                ran = true;
+                async { Ok(((), String::new())) }
+            })
+            .await;
+        assert_eq!(value, None);
+        assert!(!ran);
+
+        let report = runner.finish();
+        assert!(!report.ok);
+        let statuses: Vec<_> = report.steps.iter().map(|s| s.status).collect();
+        assert_eq!(statuses, vec![StepStatus::Fail, StepStatus::Skip]);
+        assert_eq!(report.steps[0].detail, "boom");
+    }
+}
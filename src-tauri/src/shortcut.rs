@@ -0,0 +1,81 @@
+use crate::commands::{get_settings, save_settings};
+use crate::errors::FetchError;
+use std::str::FromStr;
+use tauri::AppHandle;
+use tauri_plugin_global_shortcut::{GlobalShortcutExt, Shortcut, ShortcutState};
+use tracing::warn;
+
+/// ⌥⇧F
+pub const DEFAULT_SHORTCUT: &str = "Alt+Shift+F";
+
+/// Parse an accelerator such as "Alt+Shift+F"; an empty string disables the shortcut.
+fn parse(accelerator: &str) -> Result<Option<Shortcut>, String> {
+    let accelerator = accelerator.trim();
+    if accelerator.is_empty() {
+        return Ok(None);
+    }
+    Shortcut::from_str(accelerator)
+        .map(Some)
+        .map_err(|e| format!("Invalid shortcut \"{}\": {}", accelerator, e))
+}
+
+/// Replace the registered shortcut with `accelerator`, which toggles the panel like a tray click.
+pub fn register(app: &AppHandle, accelerator: &str) -> Result<(), String> {
+    let shortcut = parse(accelerator)?;
+    let global_shortcut = app.global_shortcut();
+    global_shortcut.unregister_all().map_err(|e| e.to_string())?;
+    let Some(shortcut) = shortcut else {
+        return Ok(());
+    };
+    global_shortcut
+        .on_shortcut(shortcut, |app, _, event| {
+            if event.state() == ShortcutState::Pressed {
+                crate::toggle_panel(app);
+            }
+        })
+        .map_err(|e| format!("Couldn't register \"{}\", it may be used by another app: {}", accelerator.trim(), e))
+}
+
+/// Register the shortcut from settings on startup.
+pub fn restore(app: &AppHandle) {
+    let Ok(settings) = get_settings(app.clone()) else {
+        return;
+    };
+    if let Err(e) = register(app, &settings.global_shortcut) {
+        warn!("{}", e);
+    }
+}
+
+/// Switch to a new shortcut, keeping the old one if the new one can't be registered.
+#[tauri::command]
+pub fn set_global_shortcut(app: AppHandle, shortcut: String) -> Result<(), FetchError> {
+    let mut settings = get_settings(app.clone())?;
+    if let Err(e) = register(&app, &shortcut) {
+        let _ = register(&app, &settings.global_shortcut);
+        return Err(e.into());
+    }
+    settings.global_shortcut = shortcut.trim().to_string();
+    save_settings(app, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_default_shortcut() {
+        assert!(parse(DEFAULT_SHORTCUT).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_parse_empty_disables() {
+        assert!(parse("").unwrap().is_none());
+        assert!(parse("   ").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_parse_reports_invalid_shortcut() {
+        let err = parse("Hyper+Shift+").unwrap_err();
+        assert!(err.starts_with("Invalid shortcut \"Hyper+Shift+\""));
+    }
+}
@@ -0,0 +1,235 @@
+use crate::client::{shared_client, CloudflareClient, Credentials};
+use crate::commands::{fetch_sites, get_settings, save_settings, Settings};
+use crate::errors::FetchError;
+use serde::Serialize;
+use std::sync::Mutex;
+use tauri::{AppHandle, Manager};
+
+const DEFAULT_PER_PAGE: usize = 25;
+const MAX_PER_PAGE: usize = 100;
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct Site {
+    pub name: String,
+    pub site_tag: String,
+}
+
+/// Every RUM site in the account, with the account ID and credentials they were listed
+/// with. After switching accounts or tokens the list counts as missing.
+#[derive(Default)]
+pub struct SiteCache(Mutex<Option<Listing>>);
+
+/// The sites of one account, listed with one set of credentials.
+struct Listing {
+    account_id: String,
+    credentials: Credentials,
+    sites: Vec<Site>,
+}
+
+impl Listing {
+    fn matches(&self, settings: &Settings) -> bool {
+        self.account_id == settings.account_id && self.credentials == settings.credentials()
+    }
+}
+
+impl SiteCache {
+    /// The cached sites, if they were listed for the account and credentials in `settings`.
+    pub(crate) fn get(&self, settings: &Settings) -> Option<Vec<Site>> {
+        self.0.lock().unwrap().as_ref().filter(|l| l.matches(settings)).map(|l| l.sites.clone())
+    }
+
+    /// Number of cached sites of the account in `settings`, 0 if there are none.
+    pub(crate) fn count(&self, settings: &Settings) -> usize {
+        self.0.lock().unwrap().as_ref().filter(|l| l.matches(settings)).map_or(0, |l| l.sites.len())
+    }
+
+    pub(crate) fn replace(&self, settings: &Settings, sites: Vec<Site>) {
+        *self.0.lock().unwrap() = Some(Listing {
+            account_id: settings.account_id.clone(),
+            credentials: settings.credentials(),
+            sites,
+        });
+    }
+}
+
+fn to_sites(pairs: Vec<(String, String)>) -> Vec<Site> {
+    pairs.into_iter().map(|(name, site_tag)| Site { name, site_tag }).collect()
+}
+
+#[derive(Serialize, Debug)]
+pub struct SitePage {
+    pub sites: Vec<Site>,
+    pub page: usize,
+    pub per_page: usize,
+    /// Number of sites matching the search, across all pages.
+    pub total: usize,
+}
+
+/// Slice one page (1-based) out of the sites whose name contains `search`, case-insensitively.
+fn page_of(sites: &[Site], page: usize, per_page: usize, search: &str) -> SitePage {
+    let search = search.trim().to_lowercase();
+    let matching: Vec<&Site> = sites
+        .iter()
+        .filter(|site| search.is_empty() || site.name.to_lowercase().contains(&search))
+        .collect();
+    let page = page.max(1);
+    let per_page = per_page.clamp(1, MAX_PER_PAGE);
+
+    SitePage {
+        sites: matching
+            .iter()
+            .skip((page - 1).saturating_mul(per_page))
+            .take(per_page)
+            .map(|&site| site.clone())
+            .collect(),
+        page,
+        per_page,
+        total: matching.len(),
+    }
+}
+
+/// The cached site list, fetching and caching it first if it's empty or belongs to
+/// other settings.
+pub(crate) async fn cached_sites(app: &AppHandle, client: &impl CloudflareClient, settings: &Settings) -> Result<Vec<Site>, FetchError> {
+    if let Some(sites) = app.state::<SiteCache>().get(settings).filter(|sites| !sites.is_empty()) {
+        return Ok(sites);
+    }
+    let sites = to_sites(fetch_sites(client, &settings.credentials(), &settings.account_id).await?);
+    app.state::<SiteCache>().replace(settings, sites.clone());
+    Ok(sites)
+}
+
+/// Page through the account's sites, optionally filtered by name. The full list is
+/// fetched on first use (or with `refresh`) and cached for subsequent pages.
+#[tauri::command]
+pub async fn list_sites(
+    app: AppHandle,
+    page: Option<usize>,
+    per_page: Option<usize>,
+    search: Option<String>,
+    refresh: Option<bool>,
+) -> Result<SitePage, FetchError> {
+    let settings = get_settings(app.clone())?;
+    let cached = app.state::<SiteCache>().get(&settings).filter(|sites| !sites.is_empty());
+    let sites = match cached {
+        Some(sites) if !refresh.unwrap_or(false) => sites,
+        _ => {
+            if !settings.has_credentials() {
+                return Err(FetchError::missing_credentials());
+            }
+            let client = shared_client(&app, &settings)?;
+            let sites = to_sites(fetch_sites(&client, &settings.credentials(), &settings.account_id).await?);
+            app.state::<SiteCache>().replace(&settings, sites.clone());
+            sites
+        }
+    };
+
+    Ok(page_of(
+        &sites,
+        page.unwrap_or(1),
+        per_page.unwrap_or(DEFAULT_PER_PAGE),
+        search.as_deref().unwrap_or(""),
+    ))
+}
+
+/// Add `site` to or remove it from `list`, keeping the order of the others.
+fn set_listed(list: &mut Vec<String>, site: &str, listed: bool) {
+    list.retain(|s| s != site);
+    if listed {
+        list.push(site.to_string());
+    }
+}
+
+/// Hide a site from the results, or show it again. Hidden sites aren't queried.
+#[tauri::command]
+pub fn set_site_hidden(app: AppHandle, site: String, hidden: bool) -> Result<(), FetchError> {
+    let mut settings = get_settings(app.clone())?;
+    set_listed(&mut settings.hidden_sites, &site, hidden);
+    save_settings(app, settings)
+}
+
+/// Pin a site to the top of the results, or unpin it.
+#[tauri::command]
+pub fn set_site_pinned(app: AppHandle, site: String, pinned: bool) -> Result<(), FetchError> {
+    let mut settings = get_settings(app.clone())?;
+    set_listed(&mut settings.pinned_sites, &site, pinned);
+    save_settings(app, settings)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sites(names: &[&str]) -> Vec<Site> {
+        names
+            .iter()
+            .map(|n| Site {
+                name: n.to_string(),
+                site_tag: format!("tag-{}", n),
+            })
+            .collect()
+    }
+
+    fn names(page: &SitePage) -> Vec<&str> {
+        page.sites.iter().map(|s| s.name.as_str()).collect()
+    }
+
+    #[test]
+    fn test_page_of_slices_pages() {
+        let all = sites(&["a.com", "b.com", "c.com", "d.com", "e.com"]);
+        let first = page_of(&all, 1, 2, "");
+        assert_eq!(names(&first), vec!["a.com", "b.com"]);
+        assert_eq!(first.total, 5);
+        assert_eq!(names(&page_of(&all, 3, 2, "")), vec!["e.com"]);
+        assert!(page_of(&all, 4, 2, "").sites.is_empty());
+    }
+
+    #[test]
+    fn test_page_of_filters_case_insensitively() {
+        let all = sites(&["Blog.example.com", "shop.example.com", "other.dev"]);
+        let page = page_of(&all, 1, 10, " EXAMPLE ");
+        assert_eq!(names(&page), vec!["Blog.example.com", "shop.example.com"]);
+        assert_eq!(page.total, 2);
+    }
+
+    #[test]
+    fn test_set_listed_adds_once_and_removes() {
+        let mut list = vec!["a.com".to_string()];
+        set_listed(&mut list, "b.com", true);
+        set_listed(&mut list, "b.com", true);
+        assert_eq!(list, vec!["a.com", "b.com"]);
+        set_listed(&mut list, "a.com", false);
+        assert_eq!(list, vec!["b.com"]);
+    }
+
+    fn settings(account_id: &str, token: &str) -> Settings {
+        Settings { account_id: account_id.to_string(), token: token.to_string(), ..Default::default() }
+    }
+
+    #[test]
+    fn test_site_cache_is_keyed_by_account_and_credentials() {
+        let cache = SiteCache::default();
+        let current = settings("acc-1", "token-1");
+        assert_eq!(cache.get(&current), None);
+        cache.replace(&current, sites(&["a.com", "b.com"]));
+        assert_eq!(cache.get(&current), Some(sites(&["a.com", "b.com"])));
+        assert_eq!(cache.count(&current), 2);
+        assert_eq!(cache.get(&settings("acc-2", "token-1")), None);
+        assert_eq!(cache.get(&settings("acc-1", "token-2")), None);
+        assert_eq!(cache.count(&settings("acc-2", "token-1")), 0);
+        // A second listing replaces the first rather than adding to it
+        cache.replace(&current, sites(&["a.com", "b.com"]));
+        assert_eq!(cache.count(&current), 2);
+    }
+
+    #[test]
+    fn test_page_of_clamps_arguments() {
+        let all = sites(&["a.com", "b.com"]);
+        let page = page_of(&all, 0, 0, "");
+        assert_eq!(page.page, 1);
+        assert_eq!(page.per_page, 1);
+        assert_eq!(names(&page), vec!["a.com"]);
+        assert_eq!(page_of(&all, 1, 10_000, "").per_page, MAX_PER_PAGE);
+        assert!(page_of(&all, usize::MAX, MAX_PER_PAGE, "").sites.is_empty());
+    }
+}
@@ -0,0 +1,13 @@
+//! Fixtures shared by the unit tests of several modules.
+
+use std::fs;
+use std::path::PathBuf;
+
+/// A fresh, empty directory under the system temp folder for one test. `module` and
+/// `name` keep tests running in parallel out of each other's files.
+pub(crate) fn temp_dir(module: &str, name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("flarestats-{}-{}-{}", module, name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    dir
+}
@@ -0,0 +1,554 @@
+use crate::commands::{fetch_hourly_visits, get_settings, timezone, SeriesPoint, Settings, SiteData, Snapshot};
+use crate::errors::FetchError;
+use crate::updater::InstallMenuItem;
+use std::sync::Mutex;
+use tauri::menu::{IsMenuItem, Menu, MenuItem, PredefinedMenuItem, Submenu};
+use tauri::{image::Image, tray::TrayIcon, AppHandle, Manager, Wry};
+use tracing::warn;
+
+/// Handle to the menu bar icon, filled in once the tray is built during setup.
+pub struct TrayState(pub Mutex<Option<TrayIcon>>);
+
+const TOOLTIP: &str = "FlareStats";
+/// Failed background refreshes in a row before the error icon shows. Auth errors
+/// won't fix themselves, so they show right away.
+const FAILURES_BEFORE_ERROR: u32 = 2;
+
+/// Consecutive background refresh failures, reset by the next success.
+#[derive(Default, Debug, PartialEq)]
+pub struct RefreshHealth {
+    failures: u32,
+    showing_error: bool,
+}
+
+impl RefreshHealth {
+    /// Count a failure; true when the error icon should be shown (or its tooltip updated).
+    fn fail(&mut self, error: &FetchError) -> bool {
+        self.failures += 1;
+        if matches!(error, FetchError::Auth { .. }) || self.failures >= FAILURES_BEFORE_ERROR {
+            self.showing_error = true;
+        }
+        self.showing_error
+    }
+
+    /// Reset after a success; true if the error icon was showing and needs replacing.
+    fn recover(&mut self) -> bool {
+        std::mem::take(self).showing_error
+    }
+}
+
+pub struct TrayHealth(pub Mutex<RefreshHealth>);
+
+fn error_tooltip(error: &FetchError) -> String {
+    let reason = match error {
+        FetchError::Auth { .. } => "Cloudflare rejected the API token",
+        FetchError::Network { .. } | FetchError::Timeout { .. } => "Can't reach Cloudflare, check your connection",
+        FetchError::RateLimit { .. } => "Rate limited by Cloudflare",
+        _ => error.message(),
+    };
+    format!("{} – refresh failing: {}", TOOLTIP, reason)
+}
+
+/// Same compact format as `formatNumber` in the frontend.
+fn format_compact(n: u64) -> String {
+    if n >= 1_000_000 {
+        format!("{:.1}M", n as f64 / 1_000_000.0)
+    } else if n >= 1_000 {
+        format!("{:.1}K", n as f64 / 1_000.0)
+    } else {
+        n.to_string()
+    }
+}
+
+fn title_for(mode: &str, site: &str, sites: &[SiteData]) -> Option<String> {
+    match mode {
+        "total" => Some(format_compact(sites.iter().map(|s| s.visits).sum())),
+        "site" => sites
+            .iter()
+            .find(|s| s.name == site)
+            .map(|s| format_compact(s.visits)),
+        _ => None,
+    }
+}
+
+/// Changes of total visits within this many percent count as flat.
+const FLAT_PERCENT: f64 = 2.0;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Trend {
+    Up,
+    Flat,
+    Down,
+}
+
+/// Direction of total visits vs the previous period, if there is one to compare with.
+fn trend_for(sites: &[SiteData]) -> Option<Trend> {
+    let current: u64 = sites.iter().map(|s| s.visits).sum();
+    let previous: u64 = sites.iter().map(|s| s.prev_visits).sum();
+    if previous == 0 {
+        return None;
+    }
+    let change = (current as f64 - previous as f64) / previous as f64 * 100.0;
+    Some(if change > FLAT_PERCENT {
+        Trend::Up
+    } else if change < -FLAT_PERCENT {
+        Trend::Down
+    } else {
+        Trend::Flat
+    })
+}
+
+fn icon_bytes(trend: Option<Trend>) -> &'static [u8] {
+    match trend {
+        Some(Trend::Up) => include_bytes!("../icons/tray-icon-up.png"),
+        Some(Trend::Flat) => include_bytes!("../icons/tray-icon-flat.png"),
+        Some(Trend::Down) => include_bytes!("../icons/tray-icon-down.png"),
+        None => include_bytes!("../icons/tray-icon.png"),
+    }
+}
+
+/// Swap the tray icon, and the tooltip unless it's `None`.
+fn set_icon(app: &AppHandle, icon: tauri::Result<Image<'_>>, tooltip: Option<&str>) {
+    let state = app.state::<TrayState>();
+    let guard = state.0.lock().unwrap();
+    let Some(tray) = guard.as_ref() else {
+        return;
+    };
+    let result = icon
+        .and_then(|icon| tray.set_icon(Some(icon)))
+        .and_then(|_| tray.set_icon_as_template(true))
+        .and_then(|_| match tooltip {
+            Some(tooltip) => tray.set_tooltip(Some(tooltip)),
+            None => Ok(()),
+        });
+    if let Err(e) = result {
+        warn!(error = %e, "Error setting tray icon");
+    }
+}
+
+/// Tooltip after a successful refresh at `time`, flagging one that left sites out.
+fn refreshed_tooltip(time: &str, partial: bool) -> String {
+    if partial {
+        format!("{} – updated {}, some sites failed to load", TOOLTIP, time)
+    } else {
+        format!("{} – updated {}", TOOLTIP, time)
+    }
+}
+
+/// Switch the tray icon to the sparkline, or the up/flat/down variant for the latest
+/// totals, clearing the error state left by earlier failed refreshes.
+pub fn update_icon(app: &AppHandle, snapshot: &Snapshot) {
+    app.state::<TrayHealth>().0.lock().unwrap().recover();
+    let tz = get_settings(app.clone()).map_or(chrono_tz::Tz::UTC, |settings| timezone(&settings));
+    let time = snapshot.fetched_at.with_timezone(&tz).format("%H:%M").to_string();
+    let icon = sparkline_icon(app).map_or_else(|| Image::from_bytes(icon_bytes(trend_for(&snapshot.sites))), Ok);
+    set_icon(app, icon, Some(&refreshed_tooltip(&time, snapshot.partial)));
+}
+
+/// Side of the sparkline icon in pixels: the 22pt menu bar at 2x.
+const SPARKLINE_SIZE: u32 = 44;
+/// Alpha of the area under the sparkline; the line itself is opaque.
+const SPARKLINE_FILL_ALPHA: u8 = 0x50;
+
+/// Hourly visits of the last 24h drawn as the tray icon; `None` unless
+/// `Settings::tray_sparkline` is on and they have been fetched.
+pub struct Sparkline(pub Mutex<Option<Vec<u64>>>);
+
+/// The site the sparkline follows: `tray_site`, or else the first pinned site.
+fn sparkline_site(settings: &Settings) -> Option<&str> {
+    Some(settings.tray_site.as_str())
+        .filter(|site| !site.is_empty())
+        .or(settings.pinned_sites.first().map(String::as_str))
+}
+
+/// RGBA pixels of `values` drawn as a line scaled to the largest value, with the area
+/// below it shaded. Black with alpha only, so it works as a template icon.
+fn render_sparkline(values: &[u64], width: u32, height: u32) -> Vec<u8> {
+    let mut pixels = vec![0u8; (width * height * 4) as usize];
+    if values.is_empty() || width == 0 || height < 4 {
+        return pixels;
+    }
+    let max = values.iter().copied().max().unwrap_or(0).max(1) as f64;
+    // Keep the line a pixel clear of the edges so it isn't clipped
+    let (top, bottom) = (2.0, (height - 2) as f64);
+    let last = (values.len() - 1) as f64;
+    let line_y = |x: f64| {
+        let pos = x / (width - 1).max(1) as f64 * last;
+        let i = pos.floor() as usize;
+        let next = (i + 1).min(values.len() - 1);
+        let value = values[i] as f64 + (values[next] as f64 - values[i] as f64) * (pos - i as f64);
+        bottom - value / max * (bottom - top)
+    };
+    let right = (width - 1) as f64;
+    for x in 0..width {
+        // Cover the whole rise or fall across the column so steep segments stay joined
+        let (a, b) = (line_y((x as f64 - 0.5).max(0.0)), line_y((x as f64 + 0.5).min(right)));
+        let (from, to) = (a.min(b) - 1.0, a.max(b) + 1.0);
+        for y in 0..height {
+            let y_pos = y as f64;
+            let alpha = if y_pos > to {
+                SPARKLINE_FILL_ALPHA
+            } else if y_pos >= from {
+                255
+            } else {
+                0
+            };
+            pixels[((y * width + x) * 4 + 3) as usize] = alpha;
+        }
+    }
+    pixels
+}
+
+fn sparkline_icon(app: &AppHandle) -> Option<Image<'static>> {
+    let values = app.state::<Sparkline>().0.lock().unwrap().clone()?;
+    let pixels = render_sparkline(&values, SPARKLINE_SIZE, SPARKLINE_SIZE);
+    Some(Image::new_owned(pixels, SPARKLINE_SIZE, SPARKLINE_SIZE))
+}
+
+/// Draw the stored sparkline as the tray icon, unless the error icon is showing.
+fn redraw_sparkline(app: &AppHandle) {
+    if app.state::<TrayHealth>().0.lock().unwrap().showing_error {
+        return;
+    }
+    if let Some(icon) = sparkline_icon(app) {
+        set_icon(app, Ok(icon), None);
+    }
+}
+
+async fn fetch_sparkline(app: &AppHandle, settings: &Settings, site: &str) -> Result<Vec<u64>, FetchError> {
+    let client = crate::budget::metered_client(app, settings)?;
+    let sites = crate::sites::cached_sites(app, &client, settings).await?;
+    let Some(found) = sites.into_iter().find(|s| s.name == site) else {
+        return Err(format!("Site {} not found", site).into());
+    };
+    fetch_hourly_visits(&client, settings, &found.site_tag).await
+}
+
+/// Visits per hour of `series`, summing the quarter hours of `Settings::quarter_hour_24h`.
+fn hourly_visits(series: &[SeriesPoint]) -> Vec<u64> {
+    let mut hours: Vec<(&str, u64)> = Vec::new();
+    for point in series {
+        // Timestamps are local RFC 3339, so the hour is everything up to the minutes
+        let hour = point.timestamp.get(..13).unwrap_or(&point.timestamp);
+        match hours.last_mut() {
+            Some((last, visits)) if *last == hour => *visits += point.visits,
+            _ => hours.push((hour, point.visits)),
+        }
+    }
+    hours.into_iter().map(|(_, visits)| visits).collect()
+}
+
+/// Store the sparkline values after a refresh, before `update_icon` draws them. With the
+/// 24h period they come from the site's own series; otherwise they're queried in the
+/// background and drawn once they arrive.
+pub fn update_sparkline(app: &AppHandle, settings: &Settings, sites: &[SiteData]) {
+    let site = match sparkline_site(settings) {
+        Some(site) if settings.tray_sparkline => site.to_string(),
+        _ => {
+            *app.state::<Sparkline>().0.lock().unwrap() = None;
+            return;
+        }
+    };
+    if settings.period == "24h"
+        && let Some(data) = sites.iter().find(|s| s.name == site && !s.series.is_empty())
+    {
+        *app.state::<Sparkline>().0.lock().unwrap() = Some(hourly_visits(&data.series));
+        return;
+    }
+    let app = app.clone();
+    let settings = settings.clone();
+    tauri::async_runtime::spawn(async move {
+        match fetch_sparkline(&app, &settings, &site).await {
+            Ok(values) => {
+                *app.state::<Sparkline>().0.lock().unwrap() = Some(values);
+                redraw_sparkline(&app);
+            }
+            Err(e) => warn!(site = %site, error = %e, "Error fetching tray sparkline"),
+        }
+    });
+}
+
+/// Record a failed background refresh, switching to the error icon once failures persist.
+pub fn report_failure(app: &AppHandle, error: &FetchError) {
+    let show = app.state::<TrayHealth>().0.lock().unwrap().fail(error);
+    if show {
+        set_icon(
+            app,
+            Image::from_bytes(include_bytes!("../icons/tray-icon-error.png")),
+            Some(&error_tooltip(error)),
+        );
+    }
+}
+
+fn set_title(app: &AppHandle, title: Option<String>) {
+    if let Some(tray) = app.state::<TrayState>().0.lock().unwrap().as_ref()
+        && let Err(e) = tray.set_title(title)
+    {
+        warn!(error = %e, "Error setting tray title");
+    }
+}
+
+/// Show visits next to the tray icon according to `Settings.tray_title`.
+pub fn update_title(app: &AppHandle, sites: &[SiteData]) {
+    let Ok(settings) = get_settings(app.clone()) else {
+        return;
+    };
+    set_title(app, title_for(&settings.tray_title, &settings.tray_site, sites));
+}
+
+pub fn clear_title(app: &AppHandle) {
+    set_title(app, None);
+}
+
+/// Menu IDs of the quick stats entries are this prefix followed by the site name.
+const SITE_ITEM_PREFIX: &str = "site:";
+
+/// Menu ID and "example.com — 4.2K" label for the busiest `limit` sites.
+fn top_site_items(sites: &[SiteData], limit: usize) -> Vec<(String, String)> {
+    let mut top: Vec<&SiteData> = sites.iter().filter(|s| !s.aggregate).collect();
+    top.sort_by_key(|s| std::cmp::Reverse(s.visits));
+    top.into_iter()
+        .take(limit)
+        .map(|s| {
+            (
+                format!("{}{}", SITE_ITEM_PREFIX, s.name),
+                format!("{} — {}", s.name, format_compact(s.visits)),
+            )
+        })
+        .collect()
+}
+
+/// The site a quick stats entry opens, if `id` is one.
+pub fn menu_site(id: &str) -> Option<&str> {
+    id.strip_prefix(SITE_ITEM_PREFIX)
+}
+
+/// The tray menu, with a "Top Sites" submenu listing `Settings.tray_menu_sites` sites
+/// once there are results to show.
+pub fn build_menu(app: &AppHandle, sites: &[SiteData]) -> tauri::Result<Menu<Wry>> {
+    let limit = get_settings(app.clone()).map(|s| s.tray_menu_sites).unwrap_or(0);
+    let show = MenuItem::with_id(app, "show", "Show", true, None::<&str>)?;
+    let refresh = MenuItem::with_id(app, "refresh_now", "Refresh Now", true, None::<&str>)?;
+    let settings = MenuItem::with_id(app, "settings", "Settings", true, None::<&str>)?;
+    let site_items = top_site_items(sites, limit)
+        .into_iter()
+        .map(|(id, label)| MenuItem::with_id(app, id, label, true, None::<&str>))
+        .collect::<tauri::Result<Vec<_>>>()?;
+    let top_sites = if site_items.is_empty() {
+        None
+    } else {
+        let refs: Vec<&dyn IsMenuItem<Wry>> = site_items.iter().map(|i| i as &dyn IsMenuItem<Wry>).collect();
+        Some(Submenu::with_items(app, "Top Sites", true, &refs)?)
+    };
+
+    // Reuse the install entry so the updater keeps control of its text and state
+    let install_state = app.state::<InstallMenuItem>();
+    let mut install_guard = install_state.0.lock().unwrap();
+    let install_update = match install_guard.as_ref() {
+        Some(item) => item.clone(),
+        None => {
+            let item = MenuItem::with_id(app, "install_update", "No Updates Available", false, None::<&str>)?;
+            *install_guard = Some(item.clone());
+            item
+        }
+    };
+    let separator = PredefinedMenuItem::separator(app)?;
+    let quit = MenuItem::with_id(app, "quit", "Quit", true, None::<&str>)?;
+
+    let mut items: Vec<&dyn IsMenuItem<Wry>> = vec![&show, &refresh, &settings];
+    if let Some(top_sites) = &top_sites {
+        items.push(top_sites);
+    }
+    items.extend([&install_update as &dyn IsMenuItem<Wry>, &separator, &quit]);
+    Menu::with_items(app, &items)
+}
+
+/// Rebuild the tray menu so the quick stats match the latest refresh.
+pub fn update_menu(app: &AppHandle, sites: &[SiteData]) {
+    let menu = match build_menu(app, sites) {
+        Ok(menu) => menu,
+        Err(e) => {
+            warn!(error = %e, "Error building tray menu");
+            return;
+        }
+    };
+    if let Some(tray) = app.state::<TrayState>().0.lock().unwrap().as_ref()
+        && let Err(e) = tray.set_menu(Some(menu))
+    {
+        warn!(error = %e, "Error setting tray menu");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn site(name: &str, visits: u64) -> SiteData {
+        SiteData {
+            name: name.to_string(),
+            visits,
+            page_views: visits,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_format_compact() {
+        assert_eq!(format_compact(999), "999");
+        assert_eq!(format_compact(1_234), "1.2K");
+        assert_eq!(format_compact(2_500_000), "2.5M");
+    }
+
+    #[test]
+    fn test_title_for_total_sums_all_sites() {
+        let sites = [site("a.com", 800), site("b.com", 400)];
+        assert_eq!(title_for("total", "", &sites), Some("1.2K".to_string()));
+    }
+
+    #[test]
+    fn test_title_for_site_uses_pinned_site() {
+        let sites = [site("a.com", 800), site("b.com", 40)];
+        assert_eq!(title_for("site", "b.com", &sites), Some("40".to_string()));
+        assert_eq!(title_for("site", "missing.com", &sites), None);
+    }
+
+    #[test]
+    fn test_title_for_off() {
+        assert_eq!(title_for("off", "", &[site("a.com", 1)]), None);
+    }
+
+    fn site_with_previous(visits: u64, prev_visits: u64) -> SiteData {
+        SiteData {
+            prev_visits,
+            ..site("a.com", visits)
+        }
+    }
+
+    #[test]
+    fn test_trend_for_compares_totals() {
+        assert_eq!(trend_for(&[site_with_previous(120, 100)]), Some(Trend::Up));
+        assert_eq!(trend_for(&[site_with_previous(80, 100)]), Some(Trend::Down));
+        assert_eq!(
+            trend_for(&[site_with_previous(60, 100), site_with_previous(141, 100)]),
+            Some(Trend::Flat)
+        );
+    }
+
+    #[test]
+    fn test_trend_for_small_change_is_flat() {
+        assert_eq!(trend_for(&[site_with_previous(102, 100)]), Some(Trend::Flat));
+        assert_eq!(trend_for(&[site_with_previous(98, 100)]), Some(Trend::Flat));
+    }
+
+    #[test]
+    fn test_top_site_items_busiest_first() {
+        let mut sites = vec![site("a.com", 10), site("b.com", 4_200), site("c.com", 300)];
+        sites.insert(0, SiteData { aggregate: true, ..site("All sites", 4_510) });
+        assert_eq!(
+            top_site_items(&sites, 2),
+            vec![
+                ("site:b.com".to_string(), "b.com — 4.2K".to_string()),
+                ("site:c.com".to_string(), "c.com — 300".to_string()),
+            ]
+        );
+        assert!(top_site_items(&sites, 0).is_empty());
+    }
+
+    #[test]
+    fn test_menu_site() {
+        assert_eq!(menu_site("site:example.com"), Some("example.com"));
+        assert_eq!(menu_site("settings"), None);
+    }
+
+    #[test]
+    fn test_health_shows_error_after_repeated_failures() {
+        let mut health = RefreshHealth::default();
+        let offline = FetchError::Network { message: "connection refused".to_string() };
+        assert!(!health.fail(&offline));
+        assert!(health.fail(&offline));
+        assert!(health.recover());
+        assert!(!health.recover());
+        assert!(!health.fail(&offline));
+    }
+
+    #[test]
+    fn test_health_shows_auth_error_immediately() {
+        let mut health = RefreshHealth::default();
+        assert!(health.fail(&FetchError::Auth { message: "API error 403".to_string() }));
+    }
+
+    #[test]
+    fn test_error_tooltip_describes_failure() {
+        assert_eq!(
+            error_tooltip(&FetchError::Auth { message: "x".to_string() }),
+            "FlareStats – refresh failing: Cloudflare rejected the API token"
+        );
+        let graphql = FetchError::Graphql { message: "GraphQL errors: boom".to_string(), errors: Vec::new() };
+        assert!(error_tooltip(&graphql).ends_with("GraphQL errors: boom"));
+    }
+
+    #[test]
+    fn test_refreshed_tooltip_flags_partial_refresh() {
+        assert_eq!(refreshed_tooltip("14:05", false), "FlareStats – updated 14:05");
+        assert_eq!(
+            refreshed_tooltip("14:05", true),
+            "FlareStats – updated 14:05, some sites failed to load"
+        );
+    }
+
+    /// Alpha of the pixel at `x, y` of a rendered sparkline.
+    fn alpha(pixels: &[u8], width: u32, x: u32, y: u32) -> u8 {
+        pixels[((y * width + x) * 4 + 3) as usize]
+    }
+
+    #[test]
+    fn test_render_sparkline_scales_to_peak() {
+        let pixels = render_sparkline(&[0, 10, 0], 21, 20);
+        assert_eq!(pixels.len(), 21 * 20 * 4);
+        // The peak reaches the top margin in the middle, the ends stay at the bottom
+        assert_eq!(alpha(&pixels, 21, 10, 2), 255);
+        assert_eq!(alpha(&pixels, 21, 10, 0), 0);
+        assert_eq!(alpha(&pixels, 21, 0, 2), 0);
+        assert_eq!(alpha(&pixels, 21, 0, 18), 255);
+        // Shaded below the line
+        assert_eq!(alpha(&pixels, 21, 10, 15), SPARKLINE_FILL_ALPHA);
+        assert!(pixels.chunks(4).all(|p| p[..3] == [0, 0, 0]));
+    }
+
+    #[test]
+    fn test_render_sparkline_without_traffic_is_flat() {
+        let pixels = render_sparkline(&[0; 24], 44, 44);
+        assert!((0..44).all(|x| alpha(&pixels, 44, x, 42) == 255 && alpha(&pixels, 44, x, 30) == 0));
+        assert!(render_sparkline(&[], 44, 44).iter().all(|&b| b == 0));
+    }
+
+    #[test]
+    fn test_hourly_visits_sums_quarter_hours() {
+        let point = |timestamp: &str, visits| SeriesPoint { timestamp: timestamp.to_string(), visits, ..Default::default() };
+        let quarters = [
+            point("2024-01-15T10:30:00+01:00", 1),
+            point("2024-01-15T10:45:00+01:00", 2),
+            point("2024-01-15T11:00:00+01:00", 3),
+            point("2024-01-15T11:15:00+01:00", 4),
+            point("2024-01-15T11:30:00+01:00", 5),
+        ];
+        assert_eq!(hourly_visits(&quarters), vec![3, 12]);
+        let hours = [point("2024-01-15T10:00:00+01:00", 7), point("2024-01-15T11:00:00+01:00", 0)];
+        assert_eq!(hourly_visits(&hours), vec![7, 0]);
+    }
+
+    #[test]
+    fn test_sparkline_site_falls_back_to_first_pinned() {
+        let pinned = Settings { pinned_sites: vec!["b.com".to_string(), "c.com".to_string()], ..Default::default() };
+        assert_eq!(sparkline_site(&pinned), Some("b.com"));
+        let chosen = Settings { tray_site: "a.com".to_string(), ..pinned };
+        assert_eq!(sparkline_site(&chosen), Some("a.com"));
+        assert_eq!(sparkline_site(&Settings::default()), None);
+    }
+
+    #[test]
+    fn test_trend_for_without_previous_data() {
+        assert_eq!(trend_for(&[site_with_previous(50, 0)]), None);
+        assert_eq!(trend_for(&[]), None);
+    }
+}
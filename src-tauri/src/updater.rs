@@ -0,0 +1,172 @@
+//! Background checks for new releases, installed from the tray or the panel.
+
+use crate::commands::{get_settings, parse_interval, root_certificates};
+use crate::errors::FetchError;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::Duration;
+use tauri::menu::MenuItem;
+use tauri::{AppHandle, Emitter, Manager, Wry};
+use tauri_plugin_updater::{Update, UpdaterExt};
+use tracing::warn;
+
+/// Wait a bit after launch so the first check doesn't compete with the initial refresh.
+const FIRST_CHECK_DELAY: Duration = Duration::from_secs(30);
+/// Never poll the release feed more often than this, whatever the settings say.
+const MIN_CHECK_INTERVAL: Duration = Duration::from_secs(3600);
+/// How often to look at the settings again while checks are turned off.
+const DISABLED_POLL: Duration = Duration::from_secs(3600);
+
+/// The newest release found by the last check, ready to install.
+pub struct PendingUpdate(pub Mutex<Option<Update>>);
+
+/// "Install Update" entry in the tray menu, disabled until an update is found.
+pub struct InstallMenuItem(pub Mutex<Option<MenuItem<Wry>>>);
+
+#[derive(Serialize, Clone, Debug, PartialEq)]
+pub struct UpdateInfo {
+    pub version: String,
+    pub current_version: String,
+    pub notes: Option<String>,
+}
+
+impl From<&Update> for UpdateInfo {
+    fn from(update: &Update) -> Self {
+        Self {
+            version: update.version.clone(),
+            current_version: update.current_version.clone(),
+            notes: update.body.clone(),
+        }
+    }
+}
+
+/// Parse `update_check_interval`; "off" or empty disables background checks.
+pub(crate) fn check_interval(value: &str) -> Result<Option<Duration>, String> {
+    match value.trim() {
+        "" | "off" => Ok(None),
+        v => parse_interval(v)
+            .map(|d| Some(d.max(MIN_CHECK_INTERVAL)))
+            .map_err(|_| format!("Invalid update check interval \"{}\": use e.g. 12h, 24h or off", v)),
+    }
+}
+
+fn set_menu_item(app: &AppHandle, text: &str, enabled: bool) {
+    if let Some(item) = app.state::<InstallMenuItem>().0.lock().unwrap().as_ref() {
+        let _ = item.set_text(text);
+        let _ = item.set_enabled(enabled);
+    }
+}
+
+/// Ask the release feed for a newer version. A new find is remembered for `install`,
+/// announced with an `update-available` event and enables the tray entry.
+async fn check(app: &AppHandle) -> Result<Option<UpdateInfo>, String> {
+    let settings = get_settings(app.clone())?;
+    let mut builder = app.updater_builder();
+    let proxy_url = settings.proxy_url.trim();
+    if !proxy_url.is_empty() {
+        let proxy = proxy_url
+            .parse()
+            .map_err(|e| format!("Invalid proxy URL \"{}\": {}", proxy_url, e))?;
+        builder = builder.proxy(proxy);
+    }
+    // Behind a TLS-inspecting proxy the release feed needs the same extra CA as the API
+    let certs = root_certificates(&settings)?;
+    if !certs.is_empty() {
+        builder = builder.configure_client(move |client| {
+            certs.iter().cloned().fold(client, |client, cert| client.add_root_certificate(cert))
+        });
+    }
+    let update = builder
+        .build()
+        .map_err(|e| e.to_string())?
+        .check()
+        .await
+        .map_err(|e| format!("Update check failed: {}", e))?;
+
+    let info = update.as_ref().map(UpdateInfo::from);
+    let previous = std::mem::replace(
+        &mut *app.state::<PendingUpdate>().0.lock().map_err(|e| e.to_string())?,
+        update,
+    )
+    .map(|u| u.version);
+
+    if let Some(info) = &info
+        && previous.as_deref() != Some(info.version.as_str())
+    {
+        set_menu_item(app, &format!("Install Update {}", info.version), true);
+        let _ = app.emit("update-available", info.clone());
+    }
+    Ok(info)
+}
+
+/// Download and install the pending update, then restart into it.
+pub async fn install(app: &AppHandle) -> Result<(), String> {
+    let update = app
+        .state::<PendingUpdate>()
+        .0
+        .lock()
+        .map_err(|e| e.to_string())?
+        .clone()
+        .ok_or("No update available")?;
+
+    set_menu_item(app, "Installing Update…", false);
+    if let Err(e) = update.download_and_install(|_, _| {}, || {}).await {
+        set_menu_item(app, &format!("Install Update {}", update.version), true);
+        return Err(format!("Couldn't install update {}: {}", update.version, e));
+    }
+    app.restart();
+}
+
+/// Check on the `update_check_interval` cadence for as long as the app runs.
+pub fn start(app: &AppHandle) {
+    let app = app.clone();
+    tauri::async_runtime::spawn(async move {
+        tokio::time::sleep(FIRST_CHECK_DELAY).await;
+        loop {
+            let interval = get_settings(app.clone())
+                .ok()
+                .and_then(|s| check_interval(&s.update_check_interval).ok().flatten());
+            let Some(interval) = interval else {
+                tokio::time::sleep(DISABLED_POLL).await;
+                continue;
+            };
+            if let Err(e) = check(&app).await {
+                warn!("{}", e);
+            }
+            tokio::time::sleep(interval).await;
+        }
+    });
+}
+
+#[tauri::command]
+pub async fn check_for_updates(app: AppHandle) -> Result<Option<UpdateInfo>, FetchError> {
+    Ok(check(&app).await?)
+}
+
+#[tauri::command]
+pub async fn install_update(app: AppHandle) -> Result<(), FetchError> {
+    Ok(install(&app).await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_interval_off() {
+        assert_eq!(check_interval("off").unwrap(), None);
+        assert_eq!(check_interval("  ").unwrap(), None);
+    }
+
+    #[test]
+    fn test_check_interval_parses_and_clamps() {
+        assert_eq!(check_interval("24h").unwrap(), Some(Duration::from_secs(86400)));
+        assert_eq!(check_interval("5m").unwrap(), Some(MIN_CHECK_INTERVAL));
+    }
+
+    #[test]
+    fn test_check_interval_invalid() {
+        let err = check_interval("daily").unwrap_err();
+        assert!(err.contains("update check interval"));
+    }
+}
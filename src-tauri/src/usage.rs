@@ -0,0 +1,117 @@
+//! Local-only usage statistics. Counts are kept in `usage.json` in the app data
+//! dir and are never sent anywhere; they only feed `get_usage_insights`.
+
+use crate::commands::get_settings;
+use crate::errors::FetchError;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use tauri::{AppHandle, Manager};
+
+/// Minimum number of period selections before a default is suggested.
+const MIN_PERIOD_SAMPLES: u64 = 10;
+/// Share of selections the favourite period needs before it's suggested.
+const SUGGEST_SHARE: f64 = 0.7;
+
+#[derive(Serialize, Deserialize, Default, Clone)]
+pub struct UsageStats {
+    #[serde(default)]
+    pub panels: HashMap<String, u64>,
+    #[serde(default)]
+    pub periods: HashMap<String, u64>,
+}
+
+#[derive(Serialize, Clone)]
+pub struct UsageInsights {
+    pub stats: UsageStats,
+    /// Period the user picks most often, if it differs from the saved default.
+    pub suggested_period: Option<String>,
+}
+
+fn usage_path(app: &AppHandle) -> Result<PathBuf, String> {
+    let dir = app.path().app_data_dir().map_err(|e| e.to_string())?;
+    fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    Ok(dir.join("usage.json"))
+}
+
+fn load(app: &AppHandle) -> Result<UsageStats, String> {
+    let path = usage_path(app)?;
+    if !path.exists() {
+        return Ok(UsageStats::default());
+    }
+    let data = fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    Ok(serde_json::from_str(&data).unwrap_or_default())
+}
+
+fn suggest_period(periods: &HashMap<String, u64>, current: &str) -> Option<String> {
+    let total: u64 = periods.values().sum();
+    if total < MIN_PERIOD_SAMPLES {
+        return None;
+    }
+    let (top, count) = periods
+        .iter()
+        .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))?;
+    if top == current || (*count as f64) / (total as f64) < SUGGEST_SHARE {
+        return None;
+    }
+    Some(top.clone())
+}
+
+/// Count one usage event, e.g. `("panel", "settings")` or `("period", "7d")`.
+#[tauri::command]
+pub fn record_usage(app: AppHandle, kind: String, value: String) -> Result<(), FetchError> {
+    let mut stats = load(&app)?;
+    let bucket = match kind.as_str() {
+        "panel" => &mut stats.panels,
+        "period" => &mut stats.periods,
+        _ => return Err(format!("Unknown usage kind: {}", kind).into()),
+    };
+    *bucket.entry(value).or_insert(0) += 1;
+    let data = serde_json::to_string_pretty(&stats).map_err(|e| e.to_string())?;
+    fs::write(usage_path(&app)?, data).map_err(|e| e.to_string().into())
+}
+
+#[tauri::command]
+pub fn get_usage_insights(app: AppHandle) -> Result<UsageInsights, FetchError> {
+    let stats = load(&app)?;
+    let settings = get_settings(app)?;
+    let suggested_period = suggest_period(&stats.periods, &settings.period);
+    Ok(UsageInsights {
+        stats,
+        suggested_period,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn periods(entries: &[(&str, u64)]) -> HashMap<String, u64> {
+        entries.iter().map(|(k, v)| (k.to_string(), *v)).collect()
+    }
+
+    #[test]
+    fn test_suggest_period_dominant_choice() {
+        let p = periods(&[("7d", 9), ("24h", 2)]);
+        assert_eq!(suggest_period(&p, "24h"), Some("7d".to_string()));
+    }
+
+    #[test]
+    fn test_suggest_period_skips_current_default() {
+        let p = periods(&[("7d", 9), ("24h", 2)]);
+        assert_eq!(suggest_period(&p, "7d"), None);
+    }
+
+    #[test]
+    fn test_suggest_period_needs_enough_samples() {
+        let p = periods(&[("7d", 5)]);
+        assert_eq!(suggest_period(&p, "24h"), None);
+    }
+
+    #[test]
+    fn test_suggest_period_needs_clear_favourite() {
+        let p = periods(&[("7d", 6), ("30d", 5)]);
+        assert_eq!(suggest_period(&p, "24h"), None);
+    }
+}
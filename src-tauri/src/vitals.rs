@@ -0,0 +1,150 @@
+use crate::budget::metered_client;
+use crate::client::CloudflareClient;
+use crate::commands::{
+    fetch_sites, get_settings, get_time_range, graphql_request, timezone, Settings,
+};
+use crate::errors::FetchError;
+use serde::Serialize;
+use tauri::AppHandle;
+use tracing::warn;
+
+/// p75 Core Web Vitals for one site over the selected period.
+#[derive(Serialize, Clone, Debug, Default, PartialEq)]
+pub struct WebVitals {
+    pub name: String,
+    /// Largest Contentful Paint, milliseconds.
+    pub lcp_p75_ms: Option<f64>,
+    /// Interaction to Next Paint, milliseconds.
+    pub inp_p75_ms: Option<f64>,
+    /// Cumulative Layout Shift, unitless.
+    pub cls_p75: Option<f64>,
+    /// Number of events the quantiles are based on.
+    pub samples: u64,
+}
+
+const VITALS_QUERY: &str = r#"{
+  viewer {
+    accounts(filter: { accountTag: $accountTag }) {
+      vitals: rumWebVitalsEventsAdaptiveGroups(limit: 1, filter: $filter) {
+        count
+        quantiles {
+          largestContentfulPaintP75
+          interactionToNextPaintP75
+          cumulativeLayoutShiftP75
+        }
+      }
+    }
+  }
+}"#;
+
+/// Cloudflare reports timings in microseconds; a zero quantile means no samples.
+fn micros_to_ms(value: &serde_json::Value) -> Option<f64> {
+    value.as_f64().filter(|v| *v > 0.0).map(|v| v / 1000.0)
+}
+
+fn parse_vitals(name: &str, data: &serde_json::Value) -> WebVitals {
+    let group = &data["data"]["viewer"]["accounts"][0]["vitals"][0];
+    let quantiles = &group["quantiles"];
+    WebVitals {
+        name: name.to_string(),
+        lcp_p75_ms: micros_to_ms(&quantiles["largestContentfulPaintP75"]),
+        inp_p75_ms: micros_to_ms(&quantiles["interactionToNextPaintP75"]),
+        cls_p75: quantiles["cumulativeLayoutShiftP75"].as_f64(),
+        samples: group["count"].as_u64().unwrap_or(0),
+    }
+}
+
+async fn fetch_site_vitals(
+    client: &impl CloudflareClient,
+    settings: &Settings,
+    name: &str,
+    site_tag: &str,
+) -> Result<WebVitals, FetchError> {
+    let (start, end, _) = get_time_range(&settings.period, timezone(settings));
+    let mut filters = vec![
+        serde_json::json!({ "datetime_geq": start, "datetime_leq": end }),
+        serde_json::json!({ "siteTag": site_tag }),
+    ];
+    if settings.exclude_bots {
+        filters.push(serde_json::json!({ "bot": 0 }));
+    }
+    filters.extend(settings.filters.clauses());
+    let variables = serde_json::json!({
+        "accountTag": settings.account_id,
+        "filter": { "AND": filters },
+    });
+
+    let data = graphql_request(client, &settings.credentials(), VITALS_QUERY, variables).await?;
+    Ok(parse_vitals(name, &data))
+}
+
+#[tauri::command]
+pub async fn fetch_web_vitals(app: AppHandle) -> Result<Vec<WebVitals>, FetchError> {
+    let settings = get_settings(app.clone())?;
+    if !settings.has_credentials() {
+        return Err(FetchError::missing_credentials());
+    }
+
+    let client = metered_client(&app, &settings)?;
+    let sites = fetch_sites(&client, &settings.credentials(), &settings.account_id).await?;
+    let futures = sites
+        .iter()
+        .map(|(name, site_tag)| fetch_site_vitals(&client, &settings, name, site_tag));
+
+    let mut vitals = Vec::new();
+    for (result, (name, _)) in futures::future::join_all(futures).await.into_iter().zip(&sites) {
+        match result {
+            Ok(v) => vitals.push(v),
+            Err(e) => warn!(site = %name, error = %e, "Error fetching web vitals"),
+        }
+    }
+    vitals.sort_by_key(|v| std::cmp::Reverse(v.samples));
+    Ok(vitals)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_vitals_converts_timings_to_ms() {
+        let data = json!({"data": {"viewer": {"accounts": [{"vitals": [{
+            "count": 420,
+            "quantiles": {
+                "largestContentfulPaintP75": 2_450_000.0,
+                "interactionToNextPaintP75": 180_000,
+                "cumulativeLayoutShiftP75": 0.04
+            }
+        }]}]}}});
+        let vitals = parse_vitals("a.com", &data);
+        assert_eq!(vitals.lcp_p75_ms, Some(2450.0));
+        assert_eq!(vitals.inp_p75_ms, Some(180.0));
+        assert_eq!(vitals.cls_p75, Some(0.04));
+        assert_eq!(vitals.samples, 420);
+    }
+
+    #[test]
+    fn test_parse_vitals_without_data() {
+        let data = json!({"data": {"viewer": {"accounts": [{"vitals": []}]}}});
+        assert_eq!(
+            parse_vitals("a.com", &data),
+            WebVitals {
+                name: "a.com".to_string(),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[test]
+    fn test_parse_vitals_zero_timing_means_missing() {
+        let data = json!({"data": {"viewer": {"accounts": [{"vitals": [{
+            "count": 3,
+            "quantiles": {"largestContentfulPaintP75": 1_200_000, "interactionToNextPaintP75": 0}
+        }]}]}}});
+        let vitals = parse_vitals("a.com", &data);
+        assert_eq!(vitals.lcp_p75_ms, Some(1200.0));
+        assert_eq!(vitals.inp_p75_ms, None);
+        assert_eq!(vitals.cls_p75, None);
+    }
+}
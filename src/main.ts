@@ -2,38 +2,233 @@ import { invoke } from "@tauri-apps/api/core";
 import { listen } from "@tauri-apps/api/event";
 import Chart from "chart.js/auto";
 import type { TooltipModel } from "chart.js";
-import { formatNumber, formatTimestamp, escapeAttr } from "./utils";
+import { formatNumber, formatChange, formatTimestamp, escapeAttr, vitalRating, formatVital, errorMessage } from "./utils";
+import type { VitalMetric } from "./utils";
 
 interface Settings {
   token: string;
+  auth_method?: "token" | "api_key";
+  email?: string;
   account_id: string;
   period: string;
   exclude_bots: boolean;
+  split_bots?: boolean;
   theme: string;
   refresh_interval: string;
+  site_refresh_intervals?: Record<string, string>;
+  tray_title: string;
+  tray_site: string;
+  tray_sparkline?: boolean;
+  refresh_hook: string;
+  account_styles?: Record<string, AccountStyle>;
+  low_data_mode?: string;
+  proxy_url?: string;
+  ca_cert_path?: string;
+  launch_at_login?: boolean;
+  global_shortcut?: string;
+  update_check_interval?: string;
+  timezone?: string;
+  show_all_sites?: boolean;
+  hidden_sites?: string[];
+  pinned_sites?: string[];
+  quarter_hour_24h?: boolean;
+  sort_by?: string;
+  sort_direction?: string;
+  filters?: DimensionFilters;
+}
+
+interface DimensionFilters {
+  country: string;
+  device_type: string;
+  host: string;
+}
+
+interface AccountStyle {
+  label: string;
+  color: string;
 }
 
 interface SeriesPoint {
   timestamp: string;
   visits: number;
   page_views: number;
+  bot_visits: number | null;
+  bot_page_views: number | null;
+  /** Days in a week bucket cut short by the range, null for whole weeks. */
+  days: number | null;
 }
 
 interface SiteData {
   name: string;
   visits: number;
   page_views: number;
+  prev_visits: number;
+  prev_page_views: number;
+  visits_change: number | null;
+  page_views_change: number | null;
   series: SeriesPoint[];
+  account: AccountStyle | null;
+  aggregate: boolean;
+  pinned: boolean;
+  bot_visits: number | null;
+  bot_page_views: number | null;
+  // The chart is missing data past the API's row limit; totals are complete
+  truncated?: boolean;
+}
+
+interface WebVitals {
+  name: string;
+  lcp_p75_ms: number | null;
+  inp_p75_ms: number | null;
+  cls_p75: number | null;
+  samples: number;
+}
+
+interface ZoneSecurity {
+  name: string;
+  blocked: number;
+  challenged: number;
+  series: { timestamp: string; blocked: number; challenged: number }[];
+  spike: boolean;
+}
+
+interface QueryResult {
+  columns: string[];
+  rows: (string | number | null)[][];
+  truncated: boolean;
+}
+
+interface DimensionCount {
+  value: string;
+  visits: number;
+}
+
+interface Breakdown {
+  countries: DimensionCount[];
+  referrers: DimensionCount[];
+  paths: DimensionCount[];
+}
+
+interface AnalyticsSnapshot {
+  // Unix milliseconds of the fetch itself, earlier than the call for a cached result
+  fetched_at: number;
+  period: string;
+  sites: SiteData[];
+  // Some sites failed to load and are missing
+  partial: boolean;
+  // Cloudflare couldn't be reached; this is the last saved fetch
+  offline: boolean;
+}
+
+interface SnapshotView extends AnalyticsSnapshot {
+  index: number;
+  total: number;
+}
+
+interface InitialPayload {
+  settings: Settings;
+  analytics: AnalyticsSnapshot | null;
+  needs_setup: boolean;
+  status: {
+    background_refresh: boolean;
+    last_refreshed_at: number | null;
+  };
+}
+
+interface TimeRangePreview {
+  start: string;
+  end: string;
+  bucket: "15m" | "hour" | "day" | "week";
+  buckets: number;
+  timezone: string;
+  description: string;
+}
+
+interface RefreshStatus {
+  running: boolean;
+  paused: boolean;
+  auto_paused: boolean;
+  next_run_at: number | null;
+  last_run: { at: number; ok: boolean; error: FetchError | null } | null;
+}
+
+interface ApiUsage {
+  calls: number;
+  window_secs: number;
+  limit: number;
+  throttled: boolean;
+}
+
+interface SelfTestReport {
+  ok: boolean;
+  steps: { name: string; status: "pass" | "fail" | "skip"; detail: string; duration_ms: number }[];
+}
+
+// What every command rejects with
+type FetchError =
+  | { kind: "auth" | "timeout" | "network" | "parse" | "other"; message: string }
+  | { kind: "rate_limit"; message: string; retry_after_secs: number | null }
+  | { kind: "graphql"; message: string; errors: unknown[] }
+  | { kind: "api"; message: string; status: number };
+
+interface AnalyticsErrorEvent {
+  site: string | null;
+  error: FetchError;
+}
+
+interface UpdateInfo {
+  version: string;
+  current_version: string;
+  notes: string | null;
+}
+
+interface Account {
+  id: string;
+  name: string;
+}
+
+interface CredentialCheck {
+  status: "valid" | "invalid" | "expired" | "wrong_account" | "missing_scope";
+  message: string;
 }
 
 const app = document.getElementById("app")!;
+// Set in the detached dashboard window, see open_dashboard
+const isDashboardWindow = new URLSearchParams(location.search).get("view") === "dashboard";
+document.documentElement.classList.toggle("dashboard-window", isDashboardWindow);
 let charts: Chart[] = [];
 let cachedData: SiteData[] | null = null;
+// cachedData came from disk after a restart and a fresh fetch hasn't landed yet.
+let cachedDataStale = false;
+// Sites of the fetch in progress, rendered as they arrive while there is nothing else to show.
+let arrivingSites: SiteData[] | null = null;
 let isLoading = false;
 let systemDarkQuery = window.matchMedia("(prefers-color-scheme: dark)");
 let currentTheme = "auto";
 let lastRefreshedAt: number | null = null;
+// The data on screen is missing sites that failed to load.
+let lastFetchPartial = false;
+// The machine is offline and the data on screen is the last saved fetch.
+let lastFetchOffline = false;
 let refreshAgoTimer: number | null = null;
+let liveTimer: number | null = null;
+const liveVisitors = new Map<string, number>();
+let refreshError: FetchError | null = null;
+const siteErrors = new Map<string, FetchError>();
+// Sites loaded and failed in the latest fetch, for "3 of 52" in the error banner.
+let fetchSummary: { loaded: number; failed: number } | null = null;
+let activeTab: "traffic" | "performance" | "security" | "query" = "traffic";
+// Kept across panel re-renders so switching views doesn't lose the query being written
+let lastQuery = "";
+// Which kept refresh is on screen: 0 is the latest, higher is older.
+let snapshotIndex = 0;
+let snapshotTotal = 0;
+let snapshotFetchedAt: number | null = null;
+let snapshotPartial = false;
+const expandedSites = new Set<string>();
+// Site a flarestats:// link asked for, scrolled to once the next fetch lands
+let focusSite: string | null = null;
+let availableUpdate: UpdateInfo | null = null;
 
 function applyTheme(theme: string) {
   currentTheme = theme;
@@ -84,6 +279,10 @@ async function startBackgroundRefresh() {
   } catch { /* ignore */ }
 }
 
+function recordUsage(kind: string, value: string) {
+  invoke("record_usage", { kind, value }).catch(() => { /* ignore */ });
+}
+
 function updateRefreshAgo() {
   const el = document.querySelector(".refresh-ago");
   if (!el) return;
@@ -93,12 +292,25 @@ function updateRefreshAgo() {
   }
   const diff = Date.now() - lastRefreshedAt;
   const mins = Math.floor(diff / 60_000);
+  let ago: string;
   if (mins < 1) {
-    el.textContent = "now";
+    ago = "now";
   } else if (mins < 60) {
-    el.textContent = `${mins}m ago`;
+    ago = `${mins}m ago`;
   } else {
-    el.textContent = `${Math.floor(mins / 60)}h ago`;
+    ago = `${Math.floor(mins / 60)}h ago`;
+  }
+  if (lastFetchOffline) {
+    el.textContent = `${ago} · offline`;
+  } else {
+    el.textContent = lastFetchPartial ? `${ago} · partial` : ago;
+  }
+  el.classList.toggle("partial", lastFetchPartial && !lastFetchOffline);
+  el.classList.toggle("offline", lastFetchOffline);
+  if (lastFetchOffline) {
+    (el as HTMLElement).title = "Offline, showing the last saved results; refreshes resume when the connection is back";
+  } else {
+    (el as HTMLElement).title = lastFetchPartial ? "Some sites failed to load and are missing" : "";
   }
 }
 
@@ -108,6 +320,33 @@ function startRefreshAgoTimer() {
   refreshAgoTimer = window.setInterval(updateRefreshAgo, 5_000);
 }
 
+async function updateLiveVisitors() {
+  try {
+    const live = await invoke<{ name: string; visits: number }[]>("fetch_live_visitors");
+    live.forEach((site) => liveVisitors.set(site.name, site.visits));
+  } catch { /* keep the last known numbers */ }
+  document.querySelectorAll<HTMLElement>("[data-live]").forEach((el) => {
+    const visits = liveVisitors.get(el.dataset.live!);
+    el.textContent = visits === undefined ? "–" : formatNumber(visits);
+  });
+}
+
+function startLiveTimer() {
+  stopLiveTimer();
+  updateLiveVisitors();
+  // only poll while the panel is open; the focus handler catches up on reopen
+  liveTimer = window.setInterval(() => {
+    if (document.hasFocus()) updateLiveVisitors();
+  }, 60_000);
+}
+
+function stopLiveTimer() {
+  if (liveTimer !== null) {
+    clearInterval(liveTimer);
+    liveTimer = null;
+  }
+}
+
 function stopRefreshAgoTimer() {
   if (refreshAgoTimer !== null) {
     clearInterval(refreshAgoTimer);
@@ -116,42 +355,146 @@ function stopRefreshAgoTimer() {
 }
 
 async function init() {
-  const settings = await invoke<Settings>("get_settings");
-  applyTheme(settings.theme || "auto");
-  await startBackgroundRefresh();
-  if (!settings.token || !settings.account_id) {
+  const payload = await invoke<InitialPayload>("get_initial_payload");
+  applyTheme(payload.settings.theme || "auto");
+  if (payload.analytics) {
+    cachedData = payload.analytics.sites;
+    lastRefreshedAt = payload.analytics.fetched_at;
+    lastFetchPartial = payload.analytics.partial;
+  } else if (!payload.needs_setup) {
+    const saved = await invoke<AnalyticsSnapshot | null>("get_cached_analytics").catch(() => null);
+    if (saved) {
+      cachedData = saved.sites;
+      cachedDataStale = true;
+      lastRefreshedAt = saved.fetched_at;
+      lastFetchPartial = saved.partial;
+    }
+  }
+  if (!payload.status.background_refresh) {
+    await startBackgroundRefresh();
+  }
+  if (payload.needs_setup) {
     showSettings();
   } else {
     showDashboard();
   }
 
   // NSPanel doesn't trigger Tauri's onFocusChanged, use DOM focus event
-  window.addEventListener("focus", () => {
+  window.addEventListener("focus", async () => {
     (document.activeElement as HTMLElement)?.blur();
+    try {
+      const status = await invoke<RefreshStatus>("refresh_status");
+      if (status.last_run?.ok && status.last_run.at > (lastRefreshedAt ?? 0)) {
+        lastRefreshedAt = status.last_run.at;
+      }
+    } catch { /* keep the locally tracked time */ }
     updateRefreshAgo();
+    if (liveTimer !== null) updateLiveVisitors();
+  });
+
+  listen<AnalyticsErrorEvent>("analytics-error", (event) => {
+    if (event.payload.site) {
+      siteErrors.set(event.payload.site, event.payload.error);
+    } else {
+      refreshError = event.payload.error;
+    }
+    renderErrorBanner();
+  });
+
+  listen<SiteData>("analytics-site-loaded", (event) => {
+    if (!arrivingSites || snapshotIndex !== 0) return;
+    arrivingSites.push(event.payload);
+    arrivingSites.sort((a, b) => Number(b.pinned) - Number(a.pinned) || b.visits - a.visits);
+    renderSites(arrivingSites);
   });
 
-  listen<SiteData[]>("analytics-refreshed", (event) => {
-    cachedData = event.payload;
-    lastRefreshedAt = Date.now();
+  // Going offline keeps the data on screen; coming back is followed by `analytics-refreshed`
+  listen<{ online: boolean }>("connectivity-changed", (event) => {
+    lastFetchOffline = !event.payload.online;
+    if (lastFetchOffline) {
+      refreshError = null;
+      renderErrorBanner();
+    }
+    updateRefreshAgo();
+  });
+
+  listen<{ loaded: number; failed: number }>("analytics-summary", (event) => {
+    fetchSummary = event.payload;
+    renderErrorBanner();
+  });
+
+  listen<AnalyticsSnapshot>("analytics-refreshed", (event) => {
+    cachedData = event.payload.sites;
+    cachedDataStale = false;
+    lastRefreshedAt = event.payload.fetched_at;
+    lastFetchPartial = event.payload.partial;
+    lastFetchOffline = event.payload.offline;
+    refreshError = null;
+    event.payload.sites.forEach((site) => siteErrors.delete(site.name));
+    renderErrorBanner();
     const content = document.getElementById("dashboard-content");
-    if (content) {
-      renderSites(event.payload);
+    if (content && snapshotIndex === 0) {
+      renderSites(event.payload.sites);
     }
     updateRefreshAgo();
   });
 
+  // A new refresh pushes everything back by one; keep showing the same snapshot
+  listen<{ total: number }>("snapshots-changed", (event) => {
+    snapshotTotal = event.payload.total;
+    if (snapshotIndex > 0 && snapshotIndex + 1 >= snapshotTotal) {
+      // The snapshot on screen was dropped, fall back to the oldest kept one
+      showSnapshot(snapshotTotal - 1);
+      return;
+    }
+    if (snapshotIndex > 0) snapshotIndex += 1;
+    renderReplayBar();
+  });
+
+  window.addEventListener("keydown", (e) => {
+    if (!document.getElementById("replay-bar") || e.target instanceof HTMLInputElement) return;
+    if (e.key === "ArrowLeft") showSnapshot(snapshotIndex + 1);
+    if (e.key === "ArrowRight") showSnapshot(snapshotIndex - 1);
+  });
+
+  listen<{ site: string | null; period: string | null }>("deep-link", (event) => {
+    focusSite = event.payload.site;
+    if (focusSite) expandedSites.add(focusSite);
+    showDashboard();
+  });
+
+  listen<UpdateInfo>("update-available", (event) => {
+    availableUpdate = event.payload;
+    renderUpdateBanner();
+  });
+
+  listen<string>("update-error", (event) => renderUpdateBanner(`Update failed: ${event.payload}`));
+
   listen("open-settings", () => showSettings());
 }
 
 function showDashboard() {
   destroyCharts();
   startRefreshAgoTimer();
+  startLiveTimer();
+  recordUsage("panel", "dashboard");
 
   app.innerHTML = popover(`
     <div class="header">
       <h1>FlareStats</h1>
       <div class="header-actions">
+        <button class="icon-btn" id="prev-snapshot-btn" title="Previous refresh (←)">
+          <svg width="14" height="14" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5">
+            <path d="M10 3L5 8l5 5"/>
+          </svg>
+        </button>
+        ${isDashboardWindow ? "" : `<button class="icon-btn" id="detach-btn" title="Open in a separate window">
+          <svg width="14" height="14" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5">
+            <path d="M9 2.5h4.5V7"/>
+            <path d="M13.5 2.5L7.5 8.5"/>
+            <path d="M12 10v3.5H2.5V4H6"/>
+          </svg>
+        </button>`}
         <button class="icon-btn" id="refresh-btn" title="Refresh">
           <span class="refresh-ago"></span>
           <svg width="14" height="14" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5">
@@ -167,20 +510,297 @@ function showDashboard() {
         </button>
       </div>
     </div>
-    <div class="content" id="dashboard-content">
-      <div class="loading">
-        <div class="spinner"></div>
-        <div class="loading-text">Loading analytics...</div>
+    <div class="tab-bar period-selector">
+      <button class="period-btn" data-tab="traffic">Traffic</button>
+      <button class="period-btn" data-tab="performance">Performance</button>
+      <button class="period-btn" data-tab="security">Security</button>
+      <button class="period-btn" data-tab="query" title="Run SQL over the latest results">SQL</button>
+    </div>
+    <div class="error-banner" id="security-banner" hidden></div>
+    <div class="replay-bar" id="replay-bar" hidden>
+      <button class="icon-btn" id="replay-newer-btn" title="Newer refresh (→)">
+        <svg width="12" height="12" viewBox="0 0 16 16" fill="none" stroke="currentColor" stroke-width="1.5">
+          <path d="M6 3l5 5-5 5"/>
+        </svg>
+      </button>
+      <span id="replay-label"></span>
+      <button class="btn-link" id="replay-latest-btn">Latest</button>
+    </div>
+    <div class="update-banner" id="update-banner" hidden></div>
+    <div class="error-banner" id="error-banner" hidden></div>
+    <div class="content" id="dashboard-content">${skeletonCards()}</div>
+    <div class="content" id="performance-content" hidden>${skeletonCards()}</div>
+    <div class="content" id="security-content" hidden>${skeletonCards()}</div>
+    <div class="content" id="query-content" hidden>
+      <textarea class="query-input" id="query-input" spellcheck="false" placeholder="SELECT name, visits FROM sites ORDER BY visits DESC">${escapeHtml(lastQuery)}</textarea>
+      <div class="query-actions">
+        <span class="query-hint">Tables: sites(name, visits, page_views, prev_visits, prev_page_views), series(site, timestamp, visits, page_views)</span>
+        <button class="period-btn" id="query-run-btn" title="Run (⌘↩)">Run</button>
       </div>
+      <div id="query-result"></div>
     </div>
   `);
+  renderErrorBanner();
+  renderUpdateBanner();
+  snapshotIndex = 0;
+  renderReplayBar();
+
+  document.getElementById("prev-snapshot-btn")!.addEventListener("click", () => showSnapshot(snapshotIndex + 1));
+  document.getElementById("replay-newer-btn")!.addEventListener("click", () => showSnapshot(snapshotIndex - 1));
+  document.getElementById("replay-latest-btn")!.addEventListener("click", () => showSnapshot(0));
+
+  document.querySelectorAll<HTMLButtonElement>("[data-tab]").forEach((btn) => {
+    btn.addEventListener("click", () => showTab(btn.dataset.tab as typeof activeTab));
+  });
+  showTab(activeTab);
+
+  document.getElementById("query-run-btn")!.addEventListener("click", runQuery);
+  document.getElementById("query-input")!.addEventListener("keydown", (e) => {
+    if (e.key === "Enter" && (e.metaKey || e.ctrlKey)) {
+      e.preventDefault();
+      runQuery();
+    }
+  });
 
   document.getElementById("settings-btn")!.addEventListener("click", showSettings);
+  document.getElementById("detach-btn")?.addEventListener("click", (event) => {
+    const btn = event.currentTarget as HTMLButtonElement;
+    recordUsage("panel", "detach");
+    invoke("open_dashboard").catch((e) => { btn.title = errorMessage(e); });
+  });
   document.getElementById("refresh-btn")!.addEventListener("click", () => {
-    loadAnalytics();
+    loadAnalytics(true);
+    loadSecurityEvents();
   });
 
+  if (cachedData) {
+    renderSites(cachedData);
+  }
   loadAnalytics();
+  loadSecurityEvents();
+}
+
+async function showSnapshot(index: number) {
+  if (index < 0 || (snapshotTotal > 0 && index >= snapshotTotal)) return;
+  try {
+    const view = await invoke<SnapshotView>("get_snapshot_at", { index });
+    snapshotIndex = view.index;
+    snapshotTotal = view.total;
+    snapshotFetchedAt = view.fetched_at;
+    snapshotPartial = view.partial;
+    renderSites(view.sites);
+    renderReplayBar();
+  } catch {
+    // Nothing kept at that index (yet)
+  }
+}
+
+function renderReplayBar() {
+  const bar = document.getElementById("replay-bar");
+  const prev = document.getElementById("prev-snapshot-btn") as HTMLButtonElement | null;
+  if (prev) prev.disabled = snapshotTotal > 0 && snapshotIndex >= snapshotTotal - 1;
+  if (!bar) return;
+
+  bar.hidden = snapshotIndex === 0 || snapshotFetchedAt === null;
+  if (!bar.hidden) {
+    const time = new Date(snapshotFetchedAt!).toLocaleTimeString([], { hour: "2-digit", minute: "2-digit" });
+    document.getElementById("replay-label")!.textContent =
+      `As of ${time}${snapshotPartial ? " (partial)" : ""} · ${snapshotIndex} of ${snapshotTotal - 1} back`;
+  }
+}
+
+function showTab(tab: typeof activeTab) {
+  activeTab = tab;
+  document.querySelectorAll<HTMLButtonElement>("[data-tab]").forEach((btn) => {
+    btn.classList.toggle("active", btn.dataset.tab === tab);
+  });
+  document.getElementById("dashboard-content")!.hidden = tab !== "traffic";
+  document.getElementById("performance-content")!.hidden = tab !== "performance";
+  document.getElementById("security-content")!.hidden = tab !== "security";
+  document.getElementById("query-content")!.hidden = tab !== "query";
+  if (tab === "query") recordUsage("panel", "query");
+  if (tab === "performance") {
+    recordUsage("panel", "performance");
+    loadWebVitals();
+  }
+  if (tab === "security") {
+    recordUsage("panel", "security");
+    loadSecurityEvents();
+  }
+}
+
+async function runQuery() {
+  const input = document.getElementById("query-input") as HTMLTextAreaElement | null;
+  const output = document.getElementById("query-result");
+  if (!input || !output) return;
+  lastQuery = input.value;
+  if (!lastQuery.trim()) return;
+  output.innerHTML = `<div class="query-status">Running…</div>`;
+  try {
+    renderQueryResult(await invoke<QueryResult>("query_snapshot", { sql: lastQuery }));
+  } catch (e) {
+    output.innerHTML = `<div class="error"><div class="error-message">${escapeHtml(errorMessage(e))}</div></div>`;
+  }
+}
+
+function renderQueryResult(result: QueryResult) {
+  const output = document.getElementById("query-result");
+  if (!output) return;
+  const cell = (value: string | number | null) => value === null ? `<td class="null">NULL</td>` : `<td>${escapeHtml(String(value))}</td>`;
+  const count = `${result.rows.length} row${result.rows.length === 1 ? "" : "s"}${result.truncated ? ", cut off" : ""}`;
+  output.innerHTML = `
+    <div class="query-status">${count}</div>
+    ${result.columns.length ? `<div class="query-table-wrap"><table class="query-table">
+      <thead><tr>${result.columns.map((c) => `<th>${escapeHtml(c)}</th>`).join("")}</tr></thead>
+      <tbody>${result.rows.map((row) => `<tr>${row.map(cell).join("")}</tr>`).join("")}</tbody>
+    </table></div>` : ""}
+  `;
+}
+
+async function loadSecurityEvents() {
+  const content = document.getElementById("security-content");
+  if (!content) return;
+  try {
+    renderSecurityEvents(await invoke<ZoneSecurity[]>("fetch_security_events"));
+  } catch (e) {
+    content.innerHTML = `<div class="error"><div class="error-message">${escapeHtml(errorMessage(e))}</div></div>`;
+  }
+}
+
+function renderSecurityEvents(zones: ZoneSecurity[]) {
+  const banner = document.getElementById("security-banner");
+  if (banner) {
+    const spiking = zones.filter((z) => z.spike).map((z) => z.name);
+    banner.hidden = spiking.length === 0;
+    banner.textContent = `WAF activity spike on ${spiking.join(", ")}`;
+  }
+
+  const content = document.getElementById("security-content");
+  if (!content) return;
+  if (zones.length === 0) {
+    content.innerHTML = `<div class="empty">No zones found.</div>`;
+    return;
+  }
+
+  content.innerHTML = zones.map((zone) => `
+    <div class="site-card">
+      <div class="site-header">
+        <span class="site-name">${escapeHtml(zone.name)}${zone.spike ? ` <span class="site-change down">spike</span>` : ""}</span>
+        <div class="site-stats">
+          <div class="stat">
+            <span class="stat-value blocked">${formatNumber(zone.blocked)}</span>
+            <span class="stat-label">Blocked</span>
+          </div>
+          <div class="stat">
+            <span class="stat-value challenged">${formatNumber(zone.challenged)}</span>
+            <span class="stat-label">Challenged</span>
+          </div>
+        </div>
+      </div>
+    </div>
+  `).join("");
+}
+
+async function loadWebVitals() {
+  const content = document.getElementById("performance-content");
+  if (!content) return;
+  try {
+    renderWebVitals(await invoke<WebVitals[]>("fetch_web_vitals"));
+  } catch (e) {
+    content.innerHTML = `<div class="error"><div class="error-message">${escapeHtml(errorMessage(e))}</div></div>`;
+  }
+}
+
+function renderWebVitals(vitals: WebVitals[]) {
+  const content = document.getElementById("performance-content");
+  if (!content) return;
+
+  if (vitals.length === 0) {
+    content.innerHTML = `<div class="empty">No sites found.</div>`;
+    return;
+  }
+
+  const stat = (metric: VitalMetric, value: number | null, label: string) => `
+    <div class="stat">
+      <span class="stat-value vital ${vitalRating(metric, value)}">${formatVital(metric, value)}</span>
+      <span class="stat-label">${label}</span>
+    </div>
+  `;
+
+  content.innerHTML = vitals.map((v) => `
+    <div class="site-card">
+      <div class="site-header">
+        <span class="site-name" title="${formatNumber(v.samples)} samples">${escapeHtml(v.name)}</span>
+        <div class="site-stats">
+          ${stat("lcp", v.lcp_p75_ms, "LCP")}
+          ${stat("inp", v.inp_p75_ms, "INP")}
+          ${stat("cls", v.cls_p75, "CLS")}
+        </div>
+      </div>
+    </div>
+  `).join("");
+}
+
+function renderErrorBanner() {
+  const banner = document.getElementById("error-banner");
+  if (!banner) return;
+
+  let text = "";
+  if (refreshError) {
+    if (refreshError.kind === "auth") {
+      text = "Cloudflare rejected the API token. Check it in settings.";
+    } else if (refreshError.kind === "rate_limit") {
+      text = refreshError.retry_after_secs
+        ? `Rate limited by Cloudflare for ${refreshError.retry_after_secs}s, showing the last data.`
+        : "Rate limited by Cloudflare, showing the last data.";
+    } else {
+      text = `Refresh failed: ${refreshError.message}`;
+    }
+  } else if (siteErrors.size > 0) {
+    const names = [...siteErrors.keys()].join(", ");
+    text = fetchSummary?.failed
+      ? `Couldn't refresh ${fetchSummary.failed} of ${fetchSummary.loaded + fetchSummary.failed} sites: ${names}`
+      : `Couldn't refresh ${names}`;
+  }
+
+  banner.hidden = !text;
+  banner.textContent = text;
+  banner.title = refreshError?.message ?? [...siteErrors.values()].map((e) => e.message).join("\n");
+}
+
+function renderUpdateBanner(error?: string) {
+  const banner = document.getElementById("update-banner");
+  if (!banner) return;
+  banner.hidden = !availableUpdate;
+  if (!availableUpdate) return;
+
+  banner.innerHTML = `
+    <span>${escapeHtml(error ?? `FlareStats ${availableUpdate.version} is available`)}</span>
+    <button class="btn-link" id="install-update-btn">Install</button>
+  `;
+  banner.title = availableUpdate.notes ?? "";
+  document.getElementById("install-update-btn")!.addEventListener("click", async (e) => {
+    const btn = e.target as HTMLButtonElement;
+    btn.disabled = true;
+    btn.textContent = "Installing…";
+    try {
+      await invoke("install_update");
+    } catch (err) {
+      renderUpdateBanner(`Update failed: ${errorMessage(err)}`);
+    }
+  });
+}
+
+function skeletonCards(count = 3): string {
+  return Array.from({ length: count }, () => `
+    <div class="site-card skeleton">
+      <div class="site-header">
+        <div class="skeleton-line name"></div>
+        <div class="skeleton-line stats"></div>
+      </div>
+      <div class="skeleton-chart"></div>
+    </div>
+  `).join("");
 }
 
 function setRefreshing(active: boolean) {
@@ -188,40 +808,50 @@ function setRefreshing(active: boolean) {
   if (btn) btn.classList.toggle("refreshing", active);
 }
 
-async function loadAnalytics() {
+async function loadAnalytics(force = false) {
   if (isLoading) return;
   const content = document.getElementById("dashboard-content");
   if (!content) return;
 
   if (!cachedData) {
-    content.innerHTML = `
-      <div class="loading">
-        <div class="spinner"></div>
-        <div class="loading-text">Loading analytics...</div>
-      </div>
-    `;
+    content.innerHTML = skeletonCards();
+    arrivingSites = [];
   }
 
   isLoading = true;
   setRefreshing(true);
   try {
-    const data = await invoke<SiteData[]>("fetch_analytics");
-    cachedData = data;
-    lastRefreshedAt = Date.now();
-    renderSites(data);
+    const data = await invoke<AnalyticsSnapshot>("fetch_analytics", { force });
+    cachedData = data.sites;
+    cachedDataStale = false;
+    lastRefreshedAt = data.fetched_at;
+    lastFetchPartial = data.partial;
+    lastFetchOffline = data.offline;
+    if (force) {
+      snapshotIndex = 0;
+      renderReplayBar();
+    }
+    if (snapshotIndex === 0) renderSites(data.sites);
+    focusSite = null;
     updateRefreshAgo();
   } catch (e) {
     if (!cachedData) {
+      // A bad token needs the settings; anything else may go away on its own
+      const needsSettings = (e as FetchError).kind === "auth";
       content.innerHTML = `
         <div class="error">
-          <div class="error-message">${escapeHtml(String(e))}</div>
-          <button class="btn btn-secondary" id="error-settings-btn">Open Settings</button>
+          <div class="error-message">${escapeHtml(errorMessage(e))}</div>
+          ${needsSettings
+            ? `<button class="btn btn-secondary" id="error-settings-btn">Open Settings</button>`
+            : `<button class="btn btn-secondary" id="error-retry-btn">Retry</button>`}
         </div>
       `;
       document.getElementById("error-settings-btn")?.addEventListener("click", showSettings);
+      document.getElementById("error-retry-btn")?.addEventListener("click", () => loadAnalytics(true));
     }
   } finally {
     isLoading = false;
+    arrivingSites = null;
     setRefreshing(false);
   }
 }
@@ -231,6 +861,8 @@ function renderSites(sites: SiteData[]) {
   if (!content) return;
 
   destroyCharts();
+  content.classList.toggle("stale", cachedDataStale && snapshotIndex === 0);
+  content.title = content.classList.contains("stale") ? "From the last session, not refreshed yet" : "";
 
   if (sites.length === 0) {
     content.innerHTML = `<div class="empty">No sites found.</div>`;
@@ -238,10 +870,14 @@ function renderSites(sites: SiteData[]) {
   }
 
   content.innerHTML = `<div id="sites-inner">${sites.map((site, i) => `
-    <div class="site-card">
-      <div class="site-header">
-        <span class="site-name">${escapeHtml(site.name)}</span>
+    <div class="site-card${site.aggregate ? " overview" : ""}" ${site.aggregate ? "" : `data-site="${escapeAttr(site.name)}"`}>
+      <div class="site-header" ${site.aggregate ? "" : `title="Show top countries, referrers and paths"`}>
+        <span class="site-name">${accountTag(site.account)}${site.pinned ? `<span class="pin-mark" title="Pinned">★</span> ` : ""}${escapeHtml(site.name)}${changeBadge(site.visits_change)}</span>
         <div class="site-stats">
+          ${site.aggregate ? "" : `<div class="stat">
+            <span class="stat-value live" data-live="${escapeAttr(site.name)}">${liveVisitors.has(site.name) ? formatNumber(liveVisitors.get(site.name)!) : "–"}</span>
+            <span class="stat-label">Now</span>
+          </div>`}
           <div class="stat">
             <span class="stat-value visits">${formatNumber(site.visits)}</span>
             <span class="stat-label">Visits</span>
@@ -250,14 +886,36 @@ function renderSites(sites: SiteData[]) {
             <span class="stat-value pageviews">${formatNumber(site.page_views)}</span>
             <span class="stat-label">Views</span>
           </div>
+          ${site.bot_visits == null ? "" : `<div class="stat" title="${formatNumber(site.bot_page_views ?? 0)} bot page views">
+            <span class="stat-value bots">${formatNumber(site.bot_visits)}</span>
+            <span class="stat-label">Bots</span>
+          </div>`}
         </div>
       </div>
-      <div class="site-chart">
+      <div class="site-chart${site.truncated ? " truncated" : ""}" ${site.truncated ? `title="Too many data points for one query, the chart is missing some of them"` : ""}>
         <canvas id="chart-${i}"></canvas>
       </div>
+      <div class="site-breakdown" hidden></div>
     </div>
   `).join("")}</div>`;
 
+  content.querySelectorAll<HTMLElement>(".site-card[data-site]").forEach((card) => {
+    const name = card.dataset.site!;
+    card.querySelector(".site-header")!.addEventListener("click", () => {
+      if (expandedSites.has(name)) {
+        expandedSites.delete(name);
+      } else {
+        expandedSites.add(name);
+      }
+      toggleBreakdown(card, name);
+    });
+    if (expandedSites.has(name)) toggleBreakdown(card, name);
+    if (name === focusSite) {
+      card.classList.add("focused");
+      card.scrollIntoView({ block: "start" });
+    }
+  });
+
   sites.forEach((site, i) => {
     const canvas = document.getElementById(`chart-${i}`) as HTMLCanvasElement;
     if (canvas && site.series.length > 0) {
@@ -266,6 +924,71 @@ function renderSites(sites: SiteData[]) {
   });
 }
 
+async function toggleBreakdown(card: HTMLElement, site: string) {
+  const el = card.querySelector<HTMLElement>(".site-breakdown")!;
+  el.hidden = !expandedSites.has(site);
+  if (el.hidden) return;
+
+  if (!el.innerHTML) el.innerHTML = `<div class="skeleton-line name"></div>`;
+  try {
+    const breakdown = await invoke<Breakdown>("fetch_site_breakdown", { site });
+    const list = (title: string, rows: DimensionCount[]) => `
+      <div class="breakdown-list">
+        <div class="stat-label">${title}</div>
+        ${rows.slice(0, 5).map((row) => `
+          <div class="breakdown-row">
+            <span class="breakdown-value" title="${escapeAttr(row.value)}">${escapeHtml(row.value)}</span>
+            <span>${formatNumber(row.visits)}</span>
+          </div>
+        `).join("") || `<div class="breakdown-row">–</div>`}
+      </div>
+    `;
+    el.innerHTML = list("Countries", breakdown.countries) + list("Referrers", breakdown.referrers) + list("Paths", breakdown.paths);
+  } catch (e) {
+    el.innerHTML = `<div class="error-message">${escapeHtml(errorMessage(e))}</div>`;
+  }
+  el.insertAdjacentHTML("afterbegin", siteActions(site));
+  el.querySelectorAll<HTMLButtonElement>("[data-site-action]").forEach((btn) => {
+    btn.addEventListener("click", async () => {
+      const pinned = cachedData?.find((s) => s.name === site)?.pinned ?? false;
+      try {
+        if (btn.dataset.siteAction === "pin") {
+          await invoke("set_site_pinned", { site, pinned: !pinned });
+        } else {
+          expandedSites.delete(site);
+          await invoke("set_site_hidden", { site, hidden: true });
+        }
+        await loadAnalytics();
+      } catch (e) {
+        el.insertAdjacentHTML("beforeend", `<div class="error-message">${escapeHtml(errorMessage(e))}</div>`);
+      }
+    });
+  });
+}
+
+function siteActions(site: string) {
+  const pinned = cachedData?.find((s) => s.name === site)?.pinned ?? false;
+  return `
+    <div class="site-actions">
+      <button class="period-btn" data-site-action="pin">${pinned ? "Unpin" : "Pin to Top"}</button>
+      <button class="period-btn" data-site-action="hide" title="Show it again from settings">Hide</button>
+    </div>
+  `;
+}
+
+function accountTag(account: AccountStyle | null) {
+  if (!account) return "";
+  const style = account.color ? ` style="--account-color: ${escapeAttr(account.color)}"` : "";
+  return `<span class="account-tag"${style}>${escapeHtml(account.label)}</span> `;
+}
+
+function changeBadge(pct: number | null) {
+  const text = formatChange(pct);
+  if (!text) return "";
+  const dir = pct! >= 0.5 ? "up" : pct! <= -0.5 ? "down" : "flat";
+  return ` <span class="site-change ${dir}" title="Visits vs previous period">${text}</span>`;
+}
+
 function externalTooltip(context: { chart: Chart; tooltip: TooltipModel<"bar"> }) {
   const { chart, tooltip } = context;
   const container = chart.canvas.parentNode as HTMLElement;
@@ -291,6 +1014,7 @@ function externalTooltip(context: { chart: Chart; tooltip: TooltipModel<"bar"> }
   const idx = tooltip.dataPoints[0].dataIndex;
   const visits = chart.data.datasets[0].data[idx] as number;
   const extra = chart.data.datasets[1].data[idx] as number;
+  const bots = chart.data.datasets[2]?.data[idx] as number | undefined;
   const label = chart.data.labels?.[idx] ?? "";
 
   el.innerHTML = `
@@ -305,6 +1029,11 @@ function externalTooltip(context: { chart: Chart; tooltip: TooltipModel<"bar"> }
       <span class="tt-label">Page Views</span>
       <span class="tt-val">${visits + extra}</span>
     </div>
+    ${bots === undefined ? "" : `<div class="tt-row">
+      <span class="tt-dot bots"></span>
+      <span class="tt-label">Bot Visits</span>
+      <span class="tt-val">${bots}</span>
+    </div>`}
   `;
 
   el.style.opacity = "1";
@@ -319,9 +1048,10 @@ function externalTooltip(context: { chart: Chart; tooltip: TooltipModel<"bar"> }
 }
 
 function createChart(canvas: HTMLCanvasElement, series: SeriesPoint[]) {
-  const labels = series.map((p) => formatTimestamp(p.timestamp));
+  const labels = series.map((p) => formatTimestamp(p.timestamp) + (p.days ? ` (${p.days}d)` : ""));
   const visitsData = series.map((p) => p.visits);
   const extraViewsData = series.map((p) => Math.max(0, p.page_views - p.visits));
+  const hasBots = series.some((p) => p.bot_visits != null);
 
   const isDark = document.documentElement.classList.contains("dark");
 
@@ -336,6 +1066,7 @@ function createChart(canvas: HTMLCanvasElement, series: SeriesPoint[]) {
           backgroundColor: isDark ? "#ffc400" : "#e6a800",
           borderRadius: 1,
           borderSkipped: false,
+          stack: "humans",
         },
         {
           label: "Extra Views",
@@ -343,7 +1074,17 @@ function createChart(canvas: HTMLCanvasElement, series: SeriesPoint[]) {
           backgroundColor: isDark ? "#ff9500" : "#e67800",
           borderRadius: { topLeft: 1, topRight: 1, bottomLeft: 0, bottomRight: 0 },
           borderSkipped: false,
+          stack: "humans",
         },
+        // Bot visits as their own bar next to the human one
+        ...(hasBots ? [{
+          label: "Bot Visits",
+          data: series.map((p) => p.bot_visits ?? 0),
+          backgroundColor: isDark ? "#6e6e73" : "#aeaeb2",
+          borderRadius: 1,
+          borderSkipped: false,
+          stack: "bots",
+        }] : []),
       ],
     },
     options: {
@@ -386,14 +1127,28 @@ function destroyCharts() {
 async function showSettings() {
   destroyCharts();
   stopRefreshAgoTimer();
+  stopLiveTimer();
+  recordUsage("panel", "settings");
 
   let settings: Settings;
   try {
     settings = await invoke<Settings>("get_settings");
   } catch {
-    settings = { token: "", account_id: "", period: "24h", exclude_bots: true, theme: "auto", refresh_interval: "15m" };
+    settings = { token: "", account_id: "", period: "24h", exclude_bots: true, theme: "auto", refresh_interval: "15m", tray_title: "off", tray_site: "", refresh_hook: "" };
+  }
+
+  const accountStyles = { ...settings.account_styles };
+  const currentStyle = accountStyles[settings.account_id] ?? { label: "", color: "" };
+  const refreshPaused = await invoke<RefreshStatus>("refresh_status").then((status) => status.paused).catch(() => false);
+  const apiUsage = await invoke<ApiUsage>("get_api_usage").catch(() => null);
+  const customInterval = ["5m", "15m", "60m"].includes(settings.refresh_interval || "15m") ? "" : settings.refresh_interval;
+  const trayValue = settings.tray_title === "site" ? `site:${settings.tray_site}` : (settings.tray_title || "off");
+  const traySites = (cachedData ?? []).filter((site) => !site.aggregate).map((site) => site.name);
+  if (settings.tray_title === "site" && settings.tray_site && !traySites.includes(settings.tray_site)) {
+    traySites.push(settings.tray_site);
   }
 
+  const usesApiKey = settings.auth_method === "api_key";
   app.innerHTML = popover(`
     <div class="settings-header">
       <button class="icon-btn" id="back-btn" title="Back">
@@ -406,20 +1161,52 @@ async function showSettings() {
     <div class="content">
       <div class="settings-form">
         <div class="form-group">
-          <label>API Token</label>
-          <input type="password" id="input-token" value="${escapeAttr(settings.token)}" placeholder="Cloudflare API Token" />
+          <label>Authentication</label>
+          <div class="period-selector" id="auth-selector">
+            <button class="period-btn ${usesApiKey ? "" : "active"}" data-auth="token">API Token</button>
+            <button class="period-btn ${usesApiKey ? "active" : ""}" data-auth="api_key" title="Legacy key, sent with the email of its user">Global API Key</button>
+          </div>
+          <input type="password" id="input-token" value="${escapeAttr(settings.token)}" placeholder="${usesApiKey ? "Cloudflare Global API Key" : "Cloudflare API Token"}" />
+          <input type="email" id="input-email" value="${escapeAttr(settings.email || "")}" placeholder="Email of the key's user" ${usesApiKey ? "" : "hidden"} />
         </div>
         <div class="form-group">
           <label>Account ID</label>
+          <select id="account-select" hidden></select>
           <input type="text" id="input-account-id" value="${escapeAttr(settings.account_id)}" placeholder="Cloudflare Account ID" />
+          <div class="form-status" id="credential-status"></div>
+          <div class="account-style">
+            <input type="text" id="input-account-label" value="${escapeAttr(currentStyle.label)}" placeholder="Label, e.g. Work" />
+            <input type="text" id="input-account-color" value="${escapeAttr(currentStyle.color)}" placeholder="Color, e.g. #f38020" />
+          </div>
+        </div>
+        <div class="form-group">
+          <label>Connection</label>
+          <button class="btn btn-secondary" id="self-test-btn">Test Connection</button>
+          <ul class="self-test-steps" id="self-test-steps"></ul>
         </div>
         <div class="form-group">
           <label>Time Period</label>
           <div class="period-selector" id="period-selector">
+            <button class="period-btn ${settings.period === "3h" ? "active" : ""}" data-period="3h">3 Hours</button>
+            <button class="period-btn ${settings.period === "6h" ? "active" : ""}" data-period="6h">6 Hours</button>
             <button class="period-btn ${settings.period === "24h" ? "active" : ""}" data-period="24h">24 Hours</button>
+          </div>
+          <div class="period-selector" id="long-period-selector">
             <button class="period-btn ${settings.period === "7d" ? "active" : ""}" data-period="7d">7 Days</button>
             <button class="period-btn ${settings.period === "30d" ? "active" : ""}" data-period="30d">30 Days</button>
+            <button class="period-btn ${settings.period === "90d" ? "active" : ""}" data-period="90d">90 Days</button>
           </div>
+          <div class="period-selector" id="calendar-period-selector">
+            <button class="period-btn ${settings.period === "today" ? "active" : ""}" data-period="today">Today</button>
+            <button class="period-btn ${settings.period === "this_week" ? "active" : ""}" data-period="this_week">This Week</button>
+            <button class="period-btn ${settings.period === "this_month" ? "active" : ""}" data-period="this_month">This Month</button>
+          </div>
+          <div class="period-selector" id="detail-selector" title="Chart buckets for the 24 hour period">
+            <button class="period-btn ${settings.quarter_hour_24h ? "" : "active"}" data-detail="hour">24h Hourly</button>
+            <button class="period-btn ${settings.quarter_hour_24h ? "active" : ""}" data-detail="15m">24h Every 15 Min</button>
+          </div>
+          <input type="text" id="input-timezone" value="${escapeAttr(settings.timezone && settings.timezone !== "auto" ? settings.timezone : "")}" placeholder="Timezone, auto (${escapeAttr(Intl.DateTimeFormat().resolvedOptions().timeZone)})" />
+          <div class="form-status" id="period-preview"></div>
         </div>
         <div class="form-group">
           <label>Color Scheme</label>
@@ -430,10 +1217,26 @@ async function showSettings() {
           </div>
         </div>
         <div class="form-group">
-          <label>Exclude Bots</label>
+          <label>Bot Traffic</label>
           <div class="period-selector" id="bots-selector">
-            <button class="period-btn ${settings.exclude_bots !== false ? "active" : ""}" data-bots="yes">Yes</button>
-            <button class="period-btn ${settings.exclude_bots === false ? "active" : ""}" data-bots="no">No</button>
+            <button class="period-btn ${!settings.split_bots && settings.exclude_bots !== false ? "active" : ""}" data-bots="yes">Exclude</button>
+            <button class="period-btn ${!settings.split_bots && settings.exclude_bots === false ? "active" : ""}" data-bots="no">Include</button>
+            <button class="period-btn ${settings.split_bots ? "active" : ""}" data-bots="split" title="Chart bot visits next to human ones">Separate</button>
+          </div>
+        </div>
+        <div class="form-group">
+          <label>Only Count Traffic From</label>
+          <input type="text" id="input-filter-country" value="${escapeAttr(settings.filters?.country || "")}" placeholder="Country code, e.g. DE" maxlength="2" />
+          <select id="filter-device-select">
+            ${[["", "Any device"], ["desktop", "Desktop"], ["mobile", "Mobile"], ["tablet", "Tablet"]].map(([value, label]) => `<option value="${value}" ${(settings.filters?.device_type || "") === value ? "selected" : ""}>${label}</option>`).join("")}
+          </select>
+          <input type="text" id="input-filter-host" value="${escapeAttr(settings.filters?.host || "")}" placeholder="Hostname, e.g. blog.example.com" />
+        </div>
+        <div class="form-group">
+          <label>All Sites Overview</label>
+          <div class="period-selector" id="all-sites-selector">
+            <button class="period-btn ${settings.show_all_sites !== false ? "active" : ""}" data-allsites="on">On</button>
+            <button class="period-btn ${settings.show_all_sites === false ? "active" : ""}" data-allsites="off">Off</button>
           </div>
         </div>
         <div class="form-group">
@@ -443,26 +1246,198 @@ async function showSettings() {
             <button class="period-btn ${(settings.refresh_interval || "15m") === "15m" ? "active" : ""}" data-refresh="15m">15 Min</button>
             <button class="period-btn ${(settings.refresh_interval || "15m") === "60m" ? "active" : ""}" data-refresh="60m">1 Hour</button>
           </div>
+          <div class="period-selector" id="background-selector">
+            <button class="period-btn ${refreshPaused ? "" : "active"}" data-background="on">Running</button>
+            <button class="period-btn ${refreshPaused ? "active" : ""}" data-background="off">Paused</button>
+          </div>
+          <input type="text" id="input-refresh-custom" value="${escapeAttr(customInterval)}" placeholder="Custom, e.g. 2m or 1h30m" />
+          ${apiUsage ? `<div class="form-status">${apiUsage.calls} of ${apiUsage.limit} GraphQL calls in the last ${apiUsage.window_secs / 60} minutes${apiUsage.throttled ? ", refreshing less often to stay under the limit" : ""}</div>` : ""}
+          <div class="form-status invalid" id="save-error"></div>
+        </div>
+        <div class="form-group">
+          <label>Launch at Login</label>
+          <div class="period-selector" id="login-selector">
+            <button class="period-btn ${settings.launch_at_login ? "active" : ""}" data-login="on">On</button>
+            <button class="period-btn ${settings.launch_at_login ? "" : "active"}" data-login="off">Off</button>
+          </div>
+        </div>
+        <div class="form-group">
+          <label>Global Shortcut</label>
+          <input type="text" id="input-shortcut" value="${escapeAttr(settings.global_shortcut ?? "")}" placeholder="e.g. Alt+Shift+F, empty to disable" />
+          <div class="form-status" id="shortcut-status"></div>
+        </div>
+        <div class="form-group">
+          <label>Check for Updates</label>
+          <div class="period-selector" id="updates-selector">
+            <button class="period-btn ${(settings.update_check_interval || "24h") === "24h" ? "active" : ""}" data-updates="24h">Daily</button>
+            <button class="period-btn ${settings.update_check_interval === "168h" ? "active" : ""}" data-updates="168h">Weekly</button>
+            <button class="period-btn ${settings.update_check_interval === "off" ? "active" : ""}" data-updates="off">Off</button>
+          </div>
+          <button class="period-btn" id="check-updates-btn">Check Now</button>
+          <div class="form-status" id="update-status"></div>
+        </div>
+        <div class="form-group">
+          <label>Low Data Mode</label>
+          <div class="period-selector" id="low-data-selector">
+            <button class="period-btn ${(settings.low_data_mode || "auto") === "auto" ? "active" : ""}" data-lowdata="auto">Auto</button>
+            <button class="period-btn ${settings.low_data_mode === "on" ? "active" : ""}" data-lowdata="on">On</button>
+            <button class="period-btn ${settings.low_data_mode === "off" ? "active" : ""}" data-lowdata="off">Off</button>
+          </div>
+          <div class="form-status">Totals only, hourly at most. Auto follows the macOS Low Data Mode of the current network.</div>
+        </div>
+        <div class="form-group">
+          <label>Sort Sites By</label>
+          <select id="sort-select">
+            ${[["visits", "Visits"], ["page_views", "Page views"], ["name", "Name"], ["change", "Largest change"]].map(([value, label]) => `<option value="${value}" ${(settings.sort_by || "visits") === value ? "selected" : ""}>${label}</option>`).join("")}
+          </select>
+          <div class="period-selector" id="sort-direction-selector">
+            <button class="period-btn ${(settings.sort_direction || "desc") === "desc" ? "active" : ""}" data-sortdir="desc">Descending</button>
+            <button class="period-btn ${settings.sort_direction === "asc" ? "active" : ""}" data-sortdir="asc">Ascending</button>
+          </div>
+        </div>
+        <div class="form-group">
+          <label>Menu Bar Title</label>
+          <select id="tray-title-select">
+            <option value="off" ${trayValue === "off" ? "selected" : ""}>Off</option>
+            <option value="total" ${trayValue === "total" ? "selected" : ""}>Total visits</option>
+            ${traySites.map((name) => `<option value="site:${escapeAttr(name)}" ${trayValue === `site:${name}` ? "selected" : ""}>${escapeHtml(name)}</option>`).join("")}
+          </select>
+          <div class="period-selector" id="tray-icon-selector" title="The sparkline follows the site above, or the first pinned site">
+            <button class="period-btn ${settings.tray_sparkline ? "" : "active"}" data-trayicon="trend">Trend Icon</button>
+            <button class="period-btn ${settings.tray_sparkline ? "active" : ""}" data-trayicon="sparkline">24h Sparkline</button>
+          </div>
+        </div>
+        ${settings.hidden_sites?.length ? `<div class="form-group">
+          <label>Hidden Sites</label>
+          <div class="hidden-sites">
+            ${settings.hidden_sites.map((name) => `<button class="period-btn" data-unhide="${escapeAttr(name)}" title="Show again">${escapeHtml(name)}</button>`).join("")}
+          </div>
+        </div>` : ""}
+        <div class="form-group">
+          <label>Export</label>
+          <div class="export-actions">
+            <button class="period-btn" data-export="csv">CSV</button>
+            <button class="period-btn" data-export="json">JSON</button>
+          </div>
+        </div>
+        <div class="form-group">
+          <label>Run After Refresh</label>
+          <input type="text" id="input-refresh-hook" value="${escapeAttr(settings.refresh_hook || "")}" placeholder="Shell command, receives JSON on stdin" />
+        </div>
+        <div class="form-group">
+          <label>Network</label>
+          <input type="text" id="input-proxy-url" value="${escapeAttr(settings.proxy_url || "")}" placeholder="Proxy, e.g. http://proxy:3128 or socks5://proxy:1080" />
+          <input type="text" id="input-ca-cert" value="${escapeAttr(settings.ca_cert_path || "")}" placeholder="Extra root certificate (PEM file path)" />
+        </div>
+        <div class="form-group">
+          <label>App Data</label>
+          <div class="export-actions">
+            <button class="period-btn" data-reveal="reveal_app_data">Open Folder</button>
+            <button class="period-btn" data-reveal="reveal_settings_file">Settings File</button>
+            <button class="period-btn" data-reveal="open_log_folder">Logs</button>
+          </div>
+          <button class="period-btn" id="copy-logs-btn" title="Copy recent log lines to attach to a bug report">Copy Recent Logs</button>
         </div>
       </div>
     </div>
   `);
 
+  // The auth method, key and email currently in the form, as the credential commands take them
+  function enteredCredentials() {
+    return {
+      token: (document.getElementById("input-token") as HTMLInputElement).value.trim(),
+      authMethod: document.querySelector("#auth-selector .period-btn.active")?.getAttribute("data-auth") || "token",
+      email: (document.getElementById("input-email") as HTMLInputElement).value.trim(),
+    };
+  }
+
   async function autoSave() {
-    const token = (document.getElementById("input-token") as HTMLInputElement).value.trim();
+    const { token, authMethod, email } = enteredCredentials();
     const accountId = (document.getElementById("input-account-id") as HTMLInputElement).value.trim();
-    const period = document.querySelector("#period-selector .period-btn.active")?.getAttribute("data-period") || "24h";
+    const period = document.querySelector(".period-btn[data-period].active")?.getAttribute("data-period") || "24h";
     const theme = document.querySelector("#theme-selector .period-btn.active")?.getAttribute("data-theme") || "auto";
-    const excludeBots = document.querySelector("#bots-selector .period-btn.active")?.getAttribute("data-bots") === "yes";
-    const refreshInterval = document.querySelector("#refresh-selector .period-btn.active")?.getAttribute("data-refresh") || "15m";
+    const botsMode = document.querySelector("#bots-selector .period-btn.active")?.getAttribute("data-bots");
+    const excludeBots = botsMode !== "no";
+    const showAllSites = document.querySelector("#all-sites-selector .period-btn.active")?.getAttribute("data-allsites") !== "off";
+    const customRefresh = (document.getElementById("input-refresh-custom") as HTMLInputElement).value.trim();
+    const refreshInterval = customRefresh || document.querySelector("#refresh-selector .period-btn.active")?.getAttribute("data-refresh") || "15m";
+    const quarterHour24h = document.querySelector("#detail-selector .period-btn.active")?.getAttribute("data-detail") === "15m";
+    const sortBy = (document.getElementById("sort-select") as HTMLSelectElement).value;
+    const sortDirection = document.querySelector("#sort-direction-selector .period-btn.active")?.getAttribute("data-sortdir") || "desc";
+    const trayChoice = (document.getElementById("tray-title-select") as HTMLSelectElement).value;
+    const trayTitle = trayChoice.startsWith("site:") ? "site" : trayChoice;
+    const traySite = trayChoice.startsWith("site:") ? trayChoice.substring(5) : "";
+    const traySparkline = document.querySelector("#tray-icon-selector .period-btn.active")?.getAttribute("data-trayicon") === "sparkline";
+    const refreshHook = (document.getElementById("input-refresh-hook") as HTMLInputElement).value.trim();
+    const proxyUrl = (document.getElementById("input-proxy-url") as HTMLInputElement).value.trim();
+    const caCertPath = (document.getElementById("input-ca-cert") as HTMLInputElement).value.trim();
+    const filters: DimensionFilters = {
+      country: (document.getElementById("input-filter-country") as HTMLInputElement).value.trim().toUpperCase(),
+      device_type: (document.getElementById("filter-device-select") as HTMLSelectElement).value,
+      host: (document.getElementById("input-filter-host") as HTMLInputElement).value.trim().toLowerCase(),
+    };
+    const lowDataMode = document.querySelector("#low-data-selector .period-btn.active")?.getAttribute("data-lowdata") || "auto";
+    const timezone = (document.getElementById("input-timezone") as HTMLInputElement).value.trim() || "auto";
+    const updateCheckInterval = document.querySelector("#updates-selector .period-btn.active")?.getAttribute("data-updates") || "24h";
+    if (accountId) {
+      accountStyles[accountId] = {
+        label: (document.getElementById("input-account-label") as HTMLInputElement).value.trim(),
+        color: (document.getElementById("input-account-color") as HTMLInputElement).value.trim(),
+      };
+    }
     try {
       await invoke("save_settings", {
-        settings: { token, account_id: accountId, period, exclude_bots: excludeBots, theme, refresh_interval: refreshInterval },
+        settings: {
+          ...settings,
+          token,
+          auth_method: authMethod,
+          email,
+          account_id: accountId,
+          period,
+          exclude_bots: excludeBots,
+          split_bots: botsMode === "split",
+          show_all_sites: showAllSites,
+          quarter_hour_24h: quarterHour24h,
+          sort_by: sortBy,
+          sort_direction: sortDirection,
+          theme,
+          refresh_interval: refreshInterval,
+          tray_title: trayTitle,
+          tray_site: traySite,
+          tray_sparkline: traySparkline,
+          refresh_hook: refreshHook,
+          account_styles: accountStyles,
+          low_data_mode: lowDataMode,
+          proxy_url: proxyUrl,
+          ca_cert_path: caCertPath,
+          filters,
+          update_check_interval: updateCheckInterval,
+          timezone,
+        },
       });
+      document.getElementById("save-error")!.textContent = "";
       await startBackgroundRefresh();
-    } catch { /* ignore save errors silently */ }
+    } catch (e) {
+      document.getElementById("save-error")!.textContent = errorMessage(e);
+    }
   }
 
+  document.querySelectorAll<HTMLButtonElement>("[data-unhide]").forEach((btn) => {
+    btn.addEventListener("click", async () => {
+      const site = btn.dataset.unhide!;
+      try {
+        await invoke("set_site_hidden", { site, hidden: false });
+        settings.hidden_sites = settings.hidden_sites?.filter((name) => name !== site);
+        btn.remove();
+        // Hidden sites weren't fetched, so the cached results don't have it
+        cachedData = null;
+        await invoke("fetch_analytics", { force: true });
+      } catch (e) {
+        document.getElementById("save-error")!.textContent = errorMessage(e);
+      }
+    });
+  });
+
   document.getElementById("back-btn")!.addEventListener("click", () => {
     if (settings.token && settings.account_id) {
       showDashboard();
@@ -471,15 +1446,208 @@ async function showSettings() {
 
   document.querySelectorAll<HTMLButtonElement>(".period-selector .period-btn").forEach((btn) => {
     btn.addEventListener("click", () => {
-      btn.parentElement!.querySelectorAll(".period-btn").forEach((b) => b.classList.remove("active"));
+      // Rolling and calendar periods sit in two rows but only one can be active
+      const group = btn.dataset.period ? document.querySelectorAll(".period-btn[data-period]") : btn.parentElement!.querySelectorAll(".period-btn");
+      group.forEach((b) => b.classList.remove("active"));
       btn.classList.add("active");
       if (btn.dataset.theme) applyTheme(btn.dataset.theme);
+      if (btn.dataset.period) {
+        recordUsage("period", btn.dataset.period);
+        updatePeriodPreview();
+      }
+      if (btn.dataset.detail) updatePeriodPreview();
+      if (btn.dataset.refresh) (document.getElementById("input-refresh-custom") as HTMLInputElement).value = "";
+      if (btn.dataset.login) {
+        const enabled = btn.dataset.login === "on";
+        invoke("set_launch_at_login", { enabled })
+          .then(() => { settings.launch_at_login = enabled; })
+          .catch((e) => { document.getElementById("save-error")!.textContent = errorMessage(e); });
+        return;
+      }
+      if (btn.dataset.auth) {
+        const apiKey = btn.dataset.auth === "api_key";
+        (document.getElementById("input-token") as HTMLInputElement).placeholder = apiKey ? "Cloudflare Global API Key" : "Cloudflare API Token";
+        document.getElementById("input-email")!.hidden = !apiKey;
+        autoSave();
+        verifyCredentials();
+        loadAccounts();
+        return;
+      }
+      if (btn.dataset.background) {
+        invoke(btn.dataset.background === "on" ? "resume_background_refresh" : "pause_background_refresh").catch(() => { /* ignore */ });
+        return;
+      }
       autoSave();
     });
   });
 
-  document.getElementById("input-token")!.addEventListener("change", () => autoSave());
-  document.getElementById("input-account-id")!.addEventListener("change", () => autoSave());
+  document.querySelectorAll<HTMLButtonElement>(".export-actions .period-btn").forEach((btn) => {
+    btn.addEventListener("click", async () => {
+      const label = btn.textContent;
+      try {
+        if (btn.dataset.reveal) {
+          await invoke(btn.dataset.reveal);
+        } else {
+          await invoke("export_analytics", { format: btn.dataset.export });
+        }
+      } catch (e) {
+        btn.textContent = "Failed";
+        btn.title = errorMessage(e);
+        setTimeout(() => { btn.textContent = label; }, 2000);
+      }
+    });
+  });
+
+  async function updatePeriodPreview() {
+    const period = document.querySelector(".period-btn[data-period].active")?.getAttribute("data-period") || "24h";
+    const timezone = (document.getElementById("input-timezone") as HTMLInputElement).value.trim() || "auto";
+    const quarterHour24h = document.querySelector("#detail-selector .period-btn.active")?.getAttribute("data-detail") === "15m";
+    try {
+      const preview = await invoke<TimeRangePreview>("preview_time_range", { period, timezone, quarterHour24h });
+      document.getElementById("period-preview")!.textContent = `Queries ${preview.description}`;
+    } catch { /* preview is informational only */ }
+  }
+
+  async function verifyCredentials() {
+    const status = document.getElementById("credential-status")!;
+    const credentials = enteredCredentials();
+    const accountId = (document.getElementById("input-account-id") as HTMLInputElement).value.trim();
+    if (!credentials.token) {
+      status.textContent = "";
+      return;
+    }
+    status.className = "form-status";
+    status.textContent = credentials.authMethod === "api_key" ? "Checking API key…" : "Checking token…";
+    try {
+      const check = await invoke<CredentialCheck>("verify_credentials", { ...credentials, accountId });
+      status.className = `form-status ${check.status === "valid" ? "valid" : "invalid"}`;
+      status.textContent = check.message;
+    } catch (e) {
+      status.className = "form-status invalid";
+      status.textContent = errorMessage(e);
+    }
+  }
+
+  function showAccountStyle(accountId: string) {
+    const style = accountStyles[accountId] ?? { label: "", color: "" };
+    (document.getElementById("input-account-label") as HTMLInputElement).value = style.label;
+    (document.getElementById("input-account-color") as HTMLInputElement).value = style.color;
+  }
+
+  async function loadAccounts() {
+    const select = document.getElementById("account-select") as HTMLSelectElement;
+    const credentials = enteredCredentials();
+    let accounts: Account[] = [];
+    if (credentials.token) {
+      try {
+        accounts = await invoke<Account[]>("list_accounts", credentials);
+      } catch { /* token may lack account read access; keep manual entry */ }
+    }
+    const current = (document.getElementById("input-account-id") as HTMLInputElement).value.trim();
+    select.innerHTML = `<option value="">Choose an account…</option>` + accounts
+      .map((a) => `<option value="${escapeAttr(a.id)}" ${a.id === current ? "selected" : ""}>${escapeHtml(a.name)}</option>`)
+      .join("");
+    select.hidden = accounts.length === 0;
+  }
+
+  document.getElementById("self-test-btn")!.addEventListener("click", async () => {
+    const btn = document.getElementById("self-test-btn") as HTMLButtonElement;
+    const list = document.getElementById("self-test-steps")!;
+    btn.disabled = true;
+    btn.textContent = "Testing…";
+    try {
+      const report = await invoke<SelfTestReport>("run_self_test");
+      list.innerHTML = report.steps
+        .map((step) => `<li class="${step.status}"><span>${escapeHtml(step.name)}</span> <span class="step-detail">${escapeHtml(step.detail)}</span></li>`)
+        .join("");
+    } catch (e) {
+      list.innerHTML = `<li class="fail">${escapeHtml(errorMessage(e))}</li>`;
+    } finally {
+      btn.disabled = false;
+      btn.textContent = "Test Connection";
+    }
+  });
+
+  document.getElementById("account-select")!.addEventListener("change", (e) => {
+    const id = (e.target as HTMLSelectElement).value;
+    if (!id) return;
+    (document.getElementById("input-account-id") as HTMLInputElement).value = id;
+    showAccountStyle(id);
+    autoSave();
+    verifyCredentials();
+  });
+
+  document.getElementById("input-token")!.addEventListener("change", () => { autoSave(); verifyCredentials(); loadAccounts(); });
+  document.getElementById("input-email")!.addEventListener("change", () => { autoSave(); verifyCredentials(); loadAccounts(); });
+  document.getElementById("input-account-id")!.addEventListener("change", (e) => {
+    showAccountStyle((e.target as HTMLInputElement).value.trim());
+    autoSave();
+    verifyCredentials();
+  });
+  document.getElementById("input-account-label")!.addEventListener("change", () => autoSave());
+  document.getElementById("input-account-color")!.addEventListener("change", () => autoSave());
+  document.getElementById("sort-select")!.addEventListener("change", () => autoSave());
+  document.getElementById("input-filter-country")!.addEventListener("change", () => autoSave());
+  document.getElementById("filter-device-select")!.addEventListener("change", () => autoSave());
+  document.getElementById("input-filter-host")!.addEventListener("change", () => autoSave());
+  document.getElementById("tray-title-select")!.addEventListener("change", () => autoSave());
+  document.getElementById("input-refresh-hook")!.addEventListener("change", () => autoSave());
+  document.getElementById("input-shortcut")!.addEventListener("change", async (e) => {
+    const shortcut = (e.target as HTMLInputElement).value.trim();
+    const status = document.getElementById("shortcut-status")!;
+    try {
+      await invoke("set_global_shortcut", { shortcut });
+      settings.global_shortcut = shortcut;
+      status.className = "form-status";
+      status.textContent = "";
+    } catch (err) {
+      status.className = "form-status invalid";
+      status.textContent = errorMessage(err);
+    }
+  });
+  document.getElementById("check-updates-btn")!.addEventListener("click", async () => {
+    const status = document.getElementById("update-status")!;
+    status.className = "form-status";
+    status.textContent = "Checking…";
+    try {
+      const update = await invoke<UpdateInfo | null>("check_for_updates");
+      if (update) availableUpdate = update;
+      status.textContent = update
+        ? `Version ${update.version} is available, install it from the panel or the tray menu.`
+        : "You're on the latest version.";
+    } catch (err) {
+      status.className = "form-status invalid";
+      status.textContent = errorMessage(err);
+    }
+  });
+  document.getElementById("input-timezone")!.addEventListener("change", () => {
+    updatePeriodPreview();
+    autoSave();
+  });
+  document.getElementById("copy-logs-btn")!.addEventListener("click", async (e) => {
+    const btn = e.target as HTMLButtonElement;
+    const label = btn.textContent;
+    try {
+      const lines = await invoke<string[]>("get_recent_logs", { lines: 500 });
+      await navigator.clipboard.writeText(lines.join("\n"));
+      btn.textContent = "Copied";
+    } catch (err) {
+      btn.textContent = "Failed";
+      btn.title = errorMessage(err);
+    }
+    setTimeout(() => { btn.textContent = label; }, 2000);
+  });
+  document.getElementById("input-proxy-url")!.addEventListener("change", () => autoSave());
+  document.getElementById("input-ca-cert")!.addEventListener("change", () => autoSave());
+  document.getElementById("input-refresh-custom")!.addEventListener("change", (e) => {
+    if ((e.target as HTMLInputElement).value.trim()) {
+      document.querySelectorAll("#refresh-selector .period-btn").forEach((b) => b.classList.remove("active"));
+    }
+    autoSave();
+  });
+
+  loadAccounts();
+  updatePeriodPreview();
 }
 
 function escapeHtml(s: string): string {
@@ -1,5 +1,5 @@
 import { describe, it, expect } from "vitest";
-import { formatNumber, formatTimestamp, escapeAttr } from "./utils";
+import { formatNumber, formatChange, formatTimestamp, escapeAttr, vitalRating, formatVital, errorMessage } from "./utils";
 
 describe("formatNumber", () => {
   it("returns plain number below 1000", () => {
@@ -21,6 +21,23 @@ describe("formatNumber", () => {
   });
 });
 
+describe("formatChange", () => {
+  it("returns empty string without a previous period", () => {
+    expect(formatChange(null)).toBe("");
+    expect(formatChange(undefined)).toBe("");
+  });
+
+  it("formats increases and decreases with arrows", () => {
+    expect(formatChange(12.4)).toBe("▲ 12%");
+    expect(formatChange(-5.6)).toBe("▼ 6%");
+  });
+
+  it("formats no change as 0%", () => {
+    expect(formatChange(0)).toBe("0%");
+    expect(formatChange(0.3)).toBe("0%");
+  });
+});
+
 describe("formatTimestamp", () => {
   it("formats daily timestamps (YYYY-MM-DD) by stripping year", () => {
     expect(formatTimestamp("2024-01-15")).toBe("01-15");
@@ -38,6 +55,12 @@ describe("formatTimestamp", () => {
     expect(formatTimestamp("2024-01-15T00:00:00Z")).toBe(expected("2024-01-15T00:00:00Z"));
   });
 
+  it("keeps the wall-clock time of timestamps with an offset", () => {
+    expect(formatTimestamp("2024-01-15T15:30:00+05:30")).toBe("15:30");
+    expect(formatTimestamp("2024-01-15T09:00:00-08:00")).toBe("09:00");
+    expect(formatTimestamp("2024-01-15T00:00:00+00:00")).toBe("00:00");
+  });
+
   it("returns short strings as-is", () => {
     expect(formatTimestamp("abc")).toBe("abc");
     expect(formatTimestamp("")).toBe("");
@@ -68,3 +91,43 @@ describe("escapeAttr", () => {
     expect(escapeAttr("")).toBe("");
   });
 });
+
+describe("vitalRating", () => {
+  it("rates values against the web vitals thresholds", () => {
+    expect(vitalRating("lcp", 2500)).toBe("good");
+    expect(vitalRating("lcp", 3000)).toBe("needs-improvement");
+    expect(vitalRating("inp", 650)).toBe("poor");
+    expect(vitalRating("cls", 0.05)).toBe("good");
+    expect(vitalRating("cls", 0.3)).toBe("poor");
+  });
+
+  it("returns empty string without data", () => {
+    expect(vitalRating("lcp", null)).toBe("");
+  });
+});
+
+describe("formatVital", () => {
+  it("formats timings as ms or seconds", () => {
+    expect(formatVital("inp", 184.4)).toBe("184ms");
+    expect(formatVital("lcp", 2450)).toBe("2.5s");
+  });
+
+  it("formats CLS with two decimals", () => {
+    expect(formatVital("cls", 0.04)).toBe("0.04");
+  });
+
+  it("shows a dash without data", () => {
+    expect(formatVital("lcp", null)).toBe("–");
+  });
+});
+
+describe("errorMessage", () => {
+  it("reads the message of a typed command error", () => {
+    expect(errorMessage({ kind: "auth", message: "Invalid token" })).toBe("Invalid token");
+  });
+
+  it("falls back to the string form", () => {
+    expect(errorMessage("boom")).toBe("boom");
+    expect(errorMessage(null)).toBe("null");
+  });
+});
@@ -4,8 +4,18 @@ export function formatNumber(n: number): string {
   return n.toString();
 }
 
+export function formatChange(pct: number | null | undefined): string {
+  if (pct === null || pct === undefined) return "";
+  const rounded = Math.round(pct);
+  if (rounded > 0) return `▲ ${rounded}%`;
+  if (rounded < 0) return `▼ ${Math.abs(rounded)}%`;
+  return "0%";
+}
+
 export function formatTimestamp(ts: string): string {
   if (ts.length === 10) return ts.substring(5);
+  // Already in the configured timezone, e.g. 2024-01-15T15:30:00+05:30
+  if (/[+-]\d{2}:\d{2}$/.test(ts)) return ts.substring(11, 16);
   if (ts.length > 10) {
     const d = new Date(ts);
     return d.getHours().toString().padStart(2, "0") + ":00";
@@ -16,3 +26,35 @@ export function formatTimestamp(ts: string): string {
 export function escapeAttr(s: string): string {
   return s.replace(/&/g, "&amp;").replace(/"/g, "&quot;").replace(/</g, "&lt;").replace(/>/g, "&gt;");
 }
+
+export type VitalMetric = "lcp" | "inp" | "cls";
+
+// web.dev thresholds: [good up to, needs improvement up to]
+const VITAL_THRESHOLDS: Record<VitalMetric, [number, number]> = {
+  lcp: [2500, 4000],
+  inp: [200, 500],
+  cls: [0.1, 0.25],
+};
+
+export function vitalRating(metric: VitalMetric, value: number | null): "good" | "needs-improvement" | "poor" | "" {
+  if (value === null) return "";
+  const [good, poor] = VITAL_THRESHOLDS[metric];
+  if (value <= good) return "good";
+  if (value <= poor) return "needs-improvement";
+  return "poor";
+}
+
+export function formatVital(metric: VitalMetric, value: number | null): string {
+  if (value === null) return "–";
+  if (metric === "cls") return value.toFixed(2);
+  if (value >= 1000) return (value / 1000).toFixed(1) + "s";
+  return Math.round(value) + "ms";
+}
+
+// Commands reject with a FetchError object ({ kind, message, ... })
+export function errorMessage(e: unknown): string {
+  if (typeof e === "object" && e !== null && typeof (e as { message?: unknown }).message === "string") {
+    return (e as { message: string }).message;
+  }
+  return String(e);
+}